@@ -0,0 +1,1320 @@
+//! Command-line parsing and dispatch, shared by the binary and embedders.
+
+use std::{io::{self, Read, Write}, path::PathBuf, sync::Arc};
+use serde_json::json;
+use anyhow::{Result, Context as _};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::{env::Shells, ArgValueCandidates, CompletionCandidate};
+use crate::config_file::{default_config_path, env_overrides, find_root, load_config_file, load_root_config, resolve_config, ConfigFile};
+use crate::{copy, init, integrity, links, manifest, prune, recent, rules, search, tags};
+use crate::dedupe::{group_duplicates, remove_duplicate, scan_archive};
+use crate::index::{read_index, search_index};
+use crate::journal::undo_last;
+use crate::status::{project_status, set_project_status, ProjectActivity, ProjectStatus};
+use crate::hooks::{default_hooks_dir, ScriptHooks};
+use crate::output::Printer;
+use crate::report::{render_report, ReportFormat};
+use crate::date_prefix::DatePrefixes;
+use crate::links::LinkKind;
+use crate::{add_note, move_into_project, SlugpmError, archive_append_outcome, archive_many, check_title, create_project_dir_with, slugify_file_name, slugify_with, ArchiveOutcome,
+            create_projects_multi_with, expand_response_files, find_project, list_projects, project_dir_for, project_slugs, project_title, rename_project, resolve_project, restore_target, today_note, trash_target, read_input_text, read_target_list, resolve_append_target, untitled_name, normalize_title, parse_project_name, ParsedName, Config, ConflictPolicy, DryRunFileOps, EmojiMode, FallbackSlug, FileOps, NewlinePolicy, OnEmpty, DEFAULT_SMALL_WORDS, DEFAULT_STOPWORDS, Transliteration, ProjectSort, RealFileOps};
+
+#[derive(Parser, Debug)]
+#[command(name = "slugpm", version, about = "Project slugs + archiving")]
+struct Cli {
+    /// Subcommands. If omitted, defaults to `create`.
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
+    #[command(flatten)]
+    create: CreateArgs,
+
+    #[command(flatten)]
+    global: GlobalArgs,
+}
+
+/// Layout settings accepted by every command; each overrides the config file.
+#[derive(Args, Debug, Default)]
+struct GlobalArgs {
+    /// Config file to read [default: `~/.config/slugpm/config.toml`, if it exists]
+    #[arg(long, global = true, value_name = "PATH", env = "SLUGPM_CONFIG")]
+    config: Option<PathBuf>,
+    /// Use the project root of this `[workspaces.<NAME>]` config table
+    #[arg(long, short = 'w', global = true, value_name = "NAME", env = "SLUGPM_WORKSPACE")]
+    workspace: Option<String>,
+    /// Directory projects are created in [default: project]
+    #[arg(long, global = true, value_name = "DIR")]
+    project_root: Option<PathBuf>,
+    /// Name of archive directories [default: archive]
+    #[arg(long, global = true, value_name = "NAME")]
+    archive_dir_name: Option<String>,
+    /// strftime-style format for dates in project names [default: %Y-%m-%d]
+    #[arg(long, global = true, value_name = "FORMAT")]
+    date_format: Option<String>,
+    /// Joins the words of a slug [default: -]
+    #[arg(long, global = true, value_name = "SEP")]
+    slug_separator: Option<String>,
+    /// Longest slug in bytes, cut at a word boundary
+    #[arg(long, global = true, value_name = "N")]
+    slug_max_len: Option<usize>,
+    /// Keep the title's capitals in the slug
+    #[arg(long, global = true, overrides_with = "no_slug_keep_case")]
+    slug_keep_case: bool,
+    /// Lowercase the slug even if the config file or environment keeps case
+    #[arg(long, global = true, overrides_with = "slug_keep_case")]
+    no_slug_keep_case: bool,
+    /// Leave these comma-separated words out of slugs [default list: a, an, and, the, ...]
+    #[arg(long, global = true, value_name = "WORDS", num_args = 0..=1, require_equals = true, value_delimiter = ',')]
+    slug_stopwords: Option<Vec<String>>,
+    /// How non-ASCII letters are spelled in slugs [default: ascii]
+    #[arg(long, global = true, value_enum, value_name = "MODE")]
+    slug_transliteration: Option<Transliteration>,
+    /// `git init` new projects, and commit archives and restores to the repository holding the project root
+    #[arg(long, global = true)]
+    git: bool,
+    /// Print one JSON object per result instead of plain lines
+    #[arg(long, global = true)]
+    json: bool,
+    /// Show what would change (on stderr) without changing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+impl GlobalArgs {
+    /// Built-in defaults, then the config file, the environment, the workspace and these flags
+    /// (see [`resolve_config`]).
+    fn base_config(&self, ops: &dyn FileOps) -> Result<Config> {
+        // An explicit config file has to exist; the default one is optional.
+        let path = self.config.clone().or_else(|| default_config_path().filter(|p| ops.exists(p)));
+        let mut file = match path {
+            Some(path) => load_config_file(&path, ops)?,
+            None => ConfigFile::default(),
+        };
+        // The tree's own settings, found by walking up from the working directory like git.
+        if let Some(root) = std::env::current_dir().ok().and_then(|cwd| find_root(&cwd, ops)) {
+            file = file.layered(&load_root_config(&root, ops)?);
+        }
+        let env = env_overrides(|name| std::env::var(name).ok())?;
+        let flags = ConfigFile {
+            project_root: self.project_root.clone(),
+            archive_dir_name: self.archive_dir_name.clone(),
+            date_format: self.date_format.clone(),
+            date_prefixes: None,
+            slug_separator: self.slug_separator.clone(),
+            slug_max_len: self.slug_max_len,
+            slug_keep_case: if self.slug_keep_case { Some(true) } else if self.no_slug_keep_case { Some(false) } else { None },
+            slug_stopwords: self.slug_stopwords.clone().map(|words| {
+                if words.is_empty() { DEFAULT_STOPWORDS.iter().map(|w| w.to_string()).collect() } else { words }
+            }),
+            slug_transliteration: self.slug_transliteration,
+            dated: None,
+            ids: None,
+            dated_archive: None,
+            git: self.git.then_some(true),
+            templates_dir: None,
+            rules: None,
+            workspaces: Default::default(),
+        };
+        let mut cfg = resolve_config(&file, &env, self.workspace.as_deref(), &flags)?;
+        cfg.journal = !self.dry_run;
+        cfg.track_recent = !self.dry_run;
+        cfg.manifest = !self.dry_run;
+        cfg.git &= !self.dry_run;
+        if let Some(dir) = default_hooks_dir().filter(|_| !self.dry_run) {
+            cfg.hooks = Arc::new(ScriptHooks::new(dir));
+        }
+        #[cfg(feature = "progress")]
+        {
+            cfg.progress = Arc::new(crate::progress::BarProgress::default());
+        }
+        Ok(cfg)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Create `project/<slug>` from a title (the default when no subcommand is given).
+    Create {
+        #[command(flatten)]
+        args: CreateArgs,
+    },
+
+    /// Archive a file or directory.
+    ///
+    /// - `slugpm archive <path>`:
+    ///   * if <path> is a file: moves it to `<parent>/archive/<filename>`
+    ///   * if <path> is a dir:  moves it to `<parent>/../archive/<dirname>`
+    /// - `slugpm archive --append <file>` (or the older `slugpm archive <file> -`):
+    ///   append STDIN to `<parent>/archive/<filename>` (creating it if needed)
+    /// - `find . -name '*.log' | slugpm archive --stdin`: archive every path on STDIN
+    ///
+    /// Each target succeeds or fails on its own; the exit status is nonzero only when every
+    /// target failed, or with `--fail-fast` at the first failure.
+    Archive {
+        /// Files or directories to archive; `@<file>` reads more targets from <file>, one per line
+        #[arg(required_unless_present_any = ["project", "stdin", "status", "list"])]
+        targets: Vec<PathBuf>,
+        /// List what the archive directory's manifest records instead of archiving: the archive
+        /// next to the project root, or given a directory, its archive (or itself, if it is one)
+        #[arg(long, conflicts_with_all = ["stdin", "project", "status", "append", "from", "message"])]
+        list: bool,
+        /// Also archive the paths read from STDIN, one per line (as `fd` or `find` print them)
+        #[arg(long, conflicts_with_all = ["append", "from"])]
+        stdin: bool,
+        /// With --stdin, paths are NUL-separated (`find -print0`, `fd -0`)
+        #[arg(short = '0', long, requires = "stdin")]
+        null: bool,
+        /// Why the targets are being archived, noted in the archive directory's ARCHIVE.md
+        #[arg(short, long, value_name = "REASON", conflicts_with_all = ["append", "from"])]
+        message: Option<String>,
+        /// Stop at the first target that fails, exiting nonzero
+        #[arg(long)]
+        fail_fast: bool,
+        /// Archive up to N targets at once (targets bound for the same archive directory still go one at a time)
+        #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Archive every active project with this status in its metadata (such as `done`)
+        #[arg(long, value_enum, conflicts_with_all = ["append", "from"])]
+        status: Option<ProjectStatus>,
+        /// Archive the project directory with this slug under the project root (repeatable)
+        #[arg(long, value_name = "SLUG", conflicts_with = "append", add = ArgValueCandidates::new(slug_candidates))]
+        project: Vec<String>,
+        /// Append STDIN to the archived file instead of moving
+        #[arg(short, long)]
+        append: bool,
+        /// Append the contents of this file instead of STDIN (implies --append)
+        #[arg(long, value_name = "SOURCE", conflicts_with = "project")]
+        from: Option<PathBuf>,
+        /// With --from, delete SOURCE once it has been appended
+        #[arg(long, requires = "from")]
+        delete_source: bool,
+        /// With --append or --from, skip content the file already ends with or that was
+        /// appended before with this flag (hashes kept in `.<name>.blocks`)
+        #[arg(long)]
+        skip_duplicate: bool,
+        /// What to do when the destination already exists [default: the archive dir's
+        /// `.slugpm-policy`, else error]
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+        /// Overwrite an existing archived item; short for `--on-conflict overwrite`
+        #[arg(short, long, conflicts_with = "on_conflict")]
+        force: bool,
+        /// Write a `<name>.sha256` sidecar for archived files and verify it before overwriting
+        #[arg(long)]
+        protect: bool,
+        /// Merge a directory into an existing archived directory of the same name
+        #[arg(long)]
+        merge: bool,
+        /// Print the source path before the destination, tab-separated
+        #[arg(long)]
+        print_source: bool,
+        /// File under `archive/<CATEGORY>/` (slugified; no path separators)
+        #[arg(long, value_name = "NAME")]
+        category: Option<String>,
+        /// Insert the appended content at the end of the Markdown section under this heading
+        /// (such as "## Log"), adding the heading if the file lacks it
+        #[arg(long, value_name = "HEADING")]
+        under: Option<String>,
+        /// Trailing newline handling for appended content
+        #[arg(long, value_enum, default_value_t)]
+        trailing_newline: NewlinePolicy,
+        /// Hard-link a file into the archive instead of moving it (files only)
+        #[arg(long)]
+        link: bool,
+        /// Record each archived item in the archive's `.index.json`
+        #[arg(long)]
+        index: bool,
+        /// Archive symlinks themselves instead of their targets (a link to a directory goes where the directory would), warning about broken ones
+        #[arg(long)]
+        no_follow: bool,
+        /// Fail a target instead of warning about it
+        #[arg(long)]
+        strict: bool,
+        /// File items under `archive/YYYY/YYYY-MM-DD/` (or `dated_archive = true` in the config file)
+        #[arg(long, conflicts_with_all = ["append", "from"])]
+        dated_archive: bool,
+        /// Pack directories into `archive/<dirname>.tar.zst` instead of moving them
+        #[arg(long, conflicts_with_all = ["merge", "append", "from"])]
+        compress: bool,
+        /// Archive an archive directory, or something already inside one, nesting the archives
+        #[arg(long)]
+        allow_nested: bool,
+    },
+
+    /// Report files with identical contents in an archive directory, keeping the oldest copy.
+    Dedupe {
+        /// Archive directory to scan (recursively)
+        archive_dir: PathBuf,
+        /// Remove the duplicates instead of only reporting them
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Re-hash archived items and report files whose contents no longer match their manifest.
+    Verify {
+        /// Archive directory, or the directory whose archive to check (default: the project root's)
+        dir: Option<PathBuf>,
+    },
+
+    /// Fuzzy-find a project in a terminal picker, then print its path or archive, restore or rename it.
+    #[cfg(feature = "tui")]
+    Pick,
+
+    /// Watch an inbox directory and archive or file what lands in it by rules, printing
+    /// `<source><TAB><dest>` per item.
+    #[cfg(feature = "watch")]
+    Watch {
+        /// The directory to watch
+        inbox: PathBuf,
+        /// `<matcher> -> <action>`, first match wins: matcher `*`, `ext:pdf,epub`,
+        /// `name:Screenshot*` or `age:30d`; action `archive` or `file:<project>[/<subdir>]`
+        #[arg(long, value_name = "RULE", required = true)]
+        rule: Vec<String>,
+        /// Leave items modified less than this many seconds ago for a later pass
+        #[arg(long, value_name = "SECS", default_value_t = 2)]
+        settle: u64,
+        /// Make one pass over the inbox and exit instead of watching
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Delete (or compress) archived items not modified for a while.
+    Prune {
+        /// Archive directories to prune [default: the archive beside the project root]
+        archive_dirs: Vec<PathBuf>,
+        /// Age past which items are pruned: hours, days or weeks, like `12h`, `90d` or `2w`
+        #[arg(long, value_name = "AGE", value_parser = prune::parse_age)]
+        older_than: std::time::Duration,
+        /// Pack old directories into `<name>.tar.zst` instead of deleting them
+        #[arg(long)]
+        compress: bool,
+    },
+
+    /// Print the path of the project that best matches a fuzzy title, or open it in an editor.
+    Open {
+        /// Part of the project's title or slug (words are joined with spaces)
+        #[arg(required = true, add = ArgValueCandidates::new(slug_candidates))]
+        query: Vec<String>,
+        /// Open the project in `$VISUAL` (or `$EDITOR`) instead of printing its path
+        #[arg(long)]
+        edit: bool,
+    },
+
+    /// Append a timestamped line to a project's `log.md`.
+    Note {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        /// The note (words are joined with spaces); read from STDIN when piped
+        text: Vec<String>,
+    },
+
+    /// Print the path of a project's note for today, `notes/YYYY-MM-DD.md`, creating it first.
+    Today {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        /// Start a new note from `templates/<NAME>.md` [default: `templates/today.md`, if it exists]
+        #[arg(long, value_name = "NAME")]
+        template: Option<String>,
+        /// Open the note in `$VISUAL` (or `$EDITOR`) instead of printing its path
+        #[arg(long)]
+        edit: bool,
+    },
+
+    /// List the projects slugpm created, opened, archived or took notes in, most recent first.
+    Recent {
+        /// How many to list
+        #[arg(long, short = 'n', default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Add, remove or list the tags kept in projects' metadata.
+    Tag {
+        #[command(subcommand)]
+        command: TagCmd,
+    },
+
+    /// List the projects under `project/`.
+    List {
+        /// Include archived projects, printing `<slug><TAB>active|archived`
+        #[arg(long)]
+        archived: bool,
+        /// Order of the listing
+        #[arg(long, value_enum, default_value_t)]
+        sort: ProjectSort,
+        /// Only list projects tagged TAG
+        #[arg(long, value_name = "TAG")]
+        tag: Option<String>,
+        /// Only list projects with this status (`archived` lists archived ones)
+        #[arg(long, value_enum)]
+        status: Option<ProjectStatus>,
+    },
+
+    /// Print the projects as a table with their title, slug, created date, status, tags and
+    /// last modification.
+    Report {
+        #[arg(long, value_enum, default_value_t)]
+        format: ReportFormat,
+        /// Include archived projects
+        #[arg(long)]
+        archived: bool,
+        /// Order of the rows
+        #[arg(long, value_enum, default_value_t)]
+        sort: ProjectSort,
+    },
+
+    /// Set up a slugpm root: `project/`, the archive directory and a starter `.slugpm.toml`.
+    Init {
+        /// Directory to set up [default: .]
+        dir: Option<PathBuf>,
+        /// Also create `templates/` and point `templates_dir` at it
+        #[arg(long)]
+        templates: bool,
+        /// Rewrite the `.slugpm.toml` of an existing root
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Record that two projects are related, in both projects' metadata.
+    Link {
+        /// The project the relation is about
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        /// The project it relates to; it records the inverse relation
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        other: String,
+        /// How SLUG relates to OTHER
+        #[arg(long, value_enum, default_value_t)]
+        kind: LinkKind,
+    },
+
+    /// Print a project's links, `<kind><TAB><slug>` per line.
+    Links {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+    },
+
+    /// Record a project's status (active, paused or done) in its metadata.
+    SetStatus {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        #[arg(value_enum)]
+        status: ProjectStatus,
+    },
+
+    /// Move files or directories to the desktop trash instead of an archive.
+    Trash {
+        /// Files or directories to trash
+        #[arg(required = true)]
+        targets: Vec<PathBuf>,
+    },
+
+    /// Count active and archived projects, and show the latest and the long-untouched ones.
+    Status {
+        /// Days without changes after which an active project is listed as untouched
+        #[arg(long, value_name = "DAYS", default_value_t = 30)]
+        stale_days: u32,
+    },
+
+    /// Move an archived file or directory back out of the archive.
+    ///
+    /// - a file in `<parent>/archive/` goes back to `<parent>/<filename>`
+    /// - a directory in `<root>/archive/` goes back to `<root>/project/<dirname>`
+    /// - a compressed `<root>/archive/<dirname>.tar.zst` is unpacked to `<root>/project/<dirname>`
+    Restore {
+        /// Archived file or directory
+        target: PathBuf,
+        /// Restore into this directory instead
+        #[arg(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+    },
+
+    /// Rename a project for a new title, keeping its date prefix and updating its metadata.
+    Rename {
+        /// Current slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        /// The new title (words are joined with spaces)
+        #[arg(required = true)]
+        title: Vec<String>,
+        /// Leave a symlink at the old path pointing to the renamed project
+        #[arg(long)]
+        symlink: bool,
+    },
+
+    /// Move files or directories into a project (or a subdirectory of one, created as needed).
+    Mv {
+        /// Files or directories to move
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// The project's slug, optionally followed by subdirectories (`my-project/refs`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        project: String,
+        /// Copy instead of moving, leaving the sources in place
+        #[arg(long)]
+        copy: bool,
+        /// What to do when the destination already exists [default: the directory's
+        /// `.slugpm-policy`, else error]
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+    },
+
+    /// Move files into projects by the config file's `[rules]`, printing where each went.
+    File {
+        /// Files to file
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Copy instead of moving, leaving the files in place
+        #[arg(long)]
+        copy: bool,
+        /// What to do when the destination already exists [default: the directory's
+        /// `.slugpm-policy`, else error]
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+    },
+
+    /// Revert the most recent create, archive, restore, trash or rename, as recorded in the project root's journal.
+    Undo,
+
+    /// Search an archive's `.index.json` by name or destination.
+    IndexSearch {
+        /// Text to look for, ignoring case
+        term: String,
+        /// Archive directory holding the index, or the directory whose archive to search (default: the project root's)
+        #[arg(long, value_name = "DIR")]
+        archive_dir: Option<PathBuf>,
+    },
+
+    /// Search the projects' Markdown and text files, printing `<path>:<line>:<text>` for each
+    /// matching line.
+    Search {
+        /// Text to look for (words are joined with spaces)
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Search archived projects too
+        #[arg(long)]
+        archived: bool,
+        /// Skip files larger than this many bytes
+        #[arg(long, value_name = "BYTES", default_value_t = search::DEFAULT_MAX_FILE_SIZE)]
+        max_size: u64,
+    },
+
+    /// Print the slug for a title.
+    Slug {
+        /// Title to slugify (words are joined with spaces)
+        #[arg(required = true)]
+        title: Vec<String>,
+        /// Only validate: exit nonzero and print the reason if the slug is empty, too long, or reserved
+        #[arg(long)]
+        check: bool,
+        /// Treat the title as a file name: slugify the stem and keep the extension
+        #[arg(long, conflicts_with = "check")]
+        file_name: bool,
+        /// With --file-name, lowercase the extension (the default)
+        #[arg(long, overrides_with = "no_ext_lower")]
+        ext_lower: bool,
+        /// With --file-name, keep the extension's original case
+        #[arg(long, overrides_with = "ext_lower")]
+        no_ext_lower: bool,
+        /// Drop emoji, or spell out common ones, before slugifying
+        #[arg(long, value_enum, default_value_t)]
+        emoji: EmojiMode,
+        #[command(flatten)]
+        case: CaseArgs,
+    },
+
+    /// Print a project's title: the one in its metadata, else one rebuilt from its slug.
+    ///
+    /// `2025-09-13-my-cool-project` becomes `My Cool Project`.
+    Title {
+        /// Project directories or slugs under the project root
+        #[arg(required = true, add = ArgValueCandidates::new(slug_candidates))]
+        names: Vec<PathBuf>,
+        /// Keep these comma-separated words lowercase inside a title [default: a, an, and, the, ...]
+        #[arg(long, value_name = "WORDS", value_delimiter = ',', conflicts_with = "capitalize_all")]
+        small_words: Option<Vec<String>>,
+        /// Capitalize every word, small ones included
+        #[arg(long)]
+        capitalize_all: bool,
+    },
+
+    /// Print the script that sets up tab completion for a shell.
+    ///
+    /// For example `source <(slugpm completions bash)` in `~/.bashrc`. Project slugs are
+    /// completed for `open`, `rename` and `archive --project`.
+    Completions {
+        #[arg(value_parser = ["bash", "elvish", "fish", "powershell", "zsh"])]
+        shell: String,
+    },
+
+    /// Print the project name excluding a leading YYYY-MM-DD- prefix, one line per directory.
+    ///
+    /// With no directories the names are read from STDIN, one per line (`ls project | slugpm name`).
+    Name {
+        /// Directories whose base names to process
+        dirnames: Vec<PathBuf>,
+        /// Read names from STDIN, one per line, and strip each (the default without directories)
+        #[arg(long, conflicts_with = "dirnames")]
+        stream: bool,
+        /// Strip this date prefix instead of the configured ones: a strftime format (`%Y%m%d`)
+        /// or a regex; repeat for several
+        #[arg(long, value_name = "PATTERN")]
+        date_prefix: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TagCmd {
+    /// Tag a project, printing its tags.
+    Add {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        /// The tag (lowercased; no commas)
+        tag: String,
+    },
+    /// Remove a tag from a project, printing the tags left.
+    Rm {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: String,
+        tag: String,
+    },
+    /// Print a project's tags, one per line, or without a slug every tag with how many
+    /// active projects have it.
+    List {
+        /// Slug of the project (its directory name under `project/`)
+        #[arg(add = ArgValueCandidates::new(slug_candidates))]
+        slug: Option<String>,
+    },
+}
+
+/// Options for `create`, accepted both by the subcommand and by the bare-title default.
+#[derive(Args, Debug)]
+struct CreateArgs {
+    /// Title for the new project (words are joined with spaces); read from STDIN when piped
+    title: Vec<String>,
+    /// Read STDIN line by line, creating a project for each non-empty line
+    #[arg(long, visible_alias = "each-line")]
+    multi: bool,
+    /// What to do when the title produces an empty slug
+    #[arg(long, value_enum, default_value_t)]
+    fallback_slug: FallbackSlug,
+    /// Append a `timestamp<TAB>title<TAB>dir` record to this file for each created project
+    #[arg(long, value_name = "PATH", env = "SLUGPM_PROJECT_LOG")]
+    project_log: Option<PathBuf>,
+    /// If the project directory exists, create `<slug>-2`, `<slug>-3`, ... instead
+    #[arg(long)]
+    unique: bool,
+    /// If the project directory exists, print its path instead of failing
+    #[arg(long, conflicts_with = "unique")]
+    reuse: bool,
+    /// What to do when piped STDIN has no title
+    #[arg(long, value_enum, default_value_t)]
+    on_empty: OnEmpty,
+    /// Drop emoji, or spell out common ones, before slugifying
+    #[arg(long, value_enum, default_value_t)]
+    emoji: EmojiMode,
+    #[command(flatten)]
+    case: CaseArgs,
+    /// Prefix the directory name with today's date (`<date>-<slug>`, date in `--date-format`)
+    #[arg(long)]
+    dated: bool,
+    /// Number the project (`<id>-<slug>`, ID from the tally in the project root; or `ids = true`)
+    #[arg(long)]
+    id: bool,
+    /// Pipe the title to this shell command and use the first line it prints as the slug
+    #[arg(long, value_name = "CMD")]
+    slugify_cmd: Option<String>,
+    /// Show the directory to be created and ask before creating it (assumed yes when STDIN is piped)
+    #[arg(long, conflicts_with = "multi")]
+    confirm: bool,
+    /// Copy the skeleton in `templates/<NAME>/` beside the config file into the new project
+    #[arg(long, value_name = "NAME")]
+    template: Option<String>,
+    /// Only print `<slug><TAB><path>` for the project that would be created
+    #[arg(long, conflicts_with_all = ["multi", "confirm"])]
+    no_create: bool,
+}
+
+/// camelCase splitting, shared by `create` and `slug`.
+#[derive(Args, Debug)]
+struct CaseArgs {
+    /// Split camelCase words before slugifying (`myProject` -> `my-project`)
+    #[arg(long)]
+    split_case: bool,
+    /// With --split-case, keep runs of capitals together (`getHTTPResponse` -> `get-http-response`)
+    #[arg(long, requires = "split_case")]
+    keep_acronyms: bool,
+}
+
+impl CreateArgs {
+    fn config(&self, base: &Config) -> Config {
+        Config { fallback_slug: self.fallback_slug, project_log: self.project_log.clone(), unique: self.unique, reuse: self.reuse, on_empty: self.on_empty, emoji: self.emoji,
+                 split_case: self.case.split_case, keep_acronyms: self.case.keep_acronyms,
+                 slugify_cmd: self.slugify_cmd.clone(), dated: self.dated || base.dated, ids: self.id || base.ids, template: self.template.clone(), ..base.clone() }
+    }
+}
+
+/// Maps the original `archive <file> -` spelling onto `archive --append <file>`:
+/// a lone `-` after the archive target (the third positional, counting the program name) becomes `--append`.
+fn legacy_dash_to_append(args: Vec<String>) -> Vec<String> {
+    let is_archive = args.iter().skip(1).find(|a| !a.starts_with('-')).is_some_and(|a| a == "archive");
+    if !is_archive {
+        return args;
+    }
+    let mut positionals = 0;
+    args.into_iter()
+        .map(|arg| {
+            if arg == "-" && positionals >= 3 {
+                return "--append".to_string();
+            }
+            if !arg.starts_with('-') {
+                positionals += 1;
+            }
+            arg
+        })
+        .collect()
+}
+
+/// Everything `run` needs from the outside world besides its streams.
+pub struct Context<'a> {
+    pub ops: &'a dyn FileOps,
+    /// Whether STDIN is an interactive terminal (i.e. nothing is piped in).
+    pub stdin_is_tty: bool,
+}
+
+impl Context<'static> {
+    /// The real file system and the process's STDIN terminal state.
+    pub fn real() -> Self {
+        Context { ops: &RealFileOps, stdin_is_tty: atty::is(atty::Stream::Stdin) }
+    }
+}
+
+/// Runs slugpm as the binary would, returning the process exit code.
+///
+/// `args` includes the program name first, as with `std::env::args()`. A failing command is
+/// reported on `err` and returns its exit status (see [`SlugpmError::exit_code`]); `Err` only
+/// comes back when that report can't be written.
+///
+/// Nothing process-wide is set up here: the binary installs
+/// [`copy::install_interrupt_handler`] first, so Ctrl-C can stop a copy cleanly.
+pub fn run(args: impl IntoIterator<Item = String>, input: &mut dyn Read, output: &mut dyn Write, err: &mut dyn Write) -> Result<i32> {
+    run_with(args, input, output, err, &Context::real())
+}
+
+/// Like [`run`], but against an explicit [`Context`].
+pub fn run_with(args: impl IntoIterator<Item = String>, input: &mut dyn Read, output: &mut dyn Write, err: &mut dyn Write, ctx: &Context) -> Result<i32> {
+    let args = legacy_dash_to_append(args.into_iter().collect());
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            if !e.use_stderr() {
+                write!(output, "{}", e.render())?;
+                return Ok(e.exit_code());
+            }
+            write!(err, "{}", e.render())?;
+            return Ok(crate::EXIT_INVALID_INPUT);
+        }
+    };
+    let json = cli.global.json;
+    match dispatch(cli, input, output, err, ctx) {
+        Ok(code) => Ok(code),
+        Err(e) => report_error(&e, json, err),
+    }
+}
+
+/// Writes why a command failed to `err` and returns the exit status for it (see
+/// [`SlugpmError::exit_code`]); under `--json`, as an object with the error's kind.
+fn report_error(e: &anyhow::Error, json: bool, err: &mut dyn Write) -> Result<i32> {
+    let (code, kind) = match e.chain().find_map(|c| c.downcast_ref::<SlugpmError>()) {
+        Some(e) => (e.exit_code(), e.kind_name()),
+        None => match e.chain().find_map(|c| c.downcast_ref::<io::Error>()) {
+            Some(e) => crate::io_exit(e.kind()),
+            None => (crate::EXIT_FAILURE, "other"),
+        },
+    };
+    if json {
+        serde_json::to_writer(&mut *err, &json!({ "error": kind, "message": format!("{e:#}"), "code": code }))?;
+        writeln!(err)?;
+    } else {
+        writeln!(err, "error: {e:#}")?;
+    }
+    Ok(code)
+}
+
+/// Runs a parsed command line.
+fn dispatch(cli: Cli, input: &mut dyn Read, output: &mut dyn Write, err: &mut dyn Write, ctx: &Context) -> Result<i32> {
+    let base = cli.global.base_config(ctx.ops)?;
+    let mut out = Printer::new(output, cli.global.json);
+    let command = cli.command.unwrap_or(Cmd::Create { args: cli.create });
+    if !cli.global.dry_run {
+        return execute(command, base, input, &mut out, err, ctx);
+    }
+    let dry = DryRunFileOps::new(ctx.ops);
+    let result = execute(command, base, input, &mut out, err, &Context { ops: &dry, stdin_is_tty: ctx.stdin_is_tty });
+    for action in dry.actions() {
+        writeln!(err, "would {action}")?;
+    }
+    result
+}
+
+/// Runs a parsed command.
+fn execute(command: Cmd, base: Config, input: &mut dyn Read, out: &mut Printer, err: &mut dyn Write, ctx: &Context) -> Result<i32> {
+    match command {
+        Cmd::Create { args } if args.multi => {
+            let buf = read_input_text(input)?;
+            let summary = create_projects_multi_with(&buf, &args.config(&base), ctx.ops)?;
+            for dir in &summary.created {
+                out.emit(dir.display(), || json!({ "action": "create", "slug": dir_name(dir), "path": dir }))?;
+            }
+            for title in &summary.skipped {
+                writeln!(err, "skipped duplicate: {title}")?;
+            }
+            writeln!(err, "created {} project(s), skipped {} duplicate(s)", summary.created.len(), summary.skipped.len())?;
+        }
+        Cmd::Create { args } => {
+            let cfg = args.config(&base);
+            let title = create_title(&args.title, &cfg, input, ctx)?;
+            if args.no_create {
+                let dir = project_dir_for(&title, &cfg, ctx.ops)?;
+                let slug = dir_name(&dir);
+                out.emit(format!("{slug}\t{}", dir.display()), || json!({ "action": "create", "title": title, "slug": slug, "path": dir, "created": false }))?;
+                out.flush()?;
+                return Ok(0);
+            }
+            // With a piped title STDIN is used up, so there's nothing to answer the prompt.
+            if args.confirm && ctx.stdin_is_tty {
+                let proposed = project_dir_for(&title, &cfg, ctx.ops)?;
+                if !ask(&format!("{}\ncreate? [Y/n] ", proposed.display()), input, err)? {
+                    out.flush()?;
+                    return Ok(0);
+                }
+            }
+            let existed = cfg.reuse && ctx.ops.exists(&project_dir_for(&title, &cfg, ctx.ops)?);
+            let dir = create_project_dir_with(&title, &cfg, ctx.ops)?;
+            out.emit(dir.display(), || json!({ "action": "create", "title": title, "slug": dir_name(&dir), "path": dir, "created": !existed }))?;
+        }
+        Cmd::Archive { targets, list, stdin, null, message, fail_fast, jobs, project, append, from, under, delete_source, skip_duplicate, on_conflict, force, protect, merge, print_source, category, trailing_newline, link, index, no_follow, strict, dated_archive, compress, allow_nested, status } => {
+            let ops = ctx.ops;
+            if list {
+                if targets.len() > 1 {
+                    anyhow::bail!("--list takes at most one directory");
+                }
+                let arch_root = archive_root(targets.first(), &base);
+                for entry in manifest::list_archive(&arch_root, ops)? {
+                    let reason = entry.reason.as_deref().unwrap_or("");
+                    let line = format!("{}\t{}\t{}\t{reason}", entry.archived_at, arch_root.join(&entry.path).display(), entry.from.display());
+                    out.emit(line.trim_end(), || json!({ "archived_at": entry.archived_at, "path": arch_root.join(&entry.path), "from": entry.from,
+                                                         "reason": entry.reason, "dir": entry.dir, "compressed": entry.compressed }))?;
+                }
+                out.flush()?;
+                return Ok(0);
+            }
+            let mut targets = expand_response_files(&targets, ops)?;
+            if stdin {
+                targets.extend(read_target_list(input, null)?);
+            }
+            for slug in &project {
+                targets.push(resolve_project(slug, &base, ops)?);
+            }
+            if let Some(status) = status {
+                targets.extend(list_projects(false, ProjectSort::Name, &base, ops)?.into_iter().filter(|p| p.status == status).map(|p| p.path));
+            }
+            let on_conflict = if force { Some(ConflictPolicy::Overwrite) } else { on_conflict };
+            let cfg = Config { archive_message: message, on_conflict, protect, merge, category, trailing_newline, append_under: under, skip_duplicate, link, index, no_follow, strict, fail_fast, jobs: jobs.into(), compress, allow_nested, dated_archive: dated_archive || base.dated_archive, ..base };
+
+            if append || from.is_some() {
+                let [target] = targets.as_slice() else { anyhow::bail!("--append takes exactly one target"); };
+                // The target names the archive file to append to, so it needn't exist yet.
+                let target = resolve_append_target(target, ops)?;
+                let appended = match &from {
+                    Some(source) => {
+                        let contents = ops.read(source).with_context(|| format!("reading {}", source.display()))?;
+                        archive_append_outcome(&target, &mut contents.as_slice(), &cfg, ops)?
+                    }
+                    None => archive_append_outcome(&target, input, &cfg, ops)?,
+                };
+                if let (Some(source), true) = (&from, delete_source) {
+                    ops.remove_file(source).with_context(|| format!("removing {}", source.display()))?;
+                }
+                if appended.skipped && !out.json() {
+                    writeln!(err, "skipped duplicate content for {}", appended.dest.display())?;
+                }
+                let outcome = ArchiveOutcome { source: from.unwrap_or_else(|| "-".into()), dest: appended.dest };
+                out.emit(outcome.render(print_source), || json!({ "action": "append", "source": outcome.source, "dest": outcome.dest, "skipped": appended.skipped }))?;
+            } else {
+                if cfg.append_under.is_some() {
+                    anyhow::bail!("--under only applies with --append or --from");
+                }
+                if cfg.skip_duplicate {
+                    anyhow::bail!("--skip-duplicate only applies with --append or --from");
+                }
+                let (mut failed, mut succeeded) = (Vec::new(), false);
+                for result in archive_many(&targets, &cfg, ops) {
+                    if !out.json() {
+                        for warning in &result.warnings {
+                            writeln!(err, "warning: {}: {}", result.source.display(), warning)?;
+                        }
+                    }
+                    let record = || json!({ "action": "archive", "source": result.source, "dest": result.dest,
+                                            "error": result.error, "kind": result.kind, "code": result.code, "warnings": result.warnings });
+                    match (&result.dest, &result.error) {
+                        (Some(dest), _) => {
+                            succeeded = true;
+                            let outcome = ArchiveOutcome { source: result.source.clone(), dest: dest.clone() };
+                            out.emit(outcome.render(print_source), record)?;
+                        }
+                        (None, error) => {
+                            failed.push(result.code.unwrap_or(crate::EXIT_FAILURE));
+                            if out.json() {
+                                out.emit("", record)?;
+                            } else {
+                                writeln!(err, "error: {}: {}", result.source.display(), error.as_deref().unwrap_or_default())?;
+                            }
+                        }
+                    }
+                }
+                if copy::was_interrupted() {
+                    out.flush()?;
+                    return Ok(copy::INTERRUPTED_EXIT_CODE);
+                }
+                // A batch only fails as a whole when nothing in it could be archived, or with --fail-fast,
+                // with the status of its failures if they agree.
+                if !failed.is_empty() && (!succeeded || fail_fast) {
+                    out.flush()?;
+                    return Ok(if failed.iter().all(|code| *code == failed[0]) { failed[0] } else { crate::EXIT_FAILURE });
+                }
+            }
+        }
+        #[cfg(feature = "tui")]
+        Cmd::Pick => {
+            let Some((project, action)) = crate::tui::pick(crate::pick::Picker::load(&base, ctx.ops)?)? else {
+                return Ok(copy::INTERRUPTED_EXIT_CODE);
+            };
+            let path = crate::pick::apply_pick(&project, &action, &base, ctx.ops)?;
+            out.emit(path.display(), || json!({ "action": action.name(), "source": project.path, "dest": path }))?;
+        }
+        #[cfg(feature = "watch")]
+        Cmd::Watch { inbox, rule, settle, once } => {
+            let rules = rule.iter().map(|spec| crate::watch::parse_rule(spec)).collect::<crate::Result<Vec<_>>>()?;
+            if !ctx.ops.is_dir(&inbox) {
+                return Err(SlugpmError::not_found(&inbox).into());
+            }
+            let settle = std::time::Duration::from_secs(settle);
+            let mut report = |item: &crate::watch::Filed| -> crate::Result<()> {
+                let printed = match &item.result {
+                    Ok(dest) => out.emit(format!("{}\t{}", item.source.display(), dest.display()), || json!({ "source": item.source, "dest": dest, "rule": item.rule })),
+                    Err(e) if out.json() => out.emit("", || json!({ "source": item.source, "dest": null, "rule": item.rule, "error": e.to_string() })),
+                    Err(e) => writeln!(err, "error: {}: {e}", item.source.display()).map_err(Into::into),
+                };
+                printed.and_then(|()| out.flush()).map_err(|e| SlugpmError::Other(e.to_string()))
+            };
+            if once {
+                crate::watch::file_inbox(&inbox, &rules, settle, &base, ctx.ops)?.iter().try_for_each(&mut report)?;
+            } else {
+                crate::watch::watch_inbox(&inbox, &rules, settle, &base, ctx.ops, &mut report)?;
+            }
+        }
+        Cmd::Prune { archive_dirs, older_than, compress } => {
+            let archive_dirs = if archive_dirs.is_empty() { vec![base.archive_dir_for_dir(&base.project_root)] } else { archive_dirs };
+            let (mut count, mut bytes) = (0, 0);
+            for archive_dir in &archive_dirs {
+                for item in prune::prune(archive_dir, older_than, compress, &base, ctx.ops)? {
+                    let text = match &item.dest {
+                        Some(dest) => format!("compressed {} -> {}", item.path.display(), dest.display()),
+                        None => format!("removed {}", item.path.display()),
+                    };
+                    out.emit(text, || json!({ "action": "prune", "path": item.path, "dest": item.dest, "size": item.size }))?;
+                    count += 1;
+                    bytes += item.size;
+                }
+            }
+            writeln!(err, "pruned {count} item(s), {bytes} byte(s)")?;
+        }
+        Cmd::Dedupe { archive_dir, yes } => {
+            let groups = group_duplicates(&scan_archive(&archive_dir, ctx.ops)?);
+            for group in &groups {
+                // Each group is reported once its duplicates are gone, and a failed removal
+                // stops the run with only what was deleted reported as removed.
+                let mut removed = Vec::new();
+                let mut failed = None;
+                for dup in group.duplicates.iter().filter(|_| yes) {
+                    match remove_duplicate(dup, ctx.ops) {
+                        Ok(()) => removed.push(dup),
+                        Err(e) => {
+                            failed = Some(e);
+                            break;
+                        }
+                    }
+                }
+                let mut text = format!("keep {}", group.keep.display());
+                for dup in &group.duplicates {
+                    text += &format!("\n  {} {}", if removed.contains(&dup) { "removed" } else { "duplicate" }, dup.display());
+                }
+                out.emit(text, || json!({ "action": "dedupe", "hash": group.hash, "keep": group.keep,
+                                          "duplicates": group.duplicates, "size": group.size, "removed": removed }))?;
+                if let Some(e) = failed {
+                    out.flush()?;
+                    return Err(e.into());
+                }
+            }
+            let count: usize = groups.iter().map(|g| g.duplicates.len()).sum();
+            let bytes: u64 = groups.iter().map(|g| g.size * g.duplicates.len() as u64).sum();
+            writeln!(err, "{count} duplicate(s) in {} group(s), {bytes} byte(s) {}", groups.len(), if yes { "reclaimed" } else { "reclaimable" })?;
+        }
+        Cmd::Verify { dir } => {
+            let arch_root = archive_root(dir.as_ref(), &base);
+            let verified = integrity::verify_archive(&arch_root, ctx.ops)?;
+            for item in &verified {
+                for (path, problem) in &item.problems {
+                    out.emit(format!("{}\t{}", problem.as_str(), path.display()),
+                             || json!({ "action": "verify", "item": item.path, "path": path, "problem": problem.as_str() }))?;
+                }
+            }
+            let unrecorded = verified.iter().filter(|item| !item.recorded).count();
+            let corrupt = verified.iter().filter(|item| !item.problems.is_empty()).count();
+            writeln!(err, "{} item(s) checked, {corrupt} with problems, {unrecorded} without recorded hashes", verified.len() - unrecorded)?;
+            if corrupt > 0 {
+                out.flush()?;
+                return Ok(crate::EXIT_FAILURE);
+            }
+        }
+        Cmd::List { archived, sort, tag, status } => {
+            for entry in list_projects(archived || status == Some(ProjectStatus::Archived), sort, &base, ctx.ops)? {
+                if tag.as_deref().is_some_and(|tag| !tags::has_tag(&entry, tag)) || status.is_some_and(|s| s != entry.status) {
+                    continue;
+                }
+                let text = if sort == ProjectSort::Status { format!("{}\t{}", entry.slug, entry.status) }
+                           else if archived { format!("{}\t{}", entry.slug, if entry.archived { "archived" } else { "active" }) }
+                           else { entry.slug.clone() };
+                out.emit(text, || json!({ "slug": entry.slug, "title": entry.title, "path": entry.path, "archived": entry.archived, "status": entry.status.as_str(), "tags": entry.tags }))?;
+            }
+        }
+        Cmd::Report { format, archived, sort } => {
+            let projects = list_projects(archived, sort, &base, ctx.ops)?;
+            let report = render_report(&projects, format);
+            out.emit(report.trim_end(), || json!({ "format": format!("{format:?}").to_lowercase(), "report": report }))?;
+        }
+        Cmd::Init { dir, templates, force } => {
+            let dir = dir.unwrap_or_else(|| PathBuf::from("."));
+            let outcome = init::init_root(&dir, templates, force, &base, ctx.ops)?;
+            let lines: Vec<String> = outcome.created.iter().chain([&outcome.config]).map(|p| p.display().to_string()).collect();
+            out.emit(lines.join("\n"), || json!({ "action": "init", "root": dir, "config": outcome.config, "created": outcome.created }))?;
+        }
+        Cmd::Link { slug, other, kind } => {
+            let link = links::link_projects(&slug, &other, kind, &base, ctx.ops)?;
+            out.emit(format!("{slug}: {link}"), || json!({ "action": "link", "slug": slug, "kind": link.kind.as_str(), "other": link.slug }))?;
+        }
+        Cmd::Links { slug } => {
+            for link in links::project_links(&slug, &base, ctx.ops)? {
+                out.emit(format!("{}\t{}", link.kind, link.slug), || json!({ "slug": slug, "kind": link.kind.as_str(), "other": link.slug }))?;
+            }
+        }
+        Cmd::SetStatus { slug, status } => {
+            let previous = set_project_status(&slug, status, &base, ctx.ops)?;
+            out.emit(format!("{slug}: {previous} -> {status}"), || json!({ "action": "set-status", "slug": slug, "status": status.as_str(), "previous": previous.as_str() }))?;
+        }
+        Cmd::Recent { limit } => {
+            for entry in recent::recent_projects(&base, ctx.ops)?.into_iter().take(limit) {
+                out.emit(format!("{}\t{}\t{}", entry.at, entry.action, entry.slug), || json!(entry))?;
+            }
+        }
+        Cmd::Tag { command } => match command {
+            TagCmd::Add { slug, tag } => {
+                let tags = tags::add_tag(&slug, &tag, &base, ctx.ops)?;
+                out.emit(tags.join("\n"), || json!({ "action": "tag", "slug": slug, "tags": tags }))?;
+            }
+            TagCmd::Rm { slug, tag } => {
+                let tags = tags::remove_tag(&slug, &tag, &base, ctx.ops)?;
+                out.emit(tags.join("\n"), || json!({ "action": "untag", "slug": slug, "tags": tags }))?;
+            }
+            TagCmd::List { slug: Some(slug) } => {
+                for tag in tags::project_tags(&slug, &base, ctx.ops)? {
+                    out.emit(&tag, || json!({ "slug": slug, "tag": tag }))?;
+                }
+            }
+            TagCmd::List { slug: None } => {
+                for (tag, count) in tags::tag_counts(&base, ctx.ops)? {
+                    out.emit(format!("{tag}\t{count}"), || json!({ "tag": tag, "projects": count }))?;
+                }
+            }
+        },
+        Cmd::Status { stale_days } => {
+            let report = project_status(stale_days, &base, ctx.ops)?;
+            let activity = |p: &ProjectActivity| json!({ "slug": p.slug, "path": p.path, "touched": p.touched.format("%Y-%m-%dT%H:%M:%S").to_string(), "idle_days": p.idle_days });
+            let mut text = format!("active: {}\narchived: {}", report.active, report.archived);
+            if let Some(p) = &report.most_recent {
+                text += &format!("\nmost recent: {} ({})", p.slug, p.touched.format("%Y-%m-%d %H:%M"));
+            }
+            if !report.stale.is_empty() {
+                text += &format!("\nuntouched for {stale_days}+ days:");
+                for p in &report.stale {
+                    text += &format!("\n  {} ({} days)", p.slug, p.idle_days);
+                }
+            }
+            out.emit(text, || json!({
+                "active": report.active,
+                "archived": report.archived,
+                "most_recent": report.most_recent.as_ref().map(activity),
+                "stale_days": stale_days,
+                "stale": report.stale.iter().map(activity).collect::<Vec<_>>(),
+            }))?;
+        }
+        Cmd::Open { query, edit } => {
+            let query = query.join(" ");
+            let dir = find_project(&query, &base, ctx.ops)?;
+            recent::record("open", &dir, &base, ctx.ops);
+            if edit {
+                base.runner.launch(&editor()?, &dir)?;
+            } else {
+                out.emit(dir.display(), || json!({ "action": "open", "query": query, "path": dir }))?;
+            }
+        }
+        Cmd::Note { slug, text } => {
+            let text = if text.is_empty() && !ctx.stdin_is_tty { read_input_text(input)? } else { text.join(" ") };
+            let log = add_note(&slug, &text, &base, ctx.ops)?;
+            out.emit(log.display(), || json!({ "action": "note", "slug": slug, "path": log }))?;
+        }
+        Cmd::Today { slug, template, edit } => {
+            let (note, created) = today_note(&slug, template.as_deref(), &base, ctx.ops)?;
+            if edit {
+                base.runner.launch(&editor()?, &note)?;
+            } else {
+                out.emit(note.display(), || json!({ "action": "today", "slug": slug, "path": note, "created": created }))?;
+            }
+        }
+        Cmd::Trash { targets } => {
+            for target in targets {
+                let trashed = trash_target(&target, &base, ctx.ops)?;
+                out.emit(trashed.dest.display(), || json!({ "action": "trash", "source": target, "dest": trashed.dest }))?;
+            }
+        }
+        Cmd::Restore { target, to } => {
+            let dest = restore_target(&target, to.as_deref(), &base, ctx.ops)?;
+            out.emit(dest.display(), || json!({ "action": "restore", "source": target, "dest": dest }))?;
+        }
+        Cmd::Rename { slug, title, symlink } => {
+            let from = resolve_project(&slug, &base, ctx.ops)?;
+            let to = rename_project(&slug, &title.join(" "), symlink, &base, ctx.ops)?;
+            out.emit(to.display(), || json!({ "action": "rename", "source": from, "dest": to }))?;
+        }
+        Cmd::Mv { sources, project, copy, on_conflict } => {
+            let cfg = Config { on_conflict, ..base };
+            for source in sources {
+                let dest = move_into_project(&source, &project, copy, &cfg, ctx.ops)?;
+                out.emit(dest.display(), || json!({ "action": if copy { "copy" } else { "move" }, "source": source, "dest": dest }))?;
+            }
+        }
+        Cmd::File { paths, copy, on_conflict } => {
+            let cfg = Config { on_conflict, ..base };
+            for path in paths {
+                let (rule, dest) = rules::file_by_rules(&path, copy, &cfg, ctx.ops)?;
+                out.emit(dest.display(), || json!({ "action": if copy { "copy" } else { "move" }, "source": path, "dest": dest, "rule": rule.pattern, "project": rule.dest }))?;
+            }
+        }
+        Cmd::Undo => {
+            let entry = undo_last(&base, ctx.ops)?;
+            out.emit(format!("undid {}", entry.op), || json!({ "action": "undo", "at": entry.at, "undone": entry.op }))?;
+        }
+        Cmd::IndexSearch { term, archive_dir } => {
+            let entries = read_index(&archive_root(archive_dir.as_ref(), &base), ctx.ops)?;
+            for entry in search_index(&entries, &term) {
+                out.emit(format!("{}\t{}\t{}", entry.archived_at, entry.name, entry.dest.display()), || json!(entry))?;
+            }
+        }
+        Cmd::Search { query, regex, ignore_case, archived, max_size } => {
+            let opts = search::SearchOptions { regex, ignore_case, archived, max_file_size: max_size };
+            // Stop at the first failed write (such as a closed pipe) and report that rather than the search's error.
+            let mut write_error = None;
+            let stats = search::search_projects(&query.join(" "), &opts, &base, ctx.ops, &mut |m| {
+                out.emit(format!("{}:{}:{}", m.path.display(), m.line, m.text), || json!({ "slug": m.slug, "path": m.path, "line": m.line, "text": m.text }))
+                    .map_err(|e| { write_error = Some(e); SlugpmError::Interrupted })
+            });
+            if let Some(e) = write_error {
+                return Err(e);
+            }
+            let stats = stats?;
+            for path in &stats.skipped {
+                writeln!(err, "skipped {} (binary or larger than {max_size} bytes)", path.display())?;
+            }
+        }
+        Cmd::Slug { title, check, file_name, no_ext_lower, emoji, case, .. } => {
+            let cfg = Config { emoji, split_case: case.split_case, keep_acronyms: case.keep_acronyms, ..base };
+            let title = title.join(" ");
+            let slug = if file_name {
+                slugify_file_name(&normalize_title(&title, &cfg), !no_ext_lower)
+            } else if !check {
+                slugify_with(&title, &cfg)
+            } else {
+                match check_title(&title, &cfg) {
+                    Ok(slug) => slug,
+                    Err(reason) => {
+                        if out.json() {
+                            out.emit("", || json!({ "title": title, "valid": false, "reason": reason.to_string() }))?;
+                        } else {
+                            writeln!(err, "invalid: {reason}")?;
+                        }
+                        out.flush()?;
+                        return Ok(crate::EXIT_INVALID_INPUT);
+                    }
+                }
+            };
+            out.emit(&slug, || json!({ "title": title, "slug": slug }))?;
+        }
+        Cmd::Title { names, small_words, capitalize_all } => {
+            let small_words = match small_words {
+                _ if capitalize_all => Vec::new(),
+                Some(words) => words,
+                None => DEFAULT_SMALL_WORDS.iter().map(|w| w.to_string()).collect(),
+            };
+            for name in names {
+                let title = project_title(&name, &small_words, &base, ctx.ops)?;
+                out.emit(&title, || json!({ "input": name, "title": title }))?;
+            }
+        }
+        Cmd::Completions { shell } => {
+            let shells = Shells::builtins();
+            let completer = shells.completer(&shell).ok_or_else(|| anyhow::anyhow!("unsupported shell {shell:?}"))?;
+            let mut script = Vec::new();
+            completer.write_registration(COMPLETE_VAR, "slugpm", "slugpm", "slugpm", &mut script)?;
+            let script = String::from_utf8(script)?;
+            out.emit(script.trim_end(), || json!({ "shell": shell, "script": script }))?;
+        }
+        Cmd::Name { dirnames, stream, date_prefix } if dirnames.is_empty() => {
+            if !stream && ctx.stdin_is_tty {
+                anyhow::bail!("missing <dirname>; pass directories or pipe names on STDIN");
+            }
+            let cfg = name_config(&date_prefix, &base)?;
+            for line in read_input_text(input)?.lines() {
+                let parsed = parse_project_name(line, &cfg);
+                out.emit(&parsed.name, || name_json(line, &parsed))?;
+            }
+        }
+        Cmd::Name { dirnames, date_prefix, .. } => {
+            let cfg = name_config(&date_prefix, &base)?;
+            for dirname in dirnames {
+                let base = dirname.file_name()
+                    .and_then(|s| s.to_str())
+                    .ok_or_else(|| anyhow::anyhow!("invalid directory name: {}", dirname.display()))?;
+                let parsed = parse_project_name(base, &cfg);
+                out.emit(&parsed.name, || name_json(&dirname.to_string_lossy(), &parsed))?;
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(0)
+}
+
+/// Set by the scripts `slugpm completions` prints, to the shell asking for completions.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// The shell asking for completions through [`COMPLETE_VAR`], if any; `0` or empty is none.
+pub fn completion_shell() -> Option<String> {
+    std::env::var(COMPLETE_VAR).ok().filter(|v| !v.is_empty() && v != "0")
+}
+
+/// Answers a completion request from `shell`, whose scripts run `slugpm -- <words>`; `args`
+/// includes the program name first, as for [`run`].
+pub fn complete(shell: &str, args: Vec<String>, output: &mut dyn Write) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell).ok_or_else(|| anyhow::anyhow!("unsupported shell {shell:?}"))?;
+    let words = args.iter().position(|a| a == "--").map(|i| args[i + 1..].to_vec()).unwrap_or_default();
+    if words.is_empty() {
+        return Ok(());
+    }
+    let mut cmd = Cli::command();
+    cmd.build();
+    let current_dir = std::env::current_dir().ok();
+    completer.write_complete(&mut cmd, words.into_iter().map(Into::into).collect(), current_dir.as_deref(), output)?;
+    Ok(())
+}
+
+/// Project slugs for completion, under the project root the config file (if any) sets.
+fn slug_candidates() -> Vec<CompletionCandidate> {
+    let global = GlobalArgs {
+        config: std::env::var_os("SLUGPM_CONFIG").map(PathBuf::from),
+        workspace: std::env::var("SLUGPM_WORKSPACE").ok(),
+        ..GlobalArgs::default()
+    };
+    global.base_config(&RealFileOps)
+        .and_then(|cfg| Ok(project_slugs(&cfg, &RealFileOps)?))
+        .map(|slugs| slugs.into_iter().map(CompletionCandidate::new).collect())
+        .unwrap_or_default()
+}
+
+/// A project directory's name, which is its slug (with any date prefix).
+fn dir_name(dir: &std::path::Path) -> String {
+    dir.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// The archive directory a `dir` argument names: `dir` itself if it is one, else the archive
+/// inside it; without one, the project root's archive.
+fn archive_root(dir: Option<&PathBuf>, cfg: &Config) -> PathBuf {
+    match dir {
+        Some(dir) if dir.file_name().is_some_and(|n| *n == *cfg.archive_dir_name) => dir.clone(),
+        Some(dir) => dir.join(&cfg.archive_dir_name),
+        None => cfg.archive_dir_for_dir(&cfg.project_root),
+    }
+}
+
+/// `cfg` with the date prefixes `name` strips: the `--date-prefix` patterns, else the configured ones.
+fn name_config(patterns: &[String], cfg: &Config) -> Result<Config> {
+    let date_prefixes = if patterns.is_empty() { cfg.date_prefixes.clone() } else { DatePrefixes::new(patterns)? };
+    Ok(Config { date_prefixes, ..cfg.clone() })
+}
+
+/// `name --json`'s record: the input, the date of its prefix (`null` without one), the stripped
+/// name and its slug.
+fn name_json(input: &str, parsed: &ParsedName) -> serde_json::Value {
+    json!({ "input": input, "date": parsed.date.map(|d| d.to_string()), "name": parsed.name, "slug": parsed.slug })
+}
+
+/// The `--edit` command: `$VISUAL`, else `$EDITOR`.
+fn editor() -> Result<String> {
+    std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR"))
+        .map_err(|_| anyhow::anyhow!("--edit needs $VISUAL or $EDITOR to be set"))
+}
+
+/// Writes `prompt` to `err` and reads one line of `input`; empty, `y`, or `yes` (any case) mean yes.
+fn ask(prompt: &str, input: &mut dyn Read, err: &mut dyn Write) -> Result<bool> {
+    write!(err, "{prompt}")?;
+    err.flush()?;
+    // Byte by byte, so nothing past the answer is consumed.
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    while input.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+    let answer = String::from_utf8_lossy(&line).trim().to_lowercase();
+    Ok(matches!(answer.as_str(), "" | "y" | "yes"))
+}
+
+/// The title for a single create: `args` joined with spaces when STDIN is a terminal,
+/// else the first line of STDIN (or an untitled name, per `cfg.on_empty`).
+fn create_title(args: &[String], cfg: &Config, input: &mut dyn Read, ctx: &Context) -> Result<String> {
+    if ctx.stdin_is_tty {
+        if args.is_empty() {
+            anyhow::bail!("missing <title>");
+        }
+        return Ok(args.join(" "));
+    }
+    let buf = read_input_text(input)?;
+    let first_line = buf.lines().next().unwrap_or("").trim();
+    if !first_line.is_empty() {
+        Ok(first_line.to_string())
+    } else if cfg.on_empty == OnEmpty::Untitled {
+        Ok(untitled_name(cfg.clock.as_ref()))
+    } else {
+        anyhow::bail!("STDIN is empty");
+    }
+}
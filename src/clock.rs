@@ -0,0 +1,27 @@
+//! Injectable source of the current time, so timestamped names are testable.
+
+use std::fmt::Debug;
+use chrono::{Local, NaiveDateTime};
+
+pub trait Clock: Debug + Send + Sync {
+    /// The current local wall-clock time.
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The system clock, in local time.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// A clock stopped at a fixed time, for tests.
+#[derive(Debug)]
+pub struct FixedClock(pub NaiveDateTime);
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.0
+    }
+}
@@ -0,0 +1,46 @@
+//! Injectable runner for external commands, so features that shell out are testable.
+
+use std::{fmt::Debug, io::Write, path::Path, process::{Command, Stdio}};
+use crate::{error::IoContext, Result, SlugpmError};
+
+pub trait CommandRunner: Debug + Send + Sync {
+    /// Runs the shell command `cmd` with `stdin` as its input, returning its standard output.
+    fn run(&self, cmd: &str, stdin: &[u8]) -> Result<Vec<u8>>;
+    /// Runs the shell command `cmd` (e.g. `$EDITOR`, which may carry its own flags) on `path`,
+    /// attached to the terminal, and waits for it to exit.
+    fn launch(&self, cmd: &str, path: &Path) -> Result<()>;
+}
+
+/// Runs commands through the platform shell (`sh -c`, or `cmd /C` on Windows).
+#[derive(Debug, Default)]
+pub struct ShellRunner;
+impl CommandRunner for ShellRunner {
+    fn run(&self, cmd: &str, stdin: &[u8]) -> Result<Vec<u8>> {
+        let mut command = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+        command.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(cmd);
+        let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit())
+            .spawn()
+            .io_context(|| format!("running {cmd:?}"))?;
+        // Feed the input, then close it so the command sees end of file.
+        child.stdin.take().unwrap().write_all(stdin).io_context(|| format!("writing to {cmd:?}"))?;
+        let output = child.wait_with_output().io_context(|| format!("running {cmd:?}"))?;
+        if !output.status.success() {
+            return Err(SlugpmError::Other(format!("{cmd:?} failed with {}", output.status)));
+        }
+        Ok(output.stdout)
+    }
+
+    fn launch(&self, cmd: &str, path: &Path) -> Result<()> {
+        let status = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(cmd).arg(path).status()
+        } else {
+            // Pass the path as `$1` so it needs no quoting inside the command.
+            Command::new("sh").arg("-c").arg(format!("{cmd} \"$1\"")).arg("sh").arg(path).status()
+        }
+        .io_context(|| format!("running {cmd:?}"))?;
+        if !status.success() {
+            return Err(SlugpmError::Other(format!("{cmd:?} failed with {status}")));
+        }
+        Ok(())
+    }
+}
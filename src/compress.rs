@@ -0,0 +1,136 @@
+//! Compressed directory archives: `archive --compress` packs a directory into
+//! `archive/<dirname>.tar.zst`, and `restore` unpacks one back into a directory.
+//!
+//! The tarball holds the directory itself (`<dirname>/...`), so `tar --zstd -xf` unpacks it the
+//! same way. It is streamed through `FileOps` straight into the compressed file, one chunk of a
+//! file at a time, keeping contents, permissions and modification times; symlinks are refused
+//! rather than silently dropped.
+
+use std::{io::{Read, Write}, path::{Component, Path, PathBuf}, time::UNIX_EPOCH};
+use crate::{error::IoContext, FileOps, Result, SlugpmError};
+
+/// Extension of compressed archives.
+pub const COMPRESSED_EXT: &str = ".tar.zst";
+
+/// zstd level: the library default, which keeps packing fast.
+const LEVEL: i32 = 3;
+
+/// `<name>.tar.zst`.
+pub fn compressed_name(name: &str) -> String {
+    format!("{name}{COMPRESSED_EXT}")
+}
+
+/// Whether `path` names a compressed archive.
+pub fn is_compressed(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n.to_string_lossy().ends_with(COMPRESSED_EXT))
+}
+
+/// The directory name a compressed archive unpacks to: `notes.tar.zst` -> `notes`.
+pub fn unpacked_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    name.strip_suffix(COMPRESSED_EXT).filter(|n| !n.is_empty()).map(String::from)
+}
+
+/// Writes `dir` and everything under it to `dest` as a zstd-compressed tarball. On error the
+/// partly written `dest` is removed.
+pub fn pack_dir(dir: &Path, dest: &Path, ops: &dyn FileOps) -> Result<()> {
+    let name = dir.file_name().ok_or_else(|| SlugpmError::InvalidName(format!("cannot compress {}", dir.display())))?;
+    let encoder = zstd::Encoder::new(ops.create(dest)?, LEVEL).io_context(|| format!("compressing {}", dir.display()))?;
+    let mut builder = tar::Builder::new(encoder);
+    let result = append_tree(&mut builder, dir, Path::new(name), ops).and_then(|()| {
+        let encoder = builder.into_inner().io_context(|| format!("packing {}", dir.display()))?;
+        encoder.finish().and_then(|mut file| file.flush()).io_context(|| format!("writing {}", dest.display()))
+    });
+    if result.is_err() && ops.exists(dest) {
+        ops.remove_file(dest)?;
+    }
+    result
+}
+
+fn append_tree<W: Write>(builder: &mut tar::Builder<W>, dir: &Path, name: &Path, ops: &dyn FileOps) -> Result<()> {
+    append_entry(builder, dir, name, tar::EntryType::Directory, ops)?;
+    for entry in ops.read_dir(dir)? {
+        let entry_name = name.join(entry.file_name().unwrap_or_default());
+        if ops.is_symlink(&entry) {
+            return Err(SlugpmError::Other(format!("cannot compress symlink {}; archive without --compress", entry.display())));
+        }
+        if ops.is_dir(&entry) {
+            append_tree(builder, &entry, &entry_name, ops)?;
+        } else {
+            append_entry(builder, &entry, &entry_name, tar::EntryType::Regular, ops)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends the directory or file `path` as `name`, streaming a file's contents.
+fn append_entry<W: Write>(builder: &mut tar::Builder<W>, path: &Path, name: &Path, kind: tar::EntryType, ops: &dyn FileOps) -> Result<()> {
+    let meta = ops.metadata(path)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_size(meta.len);
+    header.set_mode(meta.mode);
+    header.set_mtime(meta.modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let contents: Box<dyn Read> = if kind.is_dir() { Box::new(std::io::empty()) } else { ops.open_read(path)? };
+    builder.append_data(&mut header, name, contents).io_context(|| format!("packing {}", path.display()))
+}
+
+/// Unpacks the compressed archive `archive` into the new directory `dest`, dropping the
+/// tarball's top-level directory and restoring each entry's permissions. Entries that would
+/// land outside `dest` are refused.
+pub fn unpack_to(archive: &Path, dest: &Path, ops: &dyn FileOps) -> Result<()> {
+    if ops.exists(dest) {
+        return Err(SlugpmError::Collision(dest.to_path_buf()));
+    }
+    let tarball = zstd::decode_all(ops.read(archive)?.as_slice()).io_context(|| format!("decompressing {}", archive.display()))?;
+    let mut files = Vec::new();
+    let mut tar = tar::Archive::new(tarball.as_slice());
+    for entry in tar.entries().io_context(|| format!("reading {}", archive.display()))? {
+        let mut entry = entry.io_context(|| format!("reading {}", archive.display()))?;
+        let path = entry.path().io_context(|| format!("reading {}", archive.display()))?.into_owned();
+        let relative = safe_relative(&path)
+            .ok_or_else(|| SlugpmError::Other(format!("{} has an unsafe entry {}", archive.display(), path.display())))?;
+        let mode = entry.header().mode().io_context(|| format!("reading {}", archive.display()))? & 0o7777;
+        match entry.header().entry_type() {
+            kind if kind.is_dir() => files.push((relative, mode, None)),
+            kind if kind.is_file() => {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).io_context(|| format!("reading {} from {}", path.display(), archive.display()))?;
+                files.push((relative, mode, Some(contents)));
+            }
+            kind => return Err(SlugpmError::Other(format!("{} has an unsupported {kind:?} entry {}", archive.display(), path.display()))),
+        }
+    }
+    // Everything is read and checked before anything is written.
+    ops.create_dir_all(dest)?;
+    let mut dirs = Vec::new();
+    for (relative, mode, contents) in files {
+        let target = dest.join(&relative);
+        match contents {
+            None => {
+                ops.create_dir_all(&target)?;
+                dirs.push((target, mode));
+            }
+            Some(contents) => {
+                if let Some(parent) = target.parent() {
+                    ops.create_dir_all(parent)?;
+                }
+                ops.write(&target, &contents)?;
+                ops.set_mode(&target, mode)?;
+            }
+        }
+    }
+    // Directories last and deepest first, so a read-only one is already filled.
+    for (dir, mode) in dirs.into_iter().rev() {
+        ops.set_mode(&dir, mode)?;
+    }
+    Ok(())
+}
+
+/// `path` without its top-level directory, if every component is a plain name.
+fn safe_relative(path: &Path) -> Option<PathBuf> {
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    Some(path.components().skip(1).collect())
+}
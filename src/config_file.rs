@@ -0,0 +1,282 @@
+//! Where slugpm's layout settings come from. Each layer overrides the ones before it
+//! ([`resolve_config`]):
+//!
+//! 1. the built-in defaults (`Config::default()`)
+//! 2. the optional `config.toml`, then the `.slugpm.toml` of the root found by [`find_root`]
+//!    ([`ConfigFile::layered`])
+//! 3. `SLUGPM_*` environment variables ([`env_overrides`])
+//! 4. the root of the workspace picked with `--workspace`/`SLUGPM_WORKSPACE`
+//! 5. command-line flags
+//!
+//! ```toml
+//! project_root = "~/work/projects"
+//! archive_dir_name = "attic"
+//! date_format = "%Y%m%d"
+//! date_prefixes = ["%Y-%m-%d", "%Y%m%d", "%Y.%m.%d"]
+//! slug_separator = "_"
+//! slug_max_len = 40
+//! slug_keep_case = false
+//! slug_stopwords = ["a", "an", "the"]
+//! slug_transliteration = "german"
+//! dated = true
+//! ids = false
+//! dated_archive = true
+//! git = true
+//! templates_dir = "~/work/templates"
+//!
+//! # Where `slugpm file` puts files, first match first (see `rules`).
+//! [rules]
+//! "*.pdf" = "reading"
+//!
+//! # Named project roots, picked with `--workspace work` or `SLUGPM_WORKSPACE=work`.
+//! [workspaces.work]
+//! root = "~/work/project"
+//!
+//! [workspaces.personal]
+//! root = "~/personal/project"
+//! ```
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+use clap::ValueEnum;
+use crate::{date_prefix::DatePrefixes, rules::{self, RuleTable}, Config, FileOps, Result, SlugpmError, Transliteration};
+
+/// The config file `slugpm init` writes at the root of a slugpm tree.
+pub const ROOT_CONFIG_FILE: &str = ".slugpm.toml";
+
+/// Settings read from a config file; unset keys keep the current value.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub project_root: Option<PathBuf>,
+    pub archive_dir_name: Option<String>,
+    pub date_format: Option<String>,
+    /// Date prefixes `name` strips; see [`crate::date_prefix`].
+    pub date_prefixes: Option<Vec<String>>,
+    pub slug_separator: Option<String>,
+    pub slug_max_len: Option<usize>,
+    pub slug_keep_case: Option<bool>,
+    pub slug_stopwords: Option<Vec<String>>,
+    pub slug_transliteration: Option<Transliteration>,
+    pub dated: Option<bool>,
+    pub ids: Option<bool>,
+    pub dated_archive: Option<bool>,
+    pub git: Option<bool>,
+    pub templates_dir: Option<PathBuf>,
+    /// Filing rules; see [`crate::rules`].
+    pub rules: Option<RuleTable>,
+    /// Named project roots; see [`ConfigFile::workspace_root`].
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, Workspace>,
+}
+
+/// A `[workspaces.<name>]` table.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workspace {
+    /// The workspace's project root, used in place of `project_root`.
+    pub root: PathBuf,
+}
+
+impl ConfigFile {
+    /// Overrides the matching fields of `cfg` with the keys that are set, checking them first.
+    pub fn apply(&self, cfg: &mut Config) -> Result<()> {
+        if let Some(root) = &self.project_root {
+            cfg.project_root = expand_tilde(root);
+        }
+        if let Some(name) = &self.archive_dir_name {
+            if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+                return Err(SlugpmError::InvalidName(format!("archive_dir_name {name:?} must be a single directory name")));
+            }
+            cfg.archive_dir_name = name.clone();
+        }
+        if let Some(format) = &self.date_format {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(SlugpmError::Other(format!("date_format {format:?} is not a valid strftime format")));
+            }
+            cfg.date_format = format.clone();
+        }
+        if let Some(prefixes) = &self.date_prefixes {
+            cfg.date_prefixes = DatePrefixes::new(prefixes)?;
+        }
+        if let Some(sep) = &self.slug_separator {
+            if sep.contains(['/', '\\']) {
+                return Err(SlugpmError::InvalidName(format!("slug_separator {sep:?} cannot contain a path separator")));
+            }
+            cfg.slug.separator = sep.clone();
+        }
+        if let Some(max) = self.slug_max_len {
+            if max == 0 {
+                return Err(SlugpmError::Other("slug_max_len must be at least 1".into()));
+            }
+            cfg.slug.max_len = Some(max);
+        }
+        if let Some(keep_case) = self.slug_keep_case {
+            cfg.slug.keep_case = keep_case;
+        }
+        if let Some(words) = &self.slug_stopwords {
+            cfg.slug.stopwords = words.clone();
+        }
+        if let Some(mode) = self.slug_transliteration {
+            cfg.slug.transliteration = mode;
+        }
+        if let Some(dated) = self.dated {
+            cfg.dated = dated;
+        }
+        if let Some(ids) = self.ids {
+            cfg.ids = ids;
+        }
+        if let Some(dated) = self.dated_archive {
+            cfg.dated_archive = dated;
+        }
+        if let Some(git) = self.git {
+            cfg.git = git;
+        }
+        if let Some(dir) = &self.templates_dir {
+            cfg.templates_dir = Some(expand_tilde(dir));
+        }
+        if let Some(table) = &self.rules {
+            cfg.rules = rules::parse_rules(table)?;
+        }
+        Ok(())
+    }
+
+    /// `self` with every key that `over` sets replaced by `over`'s value; workspaces merge,
+    /// `over`'s winning on a name clash.
+    pub fn layered(&self, over: &ConfigFile) -> ConfigFile {
+        let mut workspaces = self.workspaces.clone();
+        workspaces.extend(over.workspaces.clone());
+        ConfigFile {
+            project_root: over.project_root.clone().or_else(|| self.project_root.clone()),
+            archive_dir_name: over.archive_dir_name.clone().or_else(|| self.archive_dir_name.clone()),
+            date_format: over.date_format.clone().or_else(|| self.date_format.clone()),
+            date_prefixes: over.date_prefixes.clone().or_else(|| self.date_prefixes.clone()),
+            slug_separator: over.slug_separator.clone().or_else(|| self.slug_separator.clone()),
+            slug_max_len: over.slug_max_len.or(self.slug_max_len),
+            slug_keep_case: over.slug_keep_case.or(self.slug_keep_case),
+            slug_stopwords: over.slug_stopwords.clone().or_else(|| self.slug_stopwords.clone()),
+            slug_transliteration: over.slug_transliteration.or(self.slug_transliteration),
+            dated: over.dated.or(self.dated),
+            ids: over.ids.or(self.ids),
+            dated_archive: over.dated_archive.or(self.dated_archive),
+            git: over.git.or(self.git),
+            templates_dir: over.templates_dir.clone().or_else(|| self.templates_dir.clone()),
+            rules: over.rules.clone().or_else(|| self.rules.clone()),
+            workspaces,
+        }
+    }
+
+    /// The project root of the workspace `name`, with a leading `~` expanded.
+    pub fn workspace_root(&self, name: &str) -> Result<PathBuf> {
+        match self.workspaces.get(name) {
+            Some(workspace) => Ok(expand_tilde(&workspace.root)),
+            None if self.workspaces.is_empty() => Err(SlugpmError::NotFound(format!("no workspace {name:?}: the config file defines no [workspaces]"))),
+            None => {
+                let known: Vec<&str> = self.workspaces.keys().map(String::as_str).collect();
+                Err(SlugpmError::NotFound(format!("no workspace {name:?} (known: {})", known.join(", "))))
+            }
+        }
+    }
+}
+
+/// The settings given by `SLUGPM_*` environment variables, as looked up by `var`. Each sets the
+/// config file key of the same name, except `SLUGPM_ARCHIVE_DIR` for `archive_dir_name`:
+/// `SLUGPM_PROJECT_ROOT`, `SLUGPM_ARCHIVE_DIR`, `SLUGPM_DATE_FORMAT`, `SLUGPM_DATE_PREFIXES`
+/// (comma-separated), `SLUGPM_SLUG_SEPARATOR`, `SLUGPM_SLUG_MAX_LEN`, `SLUGPM_SLUG_KEEP_CASE`,
+/// `SLUGPM_SLUG_STOPWORDS` (comma-separated), `SLUGPM_SLUG_TRANSLITERATION`, `SLUGPM_DATED`,
+/// `SLUGPM_IDS`, `SLUGPM_DATED_ARCHIVE`, `SLUGPM_GIT` and `SLUGPM_TEMPLATES_DIR`. Booleans are `true`/`false`, `1`/`0` or `yes`/`no`; empty
+/// variables are ignored.
+pub fn env_overrides(var: impl Fn(&str) -> Option<String>) -> Result<ConfigFile> {
+    let get = |name: &str| var(name).filter(|value| !value.is_empty());
+    let invalid = |name: &str, value: &str, expected: &str| SlugpmError::Other(format!("{name}={value:?} must be {expected}"));
+    let flag = |name: &str| get(name).map(|value| match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(invalid(name, &value, "true or false")),
+    }).transpose();
+    Ok(ConfigFile {
+        project_root: get("SLUGPM_PROJECT_ROOT").map(PathBuf::from),
+        archive_dir_name: get("SLUGPM_ARCHIVE_DIR"),
+        date_format: get("SLUGPM_DATE_FORMAT"),
+        date_prefixes: get("SLUGPM_DATE_PREFIXES")
+            .map(|value| value.split(',').map(str::trim).filter(|p| !p.is_empty()).map(String::from).collect()),
+        slug_separator: get("SLUGPM_SLUG_SEPARATOR"),
+        slug_max_len: get("SLUGPM_SLUG_MAX_LEN")
+            .map(|value| value.parse().map_err(|_| invalid("SLUGPM_SLUG_MAX_LEN", &value, "a number")))
+            .transpose()?,
+        slug_keep_case: flag("SLUGPM_SLUG_KEEP_CASE")?,
+        slug_stopwords: get("SLUGPM_SLUG_STOPWORDS")
+            .map(|value| value.split(',').map(str::trim).filter(|w| !w.is_empty()).map(String::from).collect()),
+        slug_transliteration: get("SLUGPM_SLUG_TRANSLITERATION")
+            .map(|value| Transliteration::from_str(&value, true).map_err(|_| invalid("SLUGPM_SLUG_TRANSLITERATION", &value, "ascii, german or unicode")))
+            .transpose()?,
+        dated: flag("SLUGPM_DATED")?,
+        ids: flag("SLUGPM_IDS")?,
+        dated_archive: flag("SLUGPM_DATED_ARCHIVE")?,
+        git: flag("SLUGPM_GIT")?,
+        templates_dir: get("SLUGPM_TEMPLATES_DIR").map(PathBuf::from),
+        rules: None,
+        workspaces: BTreeMap::new(),
+    })
+}
+
+/// The settings from every layer in the module docs' order: `file`, then `env`, then the
+/// root of `workspace` (looked up in `file`), then `flags`.
+pub fn resolve_config(file: &ConfigFile, env: &ConfigFile, workspace: Option<&str>, flags: &ConfigFile) -> Result<Config> {
+    let mut cfg = Config::default();
+    file.apply(&mut cfg)?;
+    env.apply(&mut cfg)?;
+    if let Some(name) = workspace {
+        cfg.project_root = file.workspace_root(name)?;
+    }
+    flags.apply(&mut cfg)?;
+    Ok(cfg)
+}
+
+/// Parses the contents of a config file.
+pub fn parse_config_file(text: &str) -> Result<ConfigFile> {
+    toml::from_str(text).map_err(|e| SlugpmError::Other(e.to_string()))
+}
+
+/// `$XDG_CONFIG_HOME/slugpm/config.toml`, else `~/.config/slugpm/config.toml`
+/// (`%APPDATA%\slugpm\config.toml` on Windows).
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()).map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    Some(base?.join("slugpm").join("config.toml"))
+}
+
+/// Reads and parses the config file at `path`.
+pub fn load_config_file(path: &Path, ops: &dyn FileOps) -> Result<ConfigFile> {
+    parse_config_file(&ops.read_to_string(path)?)
+        .map_err(|e| SlugpmError::Other(format!("in config {}: {e}", path.display())))
+}
+
+/// The nearest directory at or above `start` holding a [`ROOT_CONFIG_FILE`], the way git finds
+/// its repository; `None` when no ancestor has one.
+pub fn find_root(start: &Path, ops: &dyn FileOps) -> Option<PathBuf> {
+    start.ancestors().find(|dir| ops.is_file(&dir.join(ROOT_CONFIG_FILE))).map(Path::to_path_buf)
+}
+
+/// Reads the [`ROOT_CONFIG_FILE`] in `root`. Its relative `project_root` and `templates_dir`
+/// are relative to `root`, so the tree works from any directory inside it.
+pub fn load_root_config(root: &Path, ops: &dyn FileOps) -> Result<ConfigFile> {
+    let mut file = load_config_file(&root.join(ROOT_CONFIG_FILE), ops)?;
+    let anchor = |path: PathBuf| if path.starts_with("~") { path } else { root.join(path) };
+    file.project_root = file.project_root.map(anchor);
+    file.templates_dir = file.templates_dir.map(anchor);
+    Ok(file)
+}
+
+/// Replaces a leading `~` with the home directory.
+fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,156 @@
+//! Recursive copies that can be interrupted, for moves a rename can't do.
+//!
+//! A copy never modifies its source, and an interrupted or failed copy removes whatever it
+//! had written, so Ctrl-C leaves the tree as it was before the move started.
+//!
+//! Symlinks are recreated rather than followed, so a link to a directory elsewhere (or to one
+//! of its own parents) copies as the link itself. Files keep their permissions and
+//! modification time.
+//!
+//! Copies of large projects can take minutes, so a copy reports how far it has got to a
+//! [`Progress`]: once before the first file, after each chunk of a file
+//! ([`COPY_CHUNK`](crate::COPY_CHUNK) bytes) and after each file. Files are streamed, so
+//! however large one is only a chunk of it is in memory, and an interruption stops it
+//! between chunks.
+
+use std::{fmt::Debug, path::Path, sync::atomic::{AtomicBool, Ordering}};
+use crate::{FileOps, Result, SlugpmError};
+
+/// Set by the SIGINT handler while a copy is running.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Whether a copy is running, in which case SIGINT is deferred to it rather than exiting at once.
+static COPYING: AtomicBool = AtomicBool::new(false);
+
+/// The exit code for a run stopped by SIGINT (128 + 2).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Installs the process's SIGINT handler: outside a copy it exits with [`INTERRUPTED_EXIT_CODE`]
+/// right away; during one it asks the copy to stop and clean up first.
+///
+/// This takes over SIGINT for the whole process, so it's for the `slugpm` binary's `main`, not
+/// for library callers. Fails if a handler was already installed.
+pub fn install_interrupt_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if COPYING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })
+    .map_err(|e| SlugpmError::Other(format!("installing the SIGINT handler: {e}")))
+}
+
+/// Whether SIGINT arrived during a copy.
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// How far a copy has got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    pub files: u64,
+    pub total_files: u64,
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+pub trait Progress: Debug + Send + Sync {
+    /// Called with zero copied before the first file, then after each chunk and each file.
+    fn update(&self, progress: &CopyProgress);
+    /// Called once the copy has stopped, whether or not it succeeded.
+    fn finish(&self) {}
+}
+
+/// Reports nothing; the default.
+#[derive(Clone, Debug, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn update(&self, _progress: &CopyProgress) {}
+}
+
+/// The number of files in, and total size of, the file or directory `path`. A symlink counts
+/// as one empty file.
+pub fn tree_totals(path: &Path, ops: &dyn FileOps) -> Result<(u64, u64)> {
+    if ops.is_symlink(path) {
+        return Ok((1, 0));
+    }
+    let meta = ops.metadata(path)?;
+    if !meta.is_dir {
+        return Ok((1, meta.len));
+    }
+    ops.read_dir(path)?.iter().try_fold((0, 0), |(files, bytes), entry| {
+        let (f, b) = tree_totals(entry, ops)?;
+        Ok((files + f, bytes + b))
+    })
+}
+
+/// Copies the file or directory `src` to `dest`, checking `interrupted` before each file and
+/// after each chunk, and telling `progress` after both.
+///
+/// On interruption ([`SlugpmError::Interrupted`]) or error the partial `dest` is removed; `src`
+/// is only ever read.
+pub fn copy_tree(src: &Path, dest: &Path, ops: &dyn FileOps, interrupted: &dyn Fn() -> bool, progress: &dyn Progress) -> Result<()> {
+    let result = tree_totals(src, ops).and_then(|(total_files, total_bytes)| {
+        let mut state = CopyProgress { total_files, total_bytes, ..CopyProgress::default() };
+        progress.update(&state);
+        copy_entries(src, dest, ops, interrupted, progress, &mut state)
+    });
+    progress.finish();
+    if result.is_err() && ops.exists(dest) {
+        remove_tree(dest, ops)?;
+    }
+    result
+}
+
+/// [`copy_tree`], stopped by SIGINT once [`install_interrupt_handler`] has run.
+pub fn copy_tree_interruptible(src: &Path, dest: &Path, ops: &dyn FileOps, progress: &dyn Progress) -> Result<()> {
+    COPYING.store(true, Ordering::SeqCst);
+    let result = copy_tree(src, dest, ops, &was_interrupted, progress);
+    COPYING.store(false, Ordering::SeqCst);
+    result
+}
+
+fn copy_entries(
+    src: &Path,
+    dest: &Path,
+    ops: &dyn FileOps,
+    interrupted: &dyn Fn() -> bool,
+    progress: &dyn Progress,
+    state: &mut CopyProgress,
+) -> Result<()> {
+    if ops.is_symlink(src) || !ops.is_dir(src) {
+        if interrupted() {
+            return Err(SlugpmError::Interrupted);
+        }
+        if ops.is_symlink(src) {
+            let target = ops.read_link(src)?;
+            if ops.is_dir(src) { ops.symlink_dir(&target, dest)? } else { ops.symlink_file(&target, dest)? }
+            state.files += 1;
+            progress.update(state);
+            return Ok(());
+        }
+        ops.copy_file(src, dest, &mut |copied| {
+            state.bytes += copied;
+            progress.update(state);
+            if interrupted() { Err(SlugpmError::Interrupted) } else { Ok(()) }
+        })?;
+        ops.copy_attributes(src, dest)?;
+        state.files += 1;
+        progress.update(state);
+        return Ok(());
+    }
+    ops.create_dir_all(dest)?;
+    for entry in ops.read_dir(src)? {
+        copy_entries(&entry, &dest.join(entry.file_name().unwrap()), ops, interrupted, progress, state)?;
+    }
+    Ok(())
+}
+
+/// Removes a file, a symlink, or a directory and everything in it.
+pub fn remove_tree(path: &Path, ops: &dyn FileOps) -> Result<()> {
+    if ops.is_dir(path) && !ops.is_symlink(path) {
+        return ops.remove_dir_all(path);
+    }
+    ops.remove_file(path)
+}
@@ -0,0 +1,115 @@
+//! Recognizing the date at the start of a project name, for `name`, `rename` and titles.
+//!
+//! By default that is `YYYY-MM-DD` and an optional `-`. Older trees often use other shapes
+//! (`20250913_`, `2025.09.13-`), so the config file's `date_prefixes` can list its own
+//! patterns. An entry containing `%` is a strftime-style format (`%Y%m%d`), matched with an
+//! optional trailing `-`, `_` or `.`; anything else is a regular expression, whose `year`,
+//! `month` and `day` (or `year` and `ordinal`) named groups give the date. Each list is
+//! compiled once: the defaults lazily on first use, a configured list when the config is
+//! resolved.
+//!
+//! ```toml
+//! date_prefixes = ["%Y-%m-%d", "%Y%m%d", "%Y.%m.%d", '(?P<day>\d{2})\.(?P<month>\d{2})\.(?P<year>\d{4})-']
+//! ```
+
+use std::sync::LazyLock;
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+use crate::{Result, SlugpmError};
+
+/// The patterns used when none are configured.
+pub const DEFAULT_DATE_PREFIXES: &[&str] = &[r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})-?"];
+
+static DEFAULTS: LazyLock<DatePrefixes> = LazyLock::new(|| DatePrefixes::new(DEFAULT_DATE_PREFIXES).expect("default date prefixes compile"));
+
+/// The compiled [`DEFAULT_DATE_PREFIXES`].
+pub fn defaults() -> &'static DatePrefixes {
+    &DEFAULTS
+}
+
+/// A compiled list of date-prefix patterns, each anchored at the start of the name.
+#[derive(Clone, Debug)]
+pub struct DatePrefixes {
+    patterns: Vec<Regex>,
+}
+
+impl Default for DatePrefixes {
+    fn default() -> Self {
+        DEFAULTS.clone()
+    }
+}
+
+impl DatePrefixes {
+    /// Compiles `entries` (formats or regexes, see the module docs), failing on the first bad one.
+    pub fn new<S: AsRef<str>>(entries: &[S]) -> Result<Self> {
+        let patterns = entries.iter().map(|entry| {
+            let entry = entry.as_ref();
+            let pattern = if entry.contains('%') { format_to_regex(entry)? } else { entry.to_string() };
+            Regex::new(&format!("^(?:{pattern})"))
+                .map_err(|e| SlugpmError::Other(format!("date prefix {entry:?} is not a valid pattern: {e}")))
+        }).collect::<Result<_>>()?;
+        Ok(DatePrefixes { patterns })
+    }
+
+    /// Length in bytes of the longest date prefix at the start of `name`, or 0.
+    pub fn prefix_len(&self, name: &str) -> usize {
+        self.longest(name).map_or(0, |caps| caps[0].len())
+    }
+
+    /// The date in `name`'s prefix, if it has one naming a real day, and the rest of `name`.
+    pub fn split<'a>(&self, name: &'a str) -> (Option<NaiveDate>, &'a str) {
+        match self.longest(name) {
+            Some(caps) => (date_of(&caps), &name[caps[0].len()..]),
+            None => (None, name),
+        }
+    }
+
+    fn longest<'a>(&self, name: &'a str) -> Option<Captures<'a>> {
+        self.patterns.iter().filter_map(|re| re.captures(name)).max_by_key(|caps| caps[0].len())
+    }
+
+    /// `name` without its date prefix.
+    pub fn strip<'a>(&self, name: &'a str) -> &'a str {
+        &name[self.prefix_len(name)..]
+    }
+}
+
+/// The date named by a match's `year`/`year2`, `month`, `day` and `ordinal` groups.
+fn date_of(caps: &Captures) -> Option<NaiveDate> {
+    let group = |name: &str| caps.name(name).and_then(|m| m.as_str().parse::<u32>().ok());
+    let year = group("year").or_else(|| group("year2").map(|y| 2000 + y))? as i32;
+    match (group("month"), group("day"), group("ordinal")) {
+        (Some(month), Some(day), _) => NaiveDate::from_ymd_opt(year, month, day),
+        (_, _, Some(ordinal)) => NaiveDate::from_yo_opt(year, ordinal),
+        _ => None,
+    }
+}
+
+/// The regex for a strftime-style date format, followed by an optional `-`, `_` or `.`.
+/// Supports `%Y`, `%y`, `%m`, `%d`, `%j`, `%H`, `%M`, `%S` and `%%`; other characters match
+/// themselves.
+pub fn format_to_regex(format: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(r"(?P<year>\d{4})"),
+            Some('y') => out.push_str(r"(?P<year2>\d{2})"),
+            Some('m') => out.push_str(r"(?P<month>\d{2})"),
+            Some('d') => out.push_str(r"(?P<day>\d{2})"),
+            Some('j') => out.push_str(r"(?P<ordinal>\d{3})"),
+            Some('H' | 'M' | 'S') => out.push_str(r"\d{2}"),
+            Some('%') => out.push('%'),
+            other => {
+                let spec = other.map(|c| format!("%{c}")).unwrap_or_else(|| "a trailing %".into());
+                return Err(SlugpmError::Other(format!("date prefix {format:?}: unsupported {spec}")));
+            }
+        }
+    }
+    out.push_str("[-_.]?");
+    Ok(out)
+}
@@ -0,0 +1,81 @@
+//! Finding and removing archived files with identical contents.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::SystemTime};
+use crate::{index::INDEX_FILE, integrity, manifest::{ARCHIVE_NOTES_FILE, MANIFEST_FILE}, FileOps, Result, POLICY_FILE};
+
+/// A file found in an archive, with what grouping needs to know about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScannedFile {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub size: u64,
+    /// Hex SHA-256 of the contents.
+    pub hash: String,
+}
+
+/// Files sharing one content hash: the copy to keep and the ones that can go.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    /// The oldest copy (the first by path among equally old ones).
+    pub keep: PathBuf,
+    pub duplicates: Vec<PathBuf>,
+    /// Size of one copy, in bytes.
+    pub size: u64,
+}
+
+/// Every file under `dir`, recursively, except slugpm's own sidecars, block indexes, policy files, and index.
+/// A `.sha256` file is only a sidecar when the file it's named for is next to it. Files are hashed
+/// as a stream, not read into memory.
+pub fn scan_archive(dir: &Path, ops: &dyn FileOps) -> Result<Vec<ScannedFile>> {
+    let mut files = Vec::new();
+    for entry in ops.read_dir(dir)? {
+        if ops.is_dir(&entry) {
+            files.extend(scan_archive(&entry, ops)?);
+            continue;
+        }
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        let sidecar = name.strip_suffix(".sha256").is_some_and(|file| ops.exists(&entry.with_file_name(file)));
+        if sidecar || integrity::is_block_index(&name) || name == POLICY_FILE || name == INDEX_FILE || name == ARCHIVE_NOTES_FILE || name == MANIFEST_FILE {
+            continue;
+        }
+        let meta = ops.metadata(&entry)?;
+        files.push(ScannedFile {
+            modified: meta.modified,
+            size: meta.len,
+            hash: integrity::sha256_file(&entry, ops)?,
+            path: entry,
+        });
+    }
+    Ok(files)
+}
+
+/// Groups `files` by content hash, keeping only groups with more than one file.
+pub fn group_duplicates(files: &[ScannedFile]) -> Vec<DuplicateGroup> {
+    let mut by_hash: BTreeMap<&str, Vec<&ScannedFile>> = BTreeMap::new();
+    for file in files {
+        by_hash.entry(&file.hash).or_default().push(file);
+    }
+    by_hash.into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|(hash, mut copies)| {
+            copies.sort_by(|a, b| (a.modified, &a.path).cmp(&(b.modified, &b.path)));
+            DuplicateGroup {
+                hash: hash.to_string(),
+                keep: copies[0].path.clone(),
+                duplicates: copies[1..].iter().map(|f| f.path.clone()).collect(),
+                size: copies[0].size,
+            }
+        })
+        .collect()
+}
+
+/// Deletes the duplicate `path`, along with its sidecar if it has one.
+pub fn remove_duplicate(path: &Path, ops: &dyn FileOps) -> Result<()> {
+    ops.remove_file(path)?;
+    let sidecar = integrity::sidecar_path(path);
+    if ops.exists(&sidecar) {
+        ops.remove_file(&sidecar)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! The library's error type. Only the CLI wraps these in `anyhow`.
+//!
+//! Each error maps to one of the `EXIT_*` statuses and a stable kind name, which the CLI
+//! reports (as `{"error": <kind>, "message": ..., "code": <status>}` on stderr under `--json`).
+//! Scripts rely on both, so existing ones must not change.
+
+use std::{io, path::{Path, PathBuf}};
+
+/// A failure without a more specific status, including ones from outside the library.
+pub const EXIT_FAILURE: i32 = 1;
+/// A path, project or template that has to exist doesn't.
+pub const EXIT_NOT_FOUND: i32 = 2;
+/// The destination is taken, or the target is already archived.
+pub const EXIT_COLLISION: i32 = 3;
+/// An unusable name, or a command line that doesn't parse.
+pub const EXIT_INVALID_INPUT: i32 = 4;
+pub const EXIT_PERMISSION_DENIED: i32 = 5;
+/// Any other file system or stream failure.
+pub const EXIT_IO: i32 = 6;
+
+/// Why a slugpm operation failed.
+#[derive(Debug, thiserror::Error)]
+pub enum SlugpmError {
+    /// A path, project or template that has to exist doesn't.
+    #[error("{0}")]
+    NotFound(String),
+    /// The target is already inside an archive directory.
+    #[error("{} is already archived", .0.display())]
+    AlreadyArchived(PathBuf),
+    /// The destination is taken and the conflict policy doesn't allow replacing it.
+    #[error("{} already exists", .0.display())]
+    Collision(PathBuf),
+    /// A title, slug, category or other name that can't be used.
+    #[error("{0}")]
+    InvalidName(String),
+    /// A file system or stream operation failed; `context` says what was being done.
+    #[error("{context}: {error}")]
+    Io { context: String, error: io::Error },
+    /// Ctrl-C stopped the operation.
+    #[error("interrupted")]
+    Interrupted,
+    /// Anything else, such as a malformed config file or a failing external command.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// `Result` with [`SlugpmError`] as the default error.
+pub type Result<T, E = SlugpmError> = std::result::Result<T, E>;
+
+impl SlugpmError {
+    /// `"<path> does not exist"`.
+    pub fn not_found(path: &Path) -> Self {
+        SlugpmError::NotFound(format!("{} does not exist", path.display()))
+    }
+
+    /// The process exit status for this error; Ctrl-C is [`crate::copy::INTERRUPTED_EXIT_CODE`].
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SlugpmError::NotFound(_) => EXIT_NOT_FOUND,
+            SlugpmError::AlreadyArchived(_) | SlugpmError::Collision(_) => EXIT_COLLISION,
+            SlugpmError::InvalidName(_) => EXIT_INVALID_INPUT,
+            SlugpmError::Io { error, .. } => io_exit(error.kind()).0,
+            SlugpmError::Interrupted => crate::copy::INTERRUPTED_EXIT_CODE,
+            SlugpmError::Other(_) => EXIT_FAILURE,
+        }
+    }
+
+    /// The error's kind as reported in `--json` output: `not_found`, `already_archived`,
+    /// `collision`, `invalid_name`, `permission_denied`, `io`, `interrupted` or `other`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            SlugpmError::NotFound(_) => "not_found",
+            SlugpmError::AlreadyArchived(_) => "already_archived",
+            SlugpmError::Collision(_) => "collision",
+            SlugpmError::InvalidName(_) => "invalid_name",
+            SlugpmError::Io { error, .. } => io_exit(error.kind()).1,
+            SlugpmError::Interrupted => "interrupted",
+            SlugpmError::Other(_) => "other",
+        }
+    }
+
+    /// The I/O error's kind, for [`SlugpmError::Io`].
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            SlugpmError::Io { error, .. } => Some(error.kind()),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches what was being done to an [`io::Result`].
+pub(crate) trait IoContext<T> {
+    fn io_context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn io_context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|error| SlugpmError::Io { context: context(), error })
+    }
+}
+
+
+/// The exit status and kind name for an I/O error of `kind`.
+pub fn io_exit(kind: io::ErrorKind) -> (i32, &'static str) {
+    match kind {
+        io::ErrorKind::NotFound => (EXIT_NOT_FOUND, "not_found"),
+        io::ErrorKind::AlreadyExists => (EXIT_COLLISION, "collision"),
+        io::ErrorKind::PermissionDenied => (EXIT_PERMISSION_DENIED, "permission_denied"),
+        io::ErrorKind::Interrupted => (crate::copy::INTERRUPTED_EXIT_CODE, "interrupted"),
+        _ => (EXIT_IO, "io"),
+    }
+}
@@ -0,0 +1,115 @@
+//! User scripts run after slugpm changes something: `post-create`, `post-archive` and
+//! `post-restore` in the hooks directory (`~/.config/slugpm/hooks/`, beside the default
+//! config file). A hook that doesn't exist, or isn't executable, is skipped.
+//!
+//! A hook gets what happened in its environment:
+//!
+//! - `SLUGPM_HOOK`: the hook's name, such as `post-create`
+//! - `SLUGPM_SLUG`: the project's (or item's) name
+//! - `SLUGPM_PATH`: where it is now, as an absolute path
+//! - `SLUGPM_SOURCE`: where it was, for `post-archive` and `post-restore`
+//!
+//! Its output goes to stderr, keeping slugpm's stdout to paths. A hook that fails fails the
+//! command, though the change it followed stays made.
+//!
+//! Hooks are reached through the injectable [`Hooks`] on `Config`, which runs none unless the
+//! CLI installs [`ScriptHooks`] (outside `--dry-run`).
+
+use std::{fmt::Debug, io, path::{Path, PathBuf}, process::{Command, Stdio}};
+use crate::{config_file, error::IoContext, Result, SlugpmError};
+
+/// When a hook runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookEvent {
+    PostCreate,
+    PostArchive,
+    PostRestore,
+}
+
+impl HookEvent {
+    /// The hook's file name: `post-create`, `post-archive` or `post-restore`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::PostCreate => "post-create",
+            HookEvent::PostArchive => "post-archive",
+            HookEvent::PostRestore => "post-restore",
+        }
+    }
+}
+
+/// One hook invocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HookCall<'a> {
+    pub event: HookEvent,
+    pub slug: &'a str,
+    pub path: &'a Path,
+    pub source: Option<&'a Path>,
+}
+
+pub trait Hooks: Debug + Send + Sync {
+    /// Runs the hook for `call.event`, if there is one.
+    fn run(&self, call: &HookCall) -> Result<()>;
+}
+
+/// Runs no hooks; the default.
+#[derive(Clone, Debug, Default)]
+pub struct NoHooks;
+
+impl Hooks for NoHooks {
+    fn run(&self, _call: &HookCall) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the executables named after each event in `dir`.
+#[derive(Clone, Debug)]
+pub struct ScriptHooks {
+    pub dir: PathBuf,
+}
+
+impl ScriptHooks {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ScriptHooks { dir: dir.into() }
+    }
+}
+
+impl Hooks for ScriptHooks {
+    fn run(&self, call: &HookCall) -> Result<()> {
+        let hook = self.dir.join(call.event.name());
+        if !is_executable(&hook) {
+            return Ok(());
+        }
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut command = Command::new(&hook);
+        command.env("SLUGPM_HOOK", call.event.name())
+            .env("SLUGPM_SLUG", call.slug)
+            .env("SLUGPM_PATH", absolute(call.path))
+            .env_remove("SLUGPM_SOURCE")
+            .stdin(Stdio::null())
+            .stdout(io::stderr());
+        if let Some(source) = call.source {
+            command.env("SLUGPM_SOURCE", absolute(source));
+        }
+        let status = command.status().io_context(|| format!("running hook {}", hook.display()))?;
+        if !status.success() {
+            return Err(SlugpmError::Other(format!("hook {} failed ({status})", hook.display())));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// `hooks/` beside the default config file.
+pub fn default_hooks_dir() -> Option<PathBuf> {
+    Some(config_file::default_config_path()?.parent()?.join("hooks"))
+}
@@ -0,0 +1,91 @@
+//! Numeric project IDs. With `ids` on, `create` names a project `<id>-<slug>`
+//! (`0042-my-project`) and records the number under `id` in its metadata, and commands that
+//! take a project also take that number (`slugpm open 42`).
+//!
+//! IDs come from a tally in the project root, [`ID_FILE`], which grows by one byte per ID
+//! handed out. Its length just before an [`FileOps::append_locked`] is the previous ID, so
+//! concurrent creates each get their own number without a read-modify-write. IDs are never
+//! reused, even when a create is undone.
+
+use std::{collections::BTreeMap, path::PathBuf};
+use crate::{list_projects, metadata, Config, FileOps, ProjectSort, Result};
+
+/// The tally file in the project root.
+pub const ID_FILE: &str = ".slugpm-ids";
+
+/// The metadata key holding a project's ID.
+pub const ID_KEY: &str = "id";
+
+/// `id` as it appears in directory names, zero-padded to four digits.
+pub fn format_id(id: u64) -> String {
+    format!("{id:04}")
+}
+
+/// A project's directory name with the ID `id`: `<id><separator><name>`.
+pub fn numbered_name(id: u64, name: &str, cfg: &Config) -> String {
+    format!("{}{}{name}", format_id(id), cfg.slug.separator)
+}
+
+/// The ID a query names: all ASCII digits (`42` or `0042`), nothing else.
+pub fn parse_id(query: &str) -> Option<u64> {
+    if query.is_empty() || !query.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    query.parse().ok()
+}
+
+/// The projects in the project root that have an ID in their metadata, by ID.
+pub fn project_ids(cfg: &Config, ops: &dyn FileOps) -> Result<BTreeMap<u64, PathBuf>> {
+    let mut ids = BTreeMap::new();
+    for entry in list_projects(false, ProjectSort::Name, cfg, ops)? {
+        let meta = metadata::read_metadata(&entry.path, ops).ok().flatten();
+        if let Some(id) = meta.as_ref().and_then(|meta| meta.get(ID_KEY)).and_then(parse_id) {
+            ids.insert(id, entry.path);
+        }
+    }
+    Ok(ids)
+}
+
+/// The project whose metadata has ID `id`.
+pub fn find_by_id(id: u64, cfg: &Config, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    Ok(project_ids(cfg, ops)?.remove(&id))
+}
+
+/// The project named `name` apart from its ID ([`numbered_name`]), if there is one.
+pub fn find_numbered(name: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    Ok(project_ids(cfg, ops)?.into_iter()
+        .find(|(id, path)| path.file_name().is_some_and(|n| *n == *numbered_name(*id, name, cfg)))
+        .map(|(_, path)| path))
+}
+
+/// The ID the next create would get, without taking it.
+pub fn next_id(cfg: &Config, ops: &dyn FileOps) -> Result<u64> {
+    let tally = tally_path(cfg);
+    let mut id = if ops.is_file(&tally) { ops.file_size(&tally)? } else { 0 } + 1;
+    let used = project_ids(cfg, ops)?;
+    while used.contains_key(&id) {
+        id += 1;
+    }
+    Ok(id)
+}
+
+/// Takes the next ID from the tally, skipping any a project already has (from a hand-edited
+/// or replaced tally).
+pub fn allocate_id(cfg: &Config, ops: &dyn FileOps) -> Result<u64> {
+    let tally = tally_path(cfg);
+    ops.create_dir_all(&cfg.project_root)?;
+    let used = project_ids(cfg, ops)?;
+    let mut last = 0;
+    loop {
+        let id = ops.append_locked(&tally, b"\n")? + 1;
+        // A tally that doesn't grow (a dry run) can't skip ahead; the ID is only shown.
+        if !used.contains_key(&id) || id <= last {
+            return Ok(id);
+        }
+        last = id;
+    }
+}
+
+fn tally_path(cfg: &Config) -> PathBuf {
+    cfg.project_root.join(ID_FILE)
+}
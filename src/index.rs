@@ -0,0 +1,82 @@
+//! The `archive/.index.json` record of everything archived with `--index`.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::{FileOps, Result, SlugpmError};
+
+/// Name of the index file at the root of an archive directory.
+pub const INDEX_FILE: &str = ".index.json";
+
+/// One archived item.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// The item's file or directory name before it was archived.
+    pub name: String,
+    pub dest: PathBuf,
+    /// Local time of archiving, `YYYY-MM-DDTHH:MM:SS`.
+    pub archived_at: String,
+    /// Bytes in the file, or in all files under the directory.
+    pub size: u64,
+}
+
+/// `<archive>/.index.json`.
+pub fn index_path(arch_root: &Path) -> PathBuf {
+    arch_root.join(INDEX_FILE)
+}
+
+/// Parses an index; blank text is an empty index.
+pub fn parse_index(text: &str) -> Result<Vec<IndexEntry>> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(text).map_err(|e| SlugpmError::Other(format!("parsing archive index: {e}")))
+}
+
+/// The index text with `entry` appended to `existing` (if any).
+pub fn append_to_index(existing: Option<&str>, entry: &IndexEntry) -> Result<String> {
+    let mut entries = parse_index(existing.unwrap_or(""))?;
+    entries.push(entry.clone());
+    let mut text = serde_json::to_string_pretty(&entries).map_err(|e| SlugpmError::Other(e.to_string()))?;
+    text.push('\n');
+    Ok(text)
+}
+
+/// Entries whose name or destination contains `term`, ignoring case.
+pub fn search_index<'a>(entries: &'a [IndexEntry], term: &str) -> Vec<&'a IndexEntry> {
+    let term = term.to_lowercase();
+    entries.iter()
+        .filter(|e| e.name.to_lowercase().contains(&term) || e.dest.to_string_lossy().to_lowercase().contains(&term))
+        .collect()
+}
+
+/// Reads the entries of the index under `arch_root`, or none if it doesn't exist yet.
+pub fn read_index(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<IndexEntry>> {
+    let path = index_path(arch_root);
+    if !ops.exists(&path) {
+        return Ok(Vec::new());
+    }
+    let bytes = ops.read(&path)?;
+    parse_index(&String::from_utf8_lossy(&bytes)).map_err(|e| SlugpmError::Other(format!("in {}: {e}", path.display())))
+}
+
+/// Appends `entry` to the index under `arch_root`, replacing the file in one rename so
+/// readers never see a partial write.
+pub fn record(arch_root: &Path, entry: &IndexEntry, ops: &dyn FileOps) -> Result<()> {
+    let path = index_path(arch_root);
+    let existing = if ops.exists(&path) { Some(ops.read(&path)?) } else { None };
+    let existing = existing.map(|b| String::from_utf8_lossy(&b).into_owned());
+    let text = append_to_index(existing.as_deref(), entry)
+        .map_err(|e| SlugpmError::Other(format!("in {}: {e}", path.display())))?;
+    let tmp = arch_root.join(format!("{INDEX_FILE}.tmp"));
+    ops.write(&tmp, text.as_bytes())?;
+    ops.rename(&tmp, &path)
+}
+
+/// Size of a file, or of all files under a directory.
+pub fn tree_size(path: &Path, ops: &dyn FileOps) -> Result<u64> {
+    let meta = ops.metadata(path)?;
+    if !meta.is_dir {
+        return Ok(meta.len);
+    }
+    ops.read_dir(path)?.iter().map(|entry| tree_size(entry, ops)).sum()
+}
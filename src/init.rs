@@ -0,0 +1,54 @@
+//! `slugpm init`: laying out a new slugpm root, with `project/`, the archive directory beside
+//! it, a starter [`ROOT_CONFIG_FILE`] and, if asked, `templates/`.
+
+use std::path::{Path, PathBuf};
+use crate::{config_file::ROOT_CONFIG_FILE, Config, FileOps, Result, SlugpmError};
+
+/// What [`init_root`] set up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InitOutcome {
+    /// The config file written.
+    pub config: PathBuf,
+    /// The directories that didn't exist before, in creation order.
+    pub created: Vec<PathBuf>,
+}
+
+/// The starter config: the layout `init` created, with the other settings commented out at
+/// their defaults.
+pub fn starter_config(archive_dir_name: &str, templates: bool) -> String {
+    let templates_dir = if templates { "templates_dir = \"templates\"" } else { "# templates_dir = \"templates\"" };
+    format!(
+        "# slugpm settings for this tree.\n\
+         project_root = \"project\"\n\
+         archive_dir_name = {archive_dir_name:?}\n\
+         {templates_dir}\n\
+         # date_format = \"%Y-%m-%d\"\n\
+         # dated = false\n\
+         # dated_archive = false\n\
+         # slug_separator = \"-\"\n\
+         # git = false\n"
+    )
+}
+
+/// Creates `dir/project/`, `dir/<archive>/` (named by `cfg.archive_dir_name`), with `templates`
+/// `dir/templates/`, and writes the starter config to `dir/.slugpm.toml`. Existing directories
+/// are kept; an existing config is a [`SlugpmError::Collision`] unless `force`, which rewrites it.
+pub fn init_root(dir: &Path, templates: bool, force: bool, cfg: &Config, ops: &dyn FileOps) -> Result<InitOutcome> {
+    let config = dir.join(ROOT_CONFIG_FILE);
+    if ops.exists(&config) && !force {
+        return Err(SlugpmError::Collision(config));
+    }
+    let mut dirs = vec![dir.join("project"), dir.join(&cfg.archive_dir_name)];
+    if templates {
+        dirs.push(dir.join("templates"));
+    }
+    let mut created = Vec::new();
+    for path in dirs {
+        if !ops.is_dir(&path) {
+            ops.create_dir_all(&path)?;
+            created.push(path);
+        }
+    }
+    ops.write(&config, starter_config(&cfg.archive_dir_name, templates).as_bytes())?;
+    Ok(InitOutcome { config, created })
+}
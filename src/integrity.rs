@@ -0,0 +1,190 @@
+//! Checksum sidecars for protected archives, the hash index of blocks appended with
+//! `--skip-duplicate`, and `slugpm verify`, which re-hashes archived items against the
+//! SHA-256s their manifest entries recorded at archive time (see [`crate::manifest`]).
+
+use std::{collections::BTreeMap, io::{BufRead, BufReader, Read}, path::{Path, PathBuf}};
+use sha2::{Digest, Sha256};
+use crate::{manifest, FileOps, Result, SlugpmError};
+
+/// Hex-encoded SHA-256 of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex(Sha256::digest(bytes).as_slice())
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Hex-encoded SHA-256 of everything `reader` yields, read through a buffer rather than all
+/// at once.
+pub fn sha256_reader(reader: impl Read) -> std::io::Result<String> {
+    let mut reader = BufReader::with_capacity(64 * 1024, reader);
+    let mut hasher = Sha256::new();
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(hex(hasher.finalize().as_slice()));
+        }
+        hasher.update(chunk);
+        let len = chunk.len();
+        reader.consume(len);
+    }
+}
+
+/// Hex-encoded SHA-256 of the file at `path`, streamed (see [`sha256_reader`]).
+pub fn sha256_file(path: &Path, ops: &dyn FileOps) -> Result<String> {
+    sha256_reader(ops.open_read(path)?).map_err(|error| SlugpmError::Io { context: format!("reading {}", path.display()), error })
+}
+
+/// SHA-256 of every file under `dir`, by path relative to it with `/` separators.
+pub fn tree_hashes(dir: &Path, ops: &dyn FileOps) -> Result<BTreeMap<String, String>> {
+    fn walk(root: &Path, dir: &Path, ops: &dyn FileOps, out: &mut BTreeMap<String, String>) -> Result<()> {
+        for entry in ops.read_dir(dir)? {
+            if ops.is_dir(&entry) && !ops.is_symlink(&entry) {
+                walk(root, &entry, ops, out)?;
+            } else if ops.is_file(&entry) {
+                let relative = entry.strip_prefix(root).unwrap_or(&entry);
+                let key = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                out.insert(key, sha256_file(&entry, ops)?);
+            }
+        }
+        Ok(())
+    }
+    let mut hashes = BTreeMap::new();
+    walk(dir, dir, ops, &mut hashes)?;
+    Ok(hashes)
+}
+
+/// `<dir>/<name>.sha256` for an archived file at `<dir>/<name>`.
+pub fn sidecar_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    file.with_file_name(name)
+}
+
+/// Sidecar contents in `sha256sum` format, so `sha256sum -c` can check it too.
+pub fn sidecar_contents(file: &Path, bytes: &[u8]) -> String {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    format!("{}  {}\n", sha256_hex(bytes), name)
+}
+
+/// Writes the sidecar for `file`, whose contents are `bytes`.
+pub fn write_sidecar(file: &Path, bytes: &[u8], ops: &dyn FileOps) -> Result<()> {
+    ops.write(&sidecar_path(file), sidecar_contents(file, bytes).as_bytes())
+}
+
+/// Checks `file` against its sidecar, if it has one.
+///
+/// Files without a sidecar were never protected and always pass.
+pub fn verify_sidecar(file: &Path, ops: &dyn FileOps) -> Result<()> {
+    let sidecar = sidecar_path(file);
+    if !ops.exists(&sidecar) {
+        return Ok(());
+    }
+    let recorded = String::from_utf8_lossy(&ops.read(&sidecar)?).into_owned();
+    let recorded = recorded.split_whitespace().next().unwrap_or("");
+    let actual = sha256_file(file, ops)?;
+    if recorded != actual {
+        return Err(SlugpmError::Other(format!("refusing to overwrite {}: contents do not match {}", file.display(), sidecar.display())));
+    }
+    Ok(())
+}
+
+/// `<dir>/.<name>.blocks`, the hashes of the blocks appended to `<dir>/<name>` with
+/// `--skip-duplicate`, one per line.
+pub fn block_index_path(file: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(file.file_name().unwrap_or_default());
+    name.push(".blocks");
+    file.with_file_name(name)
+}
+
+/// Whether the file name `name` is a [`block_index_path`].
+pub fn is_block_index(name: &str) -> bool {
+    name.starts_with('.') && name.ends_with(".blocks")
+}
+
+/// Whether appending `block` to `file` would repeat one: the file already ends with it, or
+/// its hash is in the file's block index.
+pub fn is_duplicate_block(file: &Path, block: &[u8], ops: &dyn FileOps) -> Result<bool> {
+    if ops.is_file(file) && ops.file_size(file)? >= block.len() as u64 && ops.read(file)?.ends_with(block) {
+        return Ok(true);
+    }
+    let index = block_index_path(file);
+    if !ops.is_file(&index) {
+        return Ok(false);
+    }
+    let hash = sha256_hex(block);
+    Ok(ops.read_to_string(&index)?.lines().any(|line| line.trim() == hash))
+}
+
+/// Adds `block`'s hash to `file`'s block index.
+pub fn record_block(file: &Path, block: &[u8], ops: &dyn FileOps) -> Result<()> {
+    ops.append_locked(&block_index_path(file), format!("{}\n", sha256_hex(block)).as_bytes()).map(drop)
+}
+
+/// How a file of an archived item differs from what its manifest entry recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Problem {
+    /// Its contents no longer hash to the recorded SHA-256.
+    Changed,
+    /// It was recorded but is gone.
+    Missing,
+    /// It wasn't there when the directory was archived.
+    Added,
+}
+
+impl Problem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Problem::Changed => "changed",
+            Problem::Missing => "missing",
+            Problem::Added => "added",
+        }
+    }
+}
+
+/// What `verify` found for one archived item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verified {
+    /// The item in the archive directory.
+    pub path: PathBuf,
+    /// Whether its manifest entry has hashes to check; items archived before they were
+    /// recorded can't be verified.
+    pub recorded: bool,
+    /// The files that don't match, sorted: the item itself for an archived file, files inside
+    /// it for a directory.
+    pub problems: Vec<(PathBuf, Problem)>,
+}
+
+/// Re-hashes each item that should be in `arch_root` according to its manifest (see
+/// [`manifest::archived_entries`]) and compares it with the hashes recorded when it was
+/// archived. An item that is gone is [`Problem::Missing`].
+pub fn verify_archive(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<Verified>> {
+    let mut verified = Vec::new();
+    for entry in manifest::archived_entries(arch_root, ops)? {
+        let path = arch_root.join(&entry.path);
+        let mut problems = Vec::new();
+        if !ops.exists(&path) && !ops.is_symlink(&path) {
+            problems.push((path.clone(), Problem::Missing));
+        } else if let Some(recorded) = &entry.sha256 {
+            if sha256_file(&path, ops)? != *recorded {
+                problems.push((path.clone(), Problem::Changed));
+            }
+        } else if !entry.files.is_empty() {
+            let mut current = tree_hashes(&path, ops)?;
+            for (file, recorded) in &entry.files {
+                match current.remove(file) {
+                    Some(hash) if hash == *recorded => {}
+                    Some(_) => problems.push((path.join(file), Problem::Changed)),
+                    None => problems.push((path.join(file), Problem::Missing)),
+                }
+            }
+            problems.extend(current.into_keys().map(|file| (path.join(file), Problem::Added)));
+            problems.sort();
+        }
+        let recorded = entry.sha256.is_some() || !entry.files.is_empty();
+        verified.push(Verified { path, recorded, problems });
+    }
+    Ok(verified)
+}
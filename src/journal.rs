@@ -0,0 +1,340 @@
+//! The undo journal: `<project_root>/.slugpm-journal`, one JSON object per line for each change
+//! slugpm made, most recent last. [`undo_last`] reverts and drops the last one.
+//!
+//! Nothing is recorded unless `Config::journal` is set (the CLI sets it outside `--dry-run`), or
+//! while the project root doesn't exist, so archiving elsewhere doesn't create one.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use crate::{compress, copy, enclosing_archive_dir_pure, integrity, links, manifest, metadata, move_path, Config, FileOps, Result, SlugpmError};
+
+/// Name of the journal file in the project root.
+pub const JOURNAL_FILE: &str = ".slugpm-journal";
+
+/// A change that can be reverted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// A project directory was created, with `files` (relative to it, such as its `project.md`)
+    /// and their SHA-256 hashes.
+    Create {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        files: BTreeMap<PathBuf, String>,
+    },
+    /// `from` was archived to `to`: moved, hard-linked (`link`), packed into a `.tar.zst`
+    /// (`compressed`), or merged into an existing directory (`merge`, which can't be undone).
+    Archive {
+        from: PathBuf,
+        to: PathBuf,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        link: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        merge: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        compressed: bool,
+    },
+    /// Content was appended to the archive file `path`, which was `previous_len` bytes long
+    /// before (`None` if it didn't exist): `appended` bytes with the SHA-256 `sha256`. Entries
+    /// from before the last two were recorded are undone without checking them.
+    Append {
+        path: PathBuf,
+        previous_len: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        appended: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// `text` was inserted into the file `path` at byte `offset`, creating the file if `created`.
+    Insert {
+        path: PathBuf,
+        offset: u64,
+        text: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        created: bool,
+    },
+    /// The archived item `from` was restored to `to`, unpacking it if `compressed`.
+    Restore {
+        from: PathBuf,
+        to: PathBuf,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        compressed: bool,
+    },
+    /// `from` was moved to the trash at `to`, with the trash record `info` if the trash keeps one.
+    Trash { from: PathBuf, to: PathBuf, info: Option<PathBuf> },
+    /// The project `from` was renamed to `to`; its metadata had `previous_title`, if it had
+    /// metadata, and `symlink` was left at `from`.
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        previous_title: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        symlink: bool,
+    },
+    /// `from` was moved into a project at `to`, or copied there (`copy`).
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        copy: bool,
+    },
+    /// The tags of the project `dir` were changed; it had `previous`.
+    Tag { dir: PathBuf, previous: Vec<String> },
+    /// The status in the project `dir`'s metadata was set; it was `previous`.
+    Status { dir: PathBuf, previous: String },
+    /// Two projects were linked: each changed project's directory and its `links` key before
+    /// (`None` without one).
+    Link { previous: Vec<(PathBuf, Option<String>)> },
+}
+
+impl Operation {
+    /// [`Operation::Append`] of `content` to `path`, which was `previous_len` bytes long before.
+    pub fn append(path: &Path, previous_len: Option<u64>, content: &[u8]) -> Self {
+        Operation::Append {
+            path: path.to_path_buf(),
+            previous_len,
+            appended: Some(content.len() as u64),
+            sha256: Some(integrity::sha256_hex(content)),
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Create { path, .. } => write!(f, "create {}", path.display()),
+            Operation::Archive { from, to, .. } => write!(f, "archive {} -> {}", from.display(), to.display()),
+            Operation::Append { path, .. } => write!(f, "append to {}", path.display()),
+            Operation::Insert { path, .. } => write!(f, "insert into {}", path.display()),
+            Operation::Restore { from, to, .. } => write!(f, "restore {} -> {}", from.display(), to.display()),
+            Operation::Trash { from, to, .. } => write!(f, "trash {} -> {}", from.display(), to.display()),
+            Operation::Rename { from, to, .. } => write!(f, "rename {} -> {}", from.display(), to.display()),
+            Operation::Move { from, to, copy: false } => write!(f, "move {} -> {}", from.display(), to.display()),
+            Operation::Move { from, to, copy: true } => write!(f, "copy {} -> {}", from.display(), to.display()),
+            Operation::Tag { dir, .. } => write!(f, "tag {}", dir.display()),
+            Operation::Status { dir, .. } => write!(f, "set status of {}", dir.display()),
+            Operation::Link { previous } => {
+                let dirs: Vec<String> = previous.iter().map(|(dir, _)| dir.display().to_string()).collect();
+                write!(f, "link {}", dirs.join(" <-> "))
+            }
+        }
+    }
+}
+
+/// One journal line.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Local time of the change, `YYYY-MM-DDTHH:MM:SS`.
+    pub at: String,
+    #[serde(flatten)]
+    pub op: Operation,
+}
+
+/// `<project_root>/.slugpm-journal`.
+pub fn journal_path(cfg: &Config) -> PathBuf {
+    cfg.project_root.join(JOURNAL_FILE)
+}
+
+/// Appends `op` to the journal, if `cfg.journal` is set and the project root exists.
+pub fn record(op: Operation, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    if !cfg.journal || !ops.is_dir(&cfg.project_root) {
+        return Ok(());
+    }
+    let entry = JournalEntry { at: cfg.clock.now().format("%Y-%m-%dT%H:%M:%S").to_string(), op };
+    let mut line = serde_json::to_string(&entry).map_err(|e| SlugpmError::Other(e.to_string()))?;
+    line.push('\n');
+    let path = journal_path(cfg);
+    // Locked, as concurrent appends each record a line.
+    ops.append_locked(&path, line.as_bytes()).map(drop)
+}
+
+/// Parses journal text; blank lines are skipped.
+pub fn parse_journal(text: &str) -> Result<Vec<JournalEntry>> {
+    text.lines().filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| SlugpmError::Other(format!("parsing journal: {e}"))))
+        .collect()
+}
+
+/// The journal's entries, oldest first; empty if there's no journal.
+pub fn read_journal(cfg: &Config, ops: &dyn FileOps) -> Result<Vec<JournalEntry>> {
+    let path = journal_path(cfg);
+    if !ops.exists(&path) {
+        return Ok(Vec::new());
+    }
+    parse_journal(&String::from_utf8_lossy(&ops.read(&path)?))
+}
+
+/// Reverts `op`, refusing anything that would overwrite or throw away later changes.
+pub fn revert(op: &Operation, ops: &dyn FileOps) -> Result<()> {
+    match op {
+        Operation::Create { path, files } => {
+            if !ops.is_dir(path) {
+                return Err(SlugpmError::not_found(path));
+            }
+            let mut found = Vec::new();
+            tree_files(path, ops, &mut found)?;
+            for file in found {
+                let unchanged = files.get(file.strip_prefix(path).unwrap_or(&file))
+                    .is_some_and(|hash| integrity::sha256_hex(&ops.read(&file).unwrap_or_default()) == *hash);
+                if !unchanged {
+                    return Err(SlugpmError::Other(format!("{} has changed since it was created; remove it by hand to undo", file.display())));
+                }
+            }
+            copy::remove_tree(path, ops)
+        }
+        Operation::Archive { merge: true, from, to, .. } => {
+            Err(SlugpmError::Other(format!("cannot undo merging {} into {}", from.display(), to.display())))
+        }
+        Operation::Archive { from, to, link, compressed, .. } => {
+            if !ops.exists(to) {
+                return Err(SlugpmError::not_found(to));
+            }
+            if *link {
+                ops.remove_file(to)?;
+            } else if *compressed {
+                compress::unpack_to(to, from, ops)?;
+                ops.remove_file(to)?;
+            } else {
+                move_back(to, from, ops)?;
+            }
+            let sidecar = integrity::sidecar_path(to);
+            if ops.is_file(&sidecar) {
+                ops.remove_file(&sidecar)?;
+            }
+            Ok(())
+        }
+        Operation::Append { path, previous_len, appended, sha256 } => {
+            let mut contents = ops.read(path)?;
+            let start = previous_len.unwrap_or(0);
+            if let (Some(appended), Some(sha256)) = (appended, sha256) {
+                let tail = contents.get(start as usize..).filter(|tail| tail.len() as u64 == *appended);
+                if tail.map(integrity::sha256_hex).as_deref() != Some(sha256.as_str()) {
+                    return Err(SlugpmError::Other(format!("{} has changed since the append; edit it by hand to undo", path.display())));
+                }
+            }
+            if previous_len.is_none() {
+                return ops.remove_file(path);
+            }
+            contents.truncate(start as usize);
+            ops.write(path, &contents)
+        }
+        Operation::Insert { path, offset, text, created } => {
+            let mut contents = ops.read(path)?;
+            let range = *offset as usize..*offset as usize + text.len();
+            if contents.get(range.clone()) != Some(text.as_bytes()) {
+                return Err(SlugpmError::Other(format!("{} has changed where the text was inserted; edit it by hand to undo", path.display())));
+            }
+            contents.drain(range);
+            if *created && contents.is_empty() {
+                return ops.remove_file(path);
+            }
+            ops.write(path, &contents)
+        }
+        Operation::Restore { from, to, compressed: false } => move_back(to, from, ops),
+        Operation::Restore { from, to, compressed: true } => {
+            if ops.exists(from) {
+                return Err(SlugpmError::Collision(from.clone()));
+            }
+            compress::pack_dir(to, from, ops)?;
+            copy::remove_tree(to, ops)
+        }
+        Operation::Trash { from, to, info } => {
+            move_back(to, from, ops)?;
+            match info {
+                Some(info) if ops.is_file(info) => ops.remove_file(info),
+                _ => Ok(()),
+            }
+        }
+        Operation::Rename { from, to, previous_title, symlink } => {
+            if *symlink && ops.is_symlink(from) {
+                ops.remove_file(from)?;
+            }
+            if from != to {
+                move_back(to, from, ops)?;
+            }
+            if let Some(title) = previous_title {
+                let old_name = from.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                metadata::update_metadata(from, ops, |meta| {
+                    meta.title = title.clone();
+                    meta.slug = old_name.clone();
+                    let aliases: Vec<String> = meta.aliases().into_iter().filter(|a| *a != old_name).map(String::from).collect();
+                    meta.set_aliases(&aliases);
+                })?;
+            }
+            Ok(())
+        }
+        Operation::Move { from, to, copy: false } => move_back(to, from, ops),
+        Operation::Move { to, copy: true, .. } => {
+            if !ops.exists(to) {
+                return Err(SlugpmError::not_found(to));
+            }
+            copy::remove_tree(to, ops)
+        }
+        Operation::Tag { dir, previous } => metadata::update_metadata(dir, ops, |meta| meta.set_tags(previous)).map(drop),
+        Operation::Status { dir, previous } => metadata::update_metadata(dir, ops, |meta| meta.status = previous.clone()).map(drop),
+        Operation::Link { previous } => previous.iter().try_for_each(|(dir, value)| {
+            metadata::update_metadata(dir, ops, |meta| match value {
+                Some(value) => meta.set(links::LINKS_KEY, value.clone()),
+                None => meta.remove(links::LINKS_KEY),
+            }).map(drop)
+        }),
+    }
+}
+
+/// Every file under `dir`.
+fn tree_files(dir: &Path, ops: &dyn FileOps, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in ops.read_dir(dir)? {
+        if ops.is_dir(&entry) {
+            tree_files(&entry, ops, out)?;
+        } else {
+            out.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Moves `from` back to `to`, which must be free again.
+fn move_back(from: &Path, to: &Path, ops: &dyn FileOps) -> Result<()> {
+    if ops.exists(to) {
+        return Err(SlugpmError::Collision(to.to_path_buf()));
+    }
+    if !ops.exists(from) {
+        return Err(SlugpmError::not_found(from));
+    }
+    if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+        ops.create_dir_all(parent)?;
+    }
+    move_path(from, to, ops)
+}
+
+/// Reverts the most recent journal entry and removes it from the journal, returning it.
+pub fn undo_last(cfg: &Config, ops: &dyn FileOps) -> Result<JournalEntry> {
+    let mut entries = read_journal(cfg, ops)?;
+    let entry = entries.pop().ok_or_else(|| SlugpmError::NotFound("nothing to undo".into()))?;
+    revert(&entry.op, ops)?;
+    // Keep the manifest's record of what should be archived in step.
+    let archived = match &entry.op {
+        Operation::Archive { to, .. } => Some((to, true)),
+        Operation::Restore { from, .. } => Some((from, false)),
+        _ => None,
+    };
+    if let Some((archived, removed)) = archived.filter(|_| cfg.manifest) {
+        if let Some(arch_root) = enclosing_archive_dir_pure(archived.parent().unwrap_or(archived), &cfg.archive_dir_name) {
+            manifest::record_removed(arch_root, archived, removed, cfg, ops)?;
+        }
+    }
+    let path = journal_path(cfg);
+    if entries.is_empty() {
+        ops.remove_file(&path)?;
+        return Ok(entry);
+    }
+    let mut text = String::new();
+    for entry in &entries {
+        text += &serde_json::to_string(entry).map_err(|e| SlugpmError::Other(e.to_string()))?;
+        text.push('\n');
+    }
+    let tmp = path.with_file_name(format!("{JOURNAL_FILE}.tmp"));
+    ops.write(&tmp, text.as_bytes())?;
+    ops.rename(&tmp, &path)?;
+    Ok(entry)
+}
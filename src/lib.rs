@@ -1,9 +1,52 @@
 //! Core logic for slugpm, extracted for testability.
 
-use std::{io::{self, Read, Write}, path::{Path, PathBuf}};
-use anyhow::{Result, Context};
+use std::{collections::{BTreeMap, BTreeSet}, io::{self, Read, Write}, path::{Component, Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::SystemTime};
+use chrono::{NaiveDate, NaiveDateTime};
 use slug::slugify;
 
+pub mod compress;
+pub mod copy;
+pub mod config_file;
+pub mod date_prefix;
+pub mod dedupe;
+pub mod hooks;
+pub mod ids;
+pub mod index;
+pub mod init;
+pub mod integrity;
+pub mod journal;
+pub mod links;
+pub mod manifest;
+pub mod metadata;
+pub mod pick;
+pub mod prune;
+pub mod recent;
+pub mod report;
+pub mod rules;
+pub mod safe_path;
+pub mod search;
+pub mod status;
+pub mod tags;
+pub mod template;
+pub mod trash;
+pub mod vcs;
+pub mod watch;
+mod cli;
+mod clock;
+mod command;
+mod error;
+mod output;
+#[cfg(feature = "progress")]
+mod progress;
+#[cfg(feature = "tui")]
+mod tui;
+
+pub use cli::{complete, completion_shell, run, run_with, Context, COMPLETE_VAR};
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use command::{CommandRunner, ShellRunner};
+pub use error::{io_exit, Result, SlugpmError, EXIT_COLLISION, EXIT_FAILURE, EXIT_INVALID_INPUT, EXIT_IO, EXIT_NOT_FOUND, EXIT_PERMISSION_DENIED};
+use error::IoContext;
+
 pub fn archive_dir_for_file_pure(parent: &Path) -> PathBuf {
     parent.join("archive")
 }
@@ -12,27 +55,252 @@ pub fn archive_dir_for_dir_pure(parent: &Path) -> PathBuf {
     parent.parent().unwrap_or(parent).join("archive")
 }
 
-pub trait FileOps {
+/// `YYYY/YYYY-MM-DD`: where a dated archive files items archived on `date`.
+pub fn dated_subdir(date: NaiveDate) -> PathBuf {
+    Path::new(&date.format("%Y").to_string()).join(date.format("%Y-%m-%d").to_string())
+}
+
+/// [`archive_dir_for_file_pure`] with the [`dated_subdir`] for `date`.
+pub fn archive_dir_for_file_dated_pure(parent: &Path, date: NaiveDate) -> PathBuf {
+    archive_dir_for_file_pure(parent).join(dated_subdir(date))
+}
+
+/// [`archive_dir_for_dir_pure`] with the [`dated_subdir`] for `date`.
+pub fn archive_dir_for_dir_dated_pure(parent: &Path, date: NaiveDate) -> PathBuf {
+    archive_dir_for_dir_pure(parent).join(dated_subdir(date))
+}
+
+/// What [`FileOps::metadata`] reports about a path, following symlinks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub modified: SystemTime,
+    /// Size in bytes; 0 for directories.
+    pub len: u64,
+    pub is_dir: bool,
+    /// Permission bits, such as `0o644`. Without Unix permissions, `0o444` (`0o555` for a
+    /// directory) when read-only and [`default_mode`] otherwise.
+    pub mode: u32,
+}
+
+/// The permission bits of a new file (`0o644`) or directory (`0o755`) without a umask.
+pub fn default_mode(is_dir: bool) -> u32 {
+    if is_dir { 0o755 } else { 0o644 }
+}
+
+pub trait FileOps: Send + Sync {
     fn create_dir_all(&self, path: &Path) -> Result<()>;
     fn rename(&self, from: &Path, to: &Path) -> Result<()>;
     fn open_append(&self, path: &Path) -> Result<Box<dyn Write>>;
+    /// Opens a file for reading as a stream, for contents too large to [`read`](FileOps::read) at once.
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>>;
+    /// Appends `contents` to `path` (creating it) in a single write while holding an exclusive
+    /// advisory lock on the file, so concurrent appends don't interleave. Returns the file's
+    /// length just before the write.
+    fn append_locked(&self, path: &Path, contents: &[u8]) -> Result<u64>;
+    fn exists(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+    /// Reads a UTF-8 file; other contents are an error.
+    fn read_to_string(&self, path: &Path) -> Result<String>;
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+    /// Creates (or truncates) `path` and opens it for writing as a stream, for contents too
+    /// large to [`write`](FileOps::write) at once.
+    fn create(&self, path: &Path) -> Result<Box<dyn Write>>;
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Entries directly inside `path`, sorted.
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: &Path) -> Result<()>;
+    /// Removes a directory and everything in it. A symlink is removed itself, not followed.
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+    /// Whether new entries can be created inside the directory `path`.
+    fn is_writable(&self, path: &Path) -> bool;
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()>;
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    /// Last modification time of `path`.
+    fn modified(&self, path: &Path) -> Result<SystemTime>;
+    /// Size of the file at `path`, in bytes.
+    fn file_size(&self, path: &Path) -> Result<u64>;
+    /// Modification time, size and kind of `path` at once.
+    fn metadata(&self, path: &Path) -> Result<FileMetadata>;
+    /// Whether `path` itself is a symbolic link, whether or not its target exists.
+    fn is_symlink(&self, path: &Path) -> bool;
+    fn read_link(&self, path: &Path) -> Result<PathBuf>;
+    /// Creates a symbolic link at `link` to the directory `target`, which is relative to the link's directory.
+    fn symlink_dir(&self, target: &Path, link: &Path) -> Result<()>;
+    /// [`symlink_dir`](FileOps::symlink_dir) for a `target` that is a file, or doesn't exist.
+    fn symlink_file(&self, target: &Path, link: &Path) -> Result<()>;
+    /// Gives the file `to` the permissions and modification time of `from`.
+    fn copy_attributes(&self, from: &Path, to: &Path) -> Result<()>;
+    /// Sets the permission bits of `path` (see [`FileMetadata::mode`]); without Unix
+    /// permissions, only whether it is read-only.
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()>;
+    /// Copies the contents of the file `from` to `to` (creating or truncating it) in chunks of
+    /// at most [`COPY_CHUNK`] bytes, calling `copied` with the size of each once it is written.
+    /// An error from `copied` stops the copy, leaving `to` partly written.
+    fn copy_file(&self, from: &Path, to: &Path, copied: &mut dyn FnMut(u64) -> Result<()>) -> Result<()>;
 }
 
+/// The most [`FileOps::copy_file`] holds in memory at once.
+pub const COPY_CHUNK: usize = 64 * 1024;
+
 pub struct RealFileOps;
 impl FileOps for RealFileOps {
     fn create_dir_all(&self, path: &Path) -> Result<()> {
-        std::fs::create_dir_all(path)?;
-        Ok(())
+        std::fs::create_dir_all(path).io_context(|| format!("creating {}", path.display()))
     }
     fn rename(&self, from: &Path, to: &Path) -> Result<()> {
-        std::fs::rename(from, to)?;
-        Ok(())
+        std::fs::rename(from, to).io_context(|| format!("moving {} -> {}", from.display(), to.display()))
     }
     fn open_append(&self, path: &Path) -> Result<Box<dyn Write>> {
-        Ok(Box::new(std::fs::OpenOptions::new().create(true).append(true).open(path)?))
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .io_context(|| format!("opening {}", path.display()))?;
+        Ok(Box::new(file))
+    }
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>> {
+        let file = std::fs::File::open(path).io_context(|| format!("opening {}", path.display()))?;
+        Ok(Box::new(file))
+    }
+    fn append_locked(&self, path: &Path, contents: &[u8]) -> Result<u64> {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .io_context(|| format!("opening {}", path.display()))?;
+        // `flock` on Unix, `LockFileEx` on Windows; waits for other writers and is released
+        // when `file` is closed.
+        file.lock().io_context(|| format!("locking {}", path.display()))?;
+        let start = file.metadata().io_context(|| format!("reading metadata of {}", path.display()))?.len();
+        file.write_all(contents).io_context(|| format!("appending to {}", path.display()))?;
+        Ok(start)
+    }
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        std::fs::read(path).io_context(|| format!("reading {}", path.display()))
+    }
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        std::fs::read_to_string(path).io_context(|| format!("reading {}", path.display()))
+    }
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        std::fs::write(path, contents).io_context(|| format!("writing {}", path.display()))
+    }
+    fn create(&self, path: &Path) -> Result<Box<dyn Write>> {
+        let file = std::fs::File::create(path).io_context(|| format!("creating {}", path.display()))?;
+        Ok(Box::new(io::BufWriter::new(file)))
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        let path = std::fs::canonicalize(path).io_context(|| format!("resolving path: {}", path.display()))?;
+        Ok(if cfg!(windows) { strip_verbatim_prefix(&path) } else { path })
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries = std::fs::read_dir(path)
+            .and_then(|entries| entries.map(|e| e.map(|e| e.path())).collect::<io::Result<Vec<_>>>())
+            .io_context(|| format!("reading {}", path.display()))?;
+        entries.sort();
+        Ok(entries)
+    }
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        std::fs::remove_dir(path).io_context(|| format!("removing {}", path.display()))
+    }
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        std::fs::remove_dir_all(path).io_context(|| format!("removing {}", path.display()))
+    }
+    fn is_writable(&self, path: &Path) -> bool {
+        // Permission bits don't account for ACLs, read-only mounts, etc., so just try it.
+        let probe = path.join(format!(".slugpm-write-probe-{}", std::process::id()));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+            Ok(_) => std::fs::remove_file(&probe).is_ok(),
+            Err(_) => false,
+        }
+    }
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        std::fs::hard_link(original, link).io_context(|| format!("linking {} -> {}", original.display(), link.display()))
+    }
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        std::fs::remove_file(path).io_context(|| format!("removing {}", path.display()))
+    }
+    fn modified(&self, path: &Path) -> Result<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).io_context(|| format!("reading metadata of {}", path.display()))
+    }
+    fn file_size(&self, path: &Path) -> Result<u64> {
+        std::fs::metadata(path).map(|m| m.len()).io_context(|| format!("reading metadata of {}", path.display()))
+    }
+    fn metadata(&self, path: &Path) -> Result<FileMetadata> {
+        let meta = std::fs::metadata(path).io_context(|| format!("reading metadata of {}", path.display()))?;
+        let modified = meta.modified().io_context(|| format!("reading metadata of {}", path.display()))?;
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o7777;
+        #[cfg(not(unix))]
+        let mode = if meta.permissions().readonly() { default_mode(meta.is_dir()) & 0o555 } else { default_mode(meta.is_dir()) };
+        Ok(FileMetadata { modified, len: if meta.is_dir() { 0 } else { meta.len() }, is_dir: meta.is_dir(), mode })
+    }
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        std::fs::read_link(path).io_context(|| format!("reading link {}", path.display()))
+    }
+    fn symlink_dir(&self, target: &Path, link: &Path) -> Result<()> {
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(target, link);
+        #[cfg(windows)]
+        let result = std::os::windows::fs::symlink_dir(target, link);
+        result.io_context(|| format!("linking {} -> {}", link.display(), target.display()))
+    }
+    fn symlink_file(&self, target: &Path, link: &Path) -> Result<()> {
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(target, link);
+        #[cfg(windows)]
+        let result = std::os::windows::fs::symlink_file(target, link);
+        result.io_context(|| format!("linking {} -> {}", link.display(), target.display()))
+    }
+    fn copy_attributes(&self, from: &Path, to: &Path) -> Result<()> {
+        let context = || format!("copying permissions of {} to {}", from.display(), to.display());
+        let meta = std::fs::metadata(from).io_context(context)?;
+        let modified = meta.modified().io_context(context)?;
+        // The time first: the permissions may make `to` read-only.
+        std::fs::OpenOptions::new().write(true).open(to)
+            .and_then(|file| file.set_modified(modified))
+            .io_context(context)?;
+        std::fs::set_permissions(to, meta.permissions()).io_context(context)
+    }
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        let context = || format!("setting permissions of {}", path.display());
+        #[cfg(unix)]
+        let permissions = std::os::unix::fs::PermissionsExt::from_mode(mode);
+        #[cfg(not(unix))]
+        let permissions = {
+            let mut permissions = std::fs::metadata(path).io_context(context)?.permissions();
+            permissions.set_readonly(mode & 0o222 == 0);
+            permissions
+        };
+        std::fs::set_permissions(path, permissions).io_context(context)
+    }
+    fn copy_file(&self, from: &Path, to: &Path, copied: &mut dyn FnMut(u64) -> Result<()>) -> Result<()> {
+        let mut reader = std::fs::File::open(from).io_context(|| format!("opening {}", from.display()))?;
+        let mut writer = std::fs::File::create(to).io_context(|| format!("creating {}", to.display()))?;
+        let mut buf = vec![0; COPY_CHUNK];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).io_context(|| format!("reading {}", from.display())),
+            };
+            writer.write_all(&buf[..n]).io_context(|| format!("writing {}", to.display()))?;
+            copied(n as u64)?;
+        }
     }
 }
 
+/// Accepts every change without keeping any of it, and reports that nothing exists. Use
+/// [`MemoryFileOps`] to check what an operation did.
 pub struct MockFileOps;
 impl FileOps for MockFileOps {
     fn create_dir_all(&self, _path: &Path) -> Result<()> { Ok(()) }
@@ -45,38 +313,2599 @@ impl FileOps for MockFileOps {
         }
         Ok(Box::new(Sink))
     }
+    fn open_read(&self, _path: &Path) -> Result<Box<dyn Read>> { Ok(Box::new(io::empty())) }
+    fn append_locked(&self, _path: &Path, _contents: &[u8]) -> Result<u64> { Ok(0) }
+    fn exists(&self, _path: &Path) -> bool { false }
+    fn read(&self, _path: &Path) -> Result<Vec<u8>> { Ok(Vec::new()) }
+    fn read_to_string(&self, _path: &Path) -> Result<String> { Ok(String::new()) }
+    fn write(&self, _path: &Path, _contents: &[u8]) -> Result<()> { Ok(()) }
+    fn create(&self, _path: &Path) -> Result<Box<dyn Write>> { Ok(Box::new(io::sink())) }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> { Ok(path.to_path_buf()) }
+    fn is_file(&self, _path: &Path) -> bool { false }
+    fn is_dir(&self, _path: &Path) -> bool { false }
+    fn read_dir(&self, _path: &Path) -> Result<Vec<PathBuf>> { Ok(Vec::new()) }
+    fn remove_dir(&self, _path: &Path) -> Result<()> { Ok(()) }
+    fn remove_dir_all(&self, _path: &Path) -> Result<()> { Ok(()) }
+    fn is_writable(&self, _path: &Path) -> bool { true }
+    fn hard_link(&self, _original: &Path, _link: &Path) -> Result<()> { Ok(()) }
+    fn remove_file(&self, _path: &Path) -> Result<()> { Ok(()) }
+    fn modified(&self, _path: &Path) -> Result<SystemTime> { Ok(SystemTime::UNIX_EPOCH) }
+    fn file_size(&self, _path: &Path) -> Result<u64> { Ok(0) }
+    fn metadata(&self, _path: &Path) -> Result<FileMetadata> { Ok(FileMetadata { modified: SystemTime::UNIX_EPOCH, len: 0, is_dir: false, mode: default_mode(false) }) }
+    fn is_symlink(&self, _path: &Path) -> bool { false }
+    fn read_link(&self, path: &Path) -> Result<PathBuf> { Err(SlugpmError::Other(format!("{} is not a symlink", path.display()))) }
+    fn symlink_dir(&self, _target: &Path, _link: &Path) -> Result<()> { Ok(()) }
+    fn symlink_file(&self, _target: &Path, _link: &Path) -> Result<()> { Ok(()) }
+    fn copy_attributes(&self, _from: &Path, _to: &Path) -> Result<()> { Ok(()) }
+    fn set_mode(&self, _path: &Path, _mode: u32) -> Result<()> { Ok(()) }
+    fn copy_file(&self, _from: &Path, _to: &Path, _copied: &mut dyn FnMut(u64) -> Result<()>) -> Result<()> { Ok(()) }
 }
 
-pub fn archive_move_file_with(file: &Path, ops: &dyn FileOps) -> Result<()> {
-    let arch_dir = archive_dir_for_file_pure(file.parent().unwrap());
-    ops.create_dir_all(&arch_dir)?;
-    let dest = arch_dir.join(file.file_name().unwrap());
-    ops.rename(file, &dest)
-        .with_context(|| format!("moving {} -> {}", file.display(), dest.display()))?;
-    Ok(())
+/// In-memory file system that keeps track of files, their contents and directories, so tests
+/// (slugpm's and downstream crates') can assert on the resulting tree rather than just on success.
+///
+/// Like a real file system, writing, appending or moving into a directory that doesn't exist
+/// fails. Symlinks are never followed: a link exists, but is neither a file nor a directory.
+/// Clones share the same tree.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFileOps {
+    files: Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>,
+    dirs: Arc<Mutex<BTreeSet<PathBuf>>>,
+    readonly: Arc<Mutex<BTreeSet<PathBuf>>>,
+    modified: Arc<Mutex<BTreeMap<PathBuf, SystemTime>>>,
+    modes: Arc<Mutex<BTreeMap<PathBuf, u32>>>,
+    /// Symlinks and their targets.
+    links: Arc<Mutex<BTreeMap<PathBuf, PathBuf>>>,
+    mounts: Arc<Mutex<BTreeSet<PathBuf>>>,
 }
 
-pub fn archive_move_dir_with(dir: &Path, ops: &dyn FileOps) -> Result<()> {
-    let arch_dir = archive_dir_for_dir_pure(dir.parent().unwrap());
-    ops.create_dir_all(&arch_dir)?;
-    let dest = arch_dir.join(dir.file_name().unwrap());
-    ops.rename(dir, &dest)
-        .with_context(|| format!("moving {} -> {}", dir.display(), dest.display()))?;
-    Ok(())
+impl MemoryFileOps {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file (and its parent directories) to the tree.
+    pub fn with_file(self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Self {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.add_dirs(parent);
+        }
+        self.files.lock().unwrap().insert(path.to_path_buf(), contents.as_ref().to_vec());
+        self
+    }
+
+    /// Adds a directory (and its parents) to the tree.
+    pub fn with_dir(self, path: impl AsRef<Path>) -> Self {
+        self.add_dirs(path.as_ref());
+        self
+    }
+
+    /// Adds a directory that reports itself as not writable.
+    pub fn with_readonly_dir(self, path: impl AsRef<Path>) -> Self {
+        self.add_dirs(path.as_ref());
+        self.readonly.lock().unwrap().insert(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets a file's modification time; files without one report the Unix epoch.
+    pub fn with_modified(self, path: impl AsRef<Path>, time: SystemTime) -> Self {
+        self.modified.lock().unwrap().insert(path.as_ref().to_path_buf(), time);
+        self
+    }
+
+    /// Sets a file's permission bits, e.g. `0o755`; see [`MemoryFileOps::mode`].
+    pub fn with_mode(self, path: impl AsRef<Path>, mode: u32) -> Self {
+        self.modes.lock().unwrap().insert(path.as_ref().to_path_buf(), mode);
+        self
+    }
+
+    /// Adds a symlink at `link` (and its parent directories) pointing to `target`.
+    pub fn with_symlink(self, link: impl AsRef<Path>, target: impl AsRef<Path>) -> Self {
+        let link = link.as_ref();
+        if let Some(parent) = link.parent() {
+            self.add_dirs(parent);
+        }
+        self.links.lock().unwrap().insert(link.to_path_buf(), target.as_ref().to_path_buf());
+        self
+    }
+
+    /// Makes `path` a separate file system: renames into or out of it fail as cross-device.
+    pub fn with_mount(self, path: impl AsRef<Path>) -> Self {
+        self.add_dirs(path.as_ref());
+        self.mounts.lock().unwrap().insert(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path.as_ref()).cloned()
+    }
+
+    /// A file's permission bits, if they were set (with [`MemoryFileOps::with_mode`] or
+    /// [`FileOps::set_mode`]) or copied.
+    pub fn mode(&self, path: impl AsRef<Path>) -> Option<u32> {
+        self.modes.lock().unwrap().get(path.as_ref()).copied()
+    }
+
+    /// Every file in the tree, sorted.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Every directory in the tree, sorted; parents of added paths included.
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        self.dirs.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The files and directories under `root` (not `root` itself) relative to it, sorted, with a
+    /// trailing `/` on directories and ` -> target` after symlinks: a compact picture of the
+    /// tree to assert on.
+    pub fn tree(&self, root: impl AsRef<Path>) -> Vec<String> {
+        let root = root.as_ref();
+        let files = self.files.lock().unwrap();
+        let dirs = self.dirs.lock().unwrap();
+        let links = self.links.lock().unwrap();
+        let relative = |path: &PathBuf| path.strip_prefix(root).ok().filter(|p| !p.as_os_str().is_empty()).map(|p| p.to_string_lossy().replace('\\', "/"));
+        let mut entries: Vec<String> = files.keys().filter_map(relative)
+            .chain(dirs.iter().filter_map(|d| relative(d).map(|d| format!("{d}/"))))
+            .chain(links.iter().filter_map(|(l, t)| relative(l).map(|l| format!("{l} -> {}", t.display()))))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// Fails like a real file system when `path`'s parent directory doesn't exist.
+    fn require_parent(&self, path: &Path, action: &str) -> Result<()> {
+        match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(parent) if !self.is_dir(parent) => {
+                Err(SlugpmError::Io { context: format!("{action} {}", path.display()), error: io::ErrorKind::NotFound.into() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn add_dirs(&self, path: &Path) {
+        let mut dirs = self.dirs.lock().unwrap();
+        for dir in path.ancestors().filter(|d| !d.as_os_str().is_empty()) {
+            dirs.insert(dir.to_path_buf());
+        }
+    }
+
+    /// The innermost mount `path` is on, if any.
+    fn mount_of(&self, path: &Path) -> Option<PathBuf> {
+        self.mounts.lock().unwrap().iter().filter(|m| path.starts_with(m)).max_by_key(|m| m.components().count()).cloned()
+    }
 }
 
-pub fn archive_append_stdin_with(file: &Path, ops: &dyn FileOps) -> Result<()> {
-    let arch_dir = archive_dir_for_file_pure(file.parent().unwrap());
-    ops.create_dir_all(&arch_dir)?;
-    let dest = arch_dir.join(file.file_name().unwrap());
-    let mut f = ops.open_append(&dest)
-        .with_context(|| format!("opening {}", dest.display()))?;
-    let mut buf = Vec::new();
-    io::stdin().read_to_end(&mut buf)?;
-    f.write_all(&buf)?;
-    Ok(())
+impl FileOps for MemoryFileOps {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.add_dirs(path);
+        Ok(())
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.require_parent(to, "moving to")?;
+        let failed = |kind: io::ErrorKind| Err(SlugpmError::Io { context: format!("moving {} -> {}", from.display(), to.display()), error: kind.into() });
+        if self.mount_of(from) != self.mount_of(to) {
+            return failed(io::ErrorKind::CrossesDevices);
+        }
+        if !self.exists(from) {
+            return Err(SlugpmError::not_found(from));
+        }
+        // Like `rename(2)`: a directory can only replace an empty directory, and a file no directory.
+        if self.is_dir(to) && from != to {
+            if !self.is_dir(from) {
+                return failed(io::ErrorKind::IsADirectory);
+            }
+            if !self.read_dir(to)?.is_empty() {
+                return failed(io::ErrorKind::DirectoryNotEmpty);
+            }
+            self.dirs.lock().unwrap().remove(to);
+        }
+        let moved = |map_key: &Path| to.join(map_key.strip_prefix(from).unwrap());
+        fn move_entries<V>(map: &mut BTreeMap<PathBuf, V>, from: &Path, moved: impl Fn(&Path) -> PathBuf) {
+            let keys: Vec<PathBuf> = map.keys().filter(|k| k.starts_with(from)).cloned().collect();
+            for key in keys {
+                let value = map.remove(&key).unwrap();
+                map.insert(moved(&key), value);
+            }
+        }
+        move_entries(&mut self.files.lock().unwrap(), from, moved);
+        move_entries(&mut self.modified.lock().unwrap(), from, moved);
+        move_entries(&mut self.modes.lock().unwrap(), from, moved);
+        move_entries(&mut self.links.lock().unwrap(), from, moved);
+        let mut dirs = self.dirs.lock().unwrap();
+        let keys: Vec<PathBuf> = dirs.iter().filter(|k| k.starts_with(from)).cloned().collect();
+        for key in keys {
+            dirs.remove(&key);
+            dirs.insert(moved(&key));
+        }
+        Ok(())
+    }
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write>> {
+        struct Appender {
+            files: Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>,
+            path: PathBuf,
+        }
+        impl Write for Appender {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.files.lock().unwrap().entry(self.path.clone()).or_default().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        }
+        self.require_parent(path, "opening")?;
+        self.files.lock().unwrap().entry(path.to_path_buf()).or_default();
+        Ok(Box::new(Appender { files: Arc::clone(&self.files), path: path.to_path_buf() }))
+    }
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+    fn append_locked(&self, path: &Path, contents: &[u8]) -> Result<u64> {
+        self.require_parent(path, "opening")?;
+        let mut files = self.files.lock().unwrap();
+        let file = files.entry(path.to_path_buf()).or_default();
+        let start = file.len() as u64;
+        file.extend_from_slice(contents);
+        Ok(start)
+    }
+    fn exists(&self, path: &Path) -> bool {
+        self.is_file(path) || self.is_dir(path) || self.is_symlink(path)
+    }
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.contents(path).ok_or_else(|| SlugpmError::not_found(path))
+    }
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|_| SlugpmError::Io { context: format!("reading {}", path.display()), error: io::ErrorKind::InvalidData.into() })
+    }
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.require_parent(path, "writing")?;
+        self.files.lock().unwrap().insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+    fn create(&self, path: &Path) -> Result<Box<dyn Write>> {
+        self.write(path, &[])?;
+        self.open_append(path)
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        if self.exists(path) { Ok(path.to_path_buf()) } else { Err(SlugpmError::not_found(path)) }
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(path)
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.lock().unwrap().contains(path)
+    }
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        let files = self.files.lock().unwrap();
+        let dirs = self.dirs.lock().unwrap();
+        let links = self.links.lock().unwrap();
+        let entries: BTreeSet<PathBuf> = files.keys().chain(dirs.iter()).chain(links.keys())
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect();
+        Ok(entries.into_iter().collect())
+    }
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.read_dir(path)?.is_empty() {
+            return Err(SlugpmError::Io { context: format!("removing {}", path.display()), error: io::ErrorKind::DirectoryNotEmpty.into() });
+        }
+        self.dirs.lock().unwrap().remove(path);
+        Ok(())
+    }
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        if !self.is_dir(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        self.files.lock().unwrap().retain(|p, _| !p.starts_with(path));
+        self.dirs.lock().unwrap().retain(|p| !p.starts_with(path));
+        self.modified.lock().unwrap().retain(|p, _| !p.starts_with(path));
+        self.modes.lock().unwrap().retain(|p, _| !p.starts_with(path));
+        self.links.lock().unwrap().retain(|p, _| !p.starts_with(path));
+        Ok(())
+    }
+    fn is_writable(&self, path: &Path) -> bool {
+        !self.readonly.lock().unwrap().contains(path)
+    }
+    /// Copies the contents; later writes to either path are not shared.
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let contents = self.read(original)?;
+        self.write(link, &contents)
+    }
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.modified.lock().unwrap().remove(path);
+        self.modes.lock().unwrap().remove(path);
+        if self.links.lock().unwrap().remove(path).is_some() {
+            return Ok(());
+        }
+        self.files.lock().unwrap().remove(path).map(|_| ()).ok_or_else(|| SlugpmError::not_found(path))
+    }
+    fn modified(&self, path: &Path) -> Result<SystemTime> {
+        if !self.exists(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        Ok(self.modified.lock().unwrap().get(path).copied().unwrap_or(SystemTime::UNIX_EPOCH))
+    }
+    fn file_size(&self, path: &Path) -> Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+    fn metadata(&self, path: &Path) -> Result<FileMetadata> {
+        let is_dir = self.is_dir(path);
+        let mode = self.mode(path).unwrap_or(default_mode(is_dir));
+        Ok(FileMetadata { modified: self.modified(path)?, len: if is_dir { 0 } else { self.file_size(path)? }, is_dir, mode })
+    }
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.links.lock().unwrap().contains_key(path)
+    }
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.links.lock().unwrap().get(path).cloned()
+            .ok_or_else(|| SlugpmError::Other(format!("{} is not a symlink", path.display())))
+    }
+    fn symlink_dir(&self, target: &Path, link: &Path) -> Result<()> {
+        self.symlink_file(target, link)
+    }
+    fn symlink_file(&self, target: &Path, link: &Path) -> Result<()> {
+        self.require_parent(link, "linking")?;
+        if self.exists(link) {
+            return Err(SlugpmError::Io { context: format!("linking {}", link.display()), error: io::ErrorKind::AlreadyExists.into() });
+        }
+        self.links.lock().unwrap().insert(link.to_path_buf(), target.to_path_buf());
+        Ok(())
+    }
+    fn copy_attributes(&self, from: &Path, to: &Path) -> Result<()> {
+        let modified = self.modified(from)?;
+        if !self.exists(to) {
+            return Err(SlugpmError::not_found(to));
+        }
+        self.modified.lock().unwrap().insert(to.to_path_buf(), modified);
+        let mode = self.mode(from);
+        if let Some(mode) = mode {
+            self.modes.lock().unwrap().insert(to.to_path_buf(), mode);
+        }
+        Ok(())
+    }
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        if !self.exists(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        self.modes.lock().unwrap().insert(path.to_path_buf(), mode);
+        Ok(())
+    }
+    fn copy_file(&self, from: &Path, to: &Path, copied: &mut dyn FnMut(u64) -> Result<()>) -> Result<()> {
+        let contents = self.read(from)?;
+        self.write(to, &[])?;
+        for chunk in contents.chunks(COPY_CHUNK) {
+            self.files.lock().unwrap().entry(to.to_path_buf()).or_default().extend_from_slice(chunk);
+            copied(chunk.len() as u64)?;
+        }
+        Ok(())
+    }
 }
 
-pub fn slugify_title(title: &str) -> String {
-    slugify(title)
+/// Wraps another [`FileOps`] for `--dry-run`: reads go to the wrapped file system, while
+/// changes are only recorded (see [`DryRunFileOps::actions`]) and then reflected in later
+/// reads, so a command runs exactly as it would for real.
+pub struct DryRunFileOps<'a> {
+    inner: &'a dyn FileOps,
+    /// Paths that would exist, and whether each is a directory.
+    added: Mutex<BTreeMap<PathBuf, bool>>,
+    removed: Mutex<BTreeSet<PathBuf>>,
+    /// Rename destinations and the sources their contents are still read from.
+    renamed: Mutex<BTreeMap<PathBuf, PathBuf>>,
+    written: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+    actions: Mutex<Vec<String>>,
+}
+
+impl<'a> DryRunFileOps<'a> {
+    pub fn new(inner: &'a dyn FileOps) -> Self {
+        DryRunFileOps {
+            inner,
+            added: Mutex::default(),
+            removed: Mutex::default(),
+            renamed: Mutex::default(),
+            written: Mutex::default(),
+            actions: Mutex::default(),
+        }
+    }
+
+    /// What would have been done, in order, e.g. `move a.txt -> archive/a.txt`.
+    pub fn actions(&self) -> Vec<String> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    fn record(&self, action: String) {
+        self.actions.lock().unwrap().push(action);
+    }
+
+    /// Where the wrapped file system still has `path`'s contents.
+    fn origin(&self, path: &Path) -> PathBuf {
+        let renamed = self.renamed.lock().unwrap();
+        renamed.iter().rev()
+            .find_map(|(to, from)| path.strip_prefix(to).ok().map(|rest| from.join(rest)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// `Some(is_dir)` if `path` would exist.
+    fn kind(&self, path: &Path) -> Option<bool> {
+        if let Some(&is_dir) = self.added.lock().unwrap().get(path) {
+            return Some(is_dir);
+        }
+        if self.removed.lock().unwrap().iter().any(|r| path.starts_with(r)) {
+            return None;
+        }
+        let real = self.origin(path);
+        if self.inner.is_dir(&real) { Some(true) } else if self.inner.is_file(&real) { Some(false) } else { None }
+    }
+
+    fn add(&self, path: &Path, is_dir: bool) {
+        self.removed.lock().unwrap().remove(path);
+        self.added.lock().unwrap().insert(path.to_path_buf(), is_dir);
+    }
+
+    fn remove(&self, path: &Path) {
+        self.added.lock().unwrap().remove(path);
+        self.removed.lock().unwrap().insert(path.to_path_buf());
+    }
+
+}
+
+impl FileOps for DryRunFileOps<'_> {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let missing: Vec<&Path> = path.ancestors().filter(|d| !d.as_os_str().is_empty() && self.kind(d).is_none()).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        for dir in missing {
+            self.add(dir, true);
+        }
+        self.record(format!("create directory {}", path.display()));
+        Ok(())
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let is_dir = self.kind(from).ok_or_else(|| SlugpmError::not_found(from))?;
+        let origin = self.origin(from);
+        self.remove(from);
+        self.add(to, is_dir);
+        self.renamed.lock().unwrap().insert(to.to_path_buf(), origin);
+        self.record(format!("move {} -> {}", from.display(), to.display()));
+        Ok(())
+    }
+    fn open_append(&self, path: &Path) -> Result<Box<dyn Write>> {
+        self.add(path, false);
+        self.record(format!("append to {}", path.display()));
+        Ok(Box::new(io::sink()))
+    }
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>> {
+        if let Some(contents) = self.written.lock().unwrap().get(path) {
+            return Ok(Box::new(io::Cursor::new(contents.clone())));
+        }
+        match self.kind(path) {
+            Some(_) => self.inner.open_read(&self.origin(path)),
+            None => Err(SlugpmError::not_found(path)),
+        }
+    }
+    fn append_locked(&self, path: &Path, _contents: &[u8]) -> Result<u64> {
+        let start = if self.is_file(path) { self.file_size(path)? } else { 0 };
+        self.add(path, false);
+        self.record(format!("append to {}", path.display()));
+        Ok(start)
+    }
+    fn exists(&self, path: &Path) -> bool {
+        self.kind(path).is_some()
+    }
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(contents) = self.written.lock().unwrap().get(path) {
+            return Ok(contents.clone());
+        }
+        match self.kind(path) {
+            Some(_) => self.inner.read(&self.origin(path)),
+            None => Err(SlugpmError::not_found(path)),
+        }
+    }
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|_| SlugpmError::Io { context: format!("reading {}", path.display()), error: io::ErrorKind::InvalidData.into() })
+    }
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.add(path, false);
+        self.written.lock().unwrap().insert(path.to_path_buf(), contents.to_vec());
+        self.record(format!("write {}", path.display()));
+        Ok(())
+    }
+    /// What is written isn't kept: the file reads as empty afterwards.
+    fn create(&self, path: &Path) -> Result<Box<dyn Write>> {
+        self.write(path, &[])?;
+        Ok(Box::new(io::sink()))
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        if self.inner.exists(path) || self.kind(path).is_none() {
+            return self.inner.canonicalize(path);
+        }
+        Ok(path.to_path_buf())
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        self.kind(path) == Some(false)
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        self.kind(path) == Some(true)
+    }
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        let real = self.origin(path);
+        let mut entries: BTreeSet<PathBuf> = BTreeSet::new();
+        if self.inner.is_dir(&real) {
+            for entry in self.inner.read_dir(&real)? {
+                entries.insert(path.join(entry.file_name().unwrap_or_default()));
+            }
+        }
+        entries.extend(self.added.lock().unwrap().keys().filter(|p| p.parent() == Some(path)).cloned());
+        Ok(entries.into_iter().filter(|e| self.kind(e).is_some()).collect())
+    }
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.read_dir(path)?.is_empty() {
+            return Err(SlugpmError::Io { context: format!("removing {}", path.display()), error: io::ErrorKind::DirectoryNotEmpty.into() });
+        }
+        self.remove(path);
+        self.record(format!("remove directory {}", path.display()));
+        Ok(())
+    }
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        if !self.is_dir(path) {
+            return Err(SlugpmError::not_found(path));
+        }
+        self.added.lock().unwrap().retain(|p, _| !p.starts_with(path));
+        self.remove(path);
+        self.record(format!("remove directory {} and its contents", path.display()));
+        Ok(())
+    }
+    fn is_writable(&self, path: &Path) -> bool {
+        // A directory that would be created is as writable as its closest existing ancestor.
+        path.ancestors().find(|d| self.inner.is_dir(d)).is_none_or(|d| self.inner.is_writable(d))
+    }
+    fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        self.kind(original).ok_or_else(|| SlugpmError::not_found(original))?;
+        self.add(link, false);
+        let origin = self.origin(original);
+        self.renamed.lock().unwrap().insert(link.to_path_buf(), origin);
+        self.record(format!("link {} -> {}", original.display(), link.display()));
+        Ok(())
+    }
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.kind(path).ok_or_else(|| SlugpmError::not_found(path))?;
+        self.remove(path);
+        self.record(format!("remove {}", path.display()));
+        Ok(())
+    }
+    fn modified(&self, path: &Path) -> Result<SystemTime> {
+        match self.added.lock().unwrap().get(path) {
+            Some(_) if self.renamed.lock().unwrap().get(path).is_none() => Ok(SystemTime::now()),
+            _ => self.inner.modified(&self.origin(path)),
+        }
+    }
+    fn file_size(&self, path: &Path) -> Result<u64> {
+        if let Some(contents) = self.written.lock().unwrap().get(path) {
+            return Ok(contents.len() as u64);
+        }
+        match self.kind(path) {
+            Some(_) => self.inner.file_size(&self.origin(path)),
+            None => Err(SlugpmError::not_found(path)),
+        }
+    }
+    fn metadata(&self, path: &Path) -> Result<FileMetadata> {
+        let is_dir = self.kind(path).ok_or_else(|| SlugpmError::not_found(path))?;
+        let mode = self.inner.metadata(&self.origin(path)).map_or(default_mode(is_dir), |meta| meta.mode);
+        Ok(FileMetadata { modified: self.modified(path)?, len: if is_dir { 0 } else { self.file_size(path)? }, is_dir, mode })
+    }
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.inner.is_symlink(&self.origin(path))
+    }
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.read_link(&self.origin(path))
+    }
+    fn symlink_dir(&self, target: &Path, link: &Path) -> Result<()> {
+        self.add(link, true);
+        self.record(format!("link {} -> {}", link.display(), target.display()));
+        Ok(())
+    }
+    fn symlink_file(&self, target: &Path, link: &Path) -> Result<()> {
+        self.add(link, false);
+        self.record(format!("link {} -> {}", link.display(), target.display()));
+        Ok(())
+    }
+    /// Copies are new files either way, so there's nothing to record.
+    fn copy_attributes(&self, from: &Path, to: &Path) -> Result<()> {
+        self.kind(from).ok_or_else(|| SlugpmError::not_found(from))?;
+        self.kind(to).map(|_| ()).ok_or_else(|| SlugpmError::not_found(to))
+    }
+    fn set_mode(&self, path: &Path, _mode: u32) -> Result<()> {
+        self.kind(path).map(|_| ()).ok_or_else(|| SlugpmError::not_found(path))
+    }
+    /// Reads of `to` go to `from`'s contents, as after a [`hard_link`](FileOps::hard_link);
+    /// `copied` hears of the whole file at once.
+    fn copy_file(&self, from: &Path, to: &Path, copied: &mut dyn FnMut(u64) -> Result<()>) -> Result<()> {
+        let len = self.file_size(from)?;
+        let written = self.written.lock().unwrap().get(from).cloned();
+        match written {
+            Some(contents) => { self.written.lock().unwrap().insert(to.to_path_buf(), contents); }
+            None => {
+                let origin = self.origin(from);
+                self.renamed.lock().unwrap().insert(to.to_path_buf(), origin);
+            }
+        }
+        self.add(to, false);
+        self.record(format!("copy {} -> {}", from.display(), to.display()));
+        copied(len)
+    }
+}
+
+/// Whether `err` is a rename failing because `from` and `to` are on different file systems.
+pub fn is_cross_device(err: &SlugpmError) -> bool {
+    err.io_kind() == Some(io::ErrorKind::CrossesDevices)
+}
+
+/// Renames `from` to `to`, falling back to copying and then removing `from` when they're on
+/// different file systems. The copy can be interrupted; see [`copy::copy_tree_interruptible`].
+pub fn move_path(from: &Path, to: &Path, ops: &dyn FileOps) -> Result<()> {
+    move_path_with_progress(from, to, &copy::NoProgress, ops)
+}
+
+/// [`move_path`], reporting a fallback copy's progress to `progress`.
+pub fn move_path_with_progress(from: &Path, to: &Path, progress: &dyn copy::Progress, ops: &dyn FileOps) -> Result<()> {
+    match ops.rename(from, to) {
+        Err(e) if is_cross_device(&e) => {
+            copy::copy_tree_interruptible(from, to, ops, progress)?;
+            copy::remove_tree(from, ops)
+        }
+        result => result,
+    }
+}
+
+/// What to do when the archive destination already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Fail without touching either path
+    #[default]
+    Error,
+    /// Replace the existing archived item
+    Overwrite,
+    /// Keep both, inserting a `-YYYYMMDD-HHMMSS` timestamp before the extension
+    Timestamp,
+    /// Keep both, inserting the first free `-2`, `-3`, ... before the extension
+    Number,
+}
+
+/// Name of the per-archive-directory file that sets its default conflict policy.
+pub const POLICY_FILE: &str = ".slugpm-policy";
+
+/// What to use as the slug when a title slugifies to nothing (e.g. all emoji).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FallbackSlug {
+    /// `untitled-<8 hex chars>`, derived from a hash of the title
+    Hash,
+    /// Fail with an error
+    #[default]
+    Error,
+    /// A fixed `untitled`
+    Literal,
+}
+
+/// Options shared by the create and archive operations.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Explicit conflict policy; when unset, the archive dir's `.slugpm-policy` or `error` applies.
+    pub on_conflict: Option<ConflictPolicy>,
+    /// Record a `<name>.sha256` sidecar next to archived files, and verify it before overwriting.
+    pub protect: bool,
+    pub fallback_slug: FallbackSlug,
+    /// Merge an archived directory into an existing one of the same name instead of conflicting.
+    pub merge: bool,
+    /// Archive archive directories and what's inside them too, nesting archives.
+    pub allow_nested: bool,
+    /// Subfolder of `archive/` to file things under, e.g. `archive/invoices/`.
+    pub category: Option<String>,
+    /// Trailing newline handling for content slugpm writes into files.
+    pub trailing_newline: NewlinePolicy,
+    /// Append into the Markdown section under this heading (such as `## Log`) rather than at
+    /// the end of the file (see [`insert_under_heading`]).
+    pub append_under: Option<String>,
+    /// Skip an append whose content was just appended, or was appended before with this set
+    /// (see [`integrity::is_duplicate_block`]).
+    pub skip_duplicate: bool,
+    pub clock: Arc<dyn Clock>,
+    /// Hard-link files into the archive instead of moving them, leaving the source in place.
+    pub link: bool,
+    /// TSV file that each successful create appends a `timestamp, title, directory` record to.
+    pub project_log: Option<PathBuf>,
+    /// When the project directory already exists, create `<slug>-2`, `<slug>-3`, ... instead.
+    pub unique: bool,
+    /// When the project directory already exists, return it untouched instead of failing.
+    pub reuse: bool,
+    /// What a create with empty piped input does.
+    pub on_empty: OnEmpty,
+    /// Whether emoji in a title are dropped or spelled out before slugifying.
+    pub emoji: EmojiMode,
+    /// Record each archived item in the archive's `.index.json`.
+    pub index: bool,
+    /// Split camelCase words in a title before slugifying (`myProject` -> `my-project`).
+    pub split_case: bool,
+    /// With `split_case`, keep runs of capitals together (`HTTPServer` -> `http-server`).
+    pub keep_acronyms: bool,
+    /// Archive symlinks themselves rather than what they point to, resolving their paths
+    /// lexically instead of with `canonicalize` (which would move the link's target).
+    pub no_follow: bool,
+    /// Turn warnings about a target (such as a broken symlink) into errors.
+    pub strict: bool,
+    /// File archived items under `archive/YYYY/YYYY-MM-DD/`.
+    pub dated_archive: bool,
+    /// Archive directories as `<dirname>.tar.zst` instead of moving them (see [`compress`]).
+    pub compress: bool,
+    /// Stop a batch archive at the first target that fails.
+    pub fail_fast: bool,
+    /// How many targets a batch archive works on at once (see [`archive_many`]).
+    pub jobs: usize,
+    /// Why things are being archived, noted in the archive's `ARCHIVE.md` (see [`manifest`]).
+    pub archive_message: Option<String>,
+    /// Record archived items in their archive directory's `.manifest.jsonl` (see [`manifest`]).
+    pub manifest: bool,
+    /// Shell command that reads a title on STDIN and prints its slug, replacing the built-in rules.
+    pub slugify_cmd: Option<String>,
+    /// Runs `slugify_cmd`.
+    pub runner: Arc<dyn CommandRunner>,
+    /// Where `trash` moves items.
+    pub trash: Arc<dyn trash::Trash>,
+    /// `git init` new projects and commit archives and restores (see [`vcs`]).
+    pub git: bool,
+    /// Runs git for `git`.
+    pub vcs: Arc<dyn vcs::Vcs>,
+    /// Runs the user's `post-*` scripts (see [`hooks`]).
+    pub hooks: Arc<dyn hooks::Hooks>,
+    /// Shown the progress of moves that fall back to copying across file systems.
+    pub progress: Arc<dyn copy::Progress>,
+    /// Where projects are created.
+    pub project_root: PathBuf,
+    /// Name of the archive directories slugpm creates and looks for.
+    pub archive_dir_name: String,
+    /// `strftime`-style format for dates in project names.
+    pub date_format: String,
+    /// Date prefixes that `name`, `rename` and titles recognize (see [`date_prefix`]).
+    pub date_prefixes: date_prefix::DatePrefixes,
+    /// How titles become slugs: separator, length, case and stopwords.
+    pub slug: SlugOptions,
+    /// Prefix new project names with today's date in `date_format`, as `<date>-<slug>`.
+    pub dated: bool,
+    /// Give new projects a numeric ID, named `<id>-<name>` (see [`ids`]).
+    pub ids: bool,
+    /// The checked `[rules]` of the config file, in order (see [`rules`]).
+    pub rules: Vec<rules::Rule>,
+    /// Template whose files a create copies into the new project.
+    pub template: Option<String>,
+    /// Where templates live; when unset, `templates/` beside the default config file.
+    pub templates_dir: Option<PathBuf>,
+    /// Record changes in the project root's undo journal (see [`journal`]).
+    pub journal: bool,
+    /// Note the projects used in the project root's recent list (see [`recent`]).
+    pub track_recent: bool,
+}
+
+impl Config {
+    /// [`archive_dir_for_file_pure`] with this config's archive directory name.
+    pub fn archive_dir_for_file(&self, parent: &Path) -> PathBuf {
+        parent.join(&self.archive_dir_name)
+    }
+
+    /// [`archive_dir_for_dir_pure`] with this config's archive directory name.
+    pub fn archive_dir_for_dir(&self, parent: &Path) -> PathBuf {
+        parent.parent().unwrap_or(parent).join(&self.archive_dir_name)
+    }
+}
+
+/// What to do when piped input for create has no title.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnEmpty {
+    /// Fail with an error
+    #[default]
+    Error,
+    /// Create `project/untitled-<timestamp>`
+    Untitled,
+}
+
+/// `untitled-YYYYMMDD-HHMMSS` for the clock's current time.
+pub fn untitled_name(clock: &dyn Clock) -> String {
+    format!("untitled-{}", clock.now().format("%Y%m%d-%H%M%S"))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            on_conflict: None,
+            protect: false,
+            fallback_slug: FallbackSlug::default(),
+            merge: false,
+            allow_nested: false,
+            category: None,
+            trailing_newline: NewlinePolicy::default(),
+            append_under: None,
+            skip_duplicate: false,
+            clock: Arc::new(SystemClock),
+            link: false,
+            project_log: None,
+            unique: false,
+            reuse: false,
+            on_empty: OnEmpty::default(),
+            emoji: EmojiMode::default(),
+            index: false,
+            split_case: false,
+            keep_acronyms: false,
+            no_follow: false,
+            strict: false,
+            dated_archive: false,
+            compress: false,
+            fail_fast: false,
+            jobs: 1,
+            archive_message: None,
+            manifest: false,
+            slugify_cmd: None,
+            runner: Arc::new(ShellRunner),
+            trash: Arc::new(trash::SystemTrash),
+            git: false,
+            vcs: Arc::new(vcs::GitVcs),
+            hooks: Arc::new(hooks::NoHooks),
+            progress: Arc::new(copy::NoProgress),
+            project_root: PathBuf::from("project"),
+            archive_dir_name: "archive".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            date_prefixes: date_prefix::DatePrefixes::default(),
+            slug: SlugOptions::default(),
+            dated: false,
+            ids: false,
+            rules: Vec::new(),
+            template: None,
+            templates_dir: None,
+            journal: false,
+            track_recent: false,
+        }
+    }
+}
+
+/// How to treat the end of content written to text files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NewlinePolicy {
+    /// Add a final newline if the content lacks one
+    Ensure,
+    /// Remove any final newlines
+    Strip,
+    /// Write the content as is
+    #[default]
+    Keep,
+}
+
+/// Applies `policy` to the end of `content`. Empty content is left empty.
+pub fn apply_newline_policy(content: &[u8], policy: NewlinePolicy) -> Vec<u8> {
+    let mut out = content.to_vec();
+    match policy {
+        NewlinePolicy::Ensure => {
+            if !out.is_empty() && !out.ends_with(b"\n") {
+                out.push(b'\n');
+            }
+        }
+        NewlinePolicy::Strip => {
+            while out.ends_with(b"\n") {
+                out.pop();
+                if out.ends_with(b"\r") {
+                    out.pop();
+                }
+            }
+        }
+        NewlinePolicy::Keep => {}
+    }
+    out
+}
+
+/// Validates a `--category` name and slugifies it into a single path segment.
+pub fn category_segment(name: &str) -> Result<String> {
+    if name.contains(['/', '\\']) {
+        return Err(SlugpmError::InvalidName(format!("category {name:?} must not contain path separators")));
+    }
+    let slug = slugify_title(name);
+    if slug.is_empty() {
+        return Err(SlugpmError::InvalidName(format!("category {name:?} produces an empty slug")));
+    }
+    Ok(slug)
+}
+
+/// `arch_dir`, or its category subfolder when `cfg.category` is set.
+fn with_category(arch_dir: PathBuf, cfg: &Config) -> Result<PathBuf> {
+    match &cfg.category {
+        Some(name) => Ok(arch_dir.join(category_segment(name)?)),
+        None => Ok(arch_dir),
+    }
+}
+
+/// Where in `arch_dir` moved items go: [`with_category`], then today's [`dated_subdir`] when
+/// `cfg.dated_archive` is set. Appends ignore the date, so they keep growing one file.
+fn archive_subdir(arch_dir: PathBuf, cfg: &Config) -> Result<PathBuf> {
+    let arch_dir = with_category(arch_dir, cfg)?;
+    Ok(if cfg.dated_archive { arch_dir.join(dated_subdir(cfg.clock.now().date())) } else { arch_dir })
+}
+
+/// The archive directory `path` is, or is somewhere inside: the nearest of `path` and its
+/// ancestors named `archive_dir_name`. This covers category and dated folders and the
+/// contents of archived directories alike.
+pub fn enclosing_archive_dir_pure<'a>(path: &'a Path, archive_dir_name: &str) -> Option<&'a Path> {
+    path.ancestors().find(|dir| dir.file_name().is_some_and(|n| n == archive_dir_name))
+}
+
+/// Fails unless `cfg.allow_nested` if `target` is an archive directory or inside one, where
+/// archiving it would nest archives (`archive/archive/...`).
+fn refuse_nested_archive(target: &Path, cfg: &Config) -> Result<()> {
+    match enclosing_archive_dir_pure(target, &cfg.archive_dir_name) {
+        _ if cfg.allow_nested => Ok(()),
+        Some(dir) if dir == target => Err(SlugpmError::InvalidName(format!(
+            "{} is an archive directory; pass --allow-nested to archive it anyway", target.display()))),
+        Some(_) => Err(SlugpmError::AlreadyArchived(target.to_path_buf())),
+        None => Ok(()),
+    }
+}
+
+/// Windows' default `MAX_PATH`, including the terminating NUL.
+pub const WINDOWS_MAX_PATH: usize = 260;
+
+/// Fails if `path` is too long for Windows APIs without the `\\?\` prefix.
+///
+/// Length is counted in UTF-16 units, as Windows does; prefixed paths are exempt.
+pub fn check_windows_path_length(path: &Path) -> Result<()> {
+    let text = path.to_string_lossy();
+    if text.starts_with(r"\\?\") {
+        return Ok(());
+    }
+    let len = text.encode_utf16().count();
+    if len >= WINDOWS_MAX_PATH {
+        return Err(SlugpmError::Other(format!(
+            "destination path is {len} characters, over the Windows limit of {}: {}; \
+             use a `\\\\?\\`-prefixed path or archive into a shallower directory",
+            WINDOWS_MAX_PATH - 1, path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Drops the `\\?\` prefix that `canonicalize` adds on Windows, so `C:\` and `\\server\share`
+/// paths print and compare like the ones users type.
+///
+/// Paths that need the prefix (too long for `MAX_PATH`, or not a drive or UNC path) are kept as is.
+pub fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    let plain = if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{rest}")
+    } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+        let bytes = rest.as_bytes();
+        if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || bytes[1] != b':' || bytes[2] != b'\\' {
+            return path.to_path_buf();
+        }
+        rest.to_string()
+    } else {
+        return path.to_path_buf();
+    };
+    if plain.encode_utf16().count() >= WINDOWS_MAX_PATH {
+        return path.to_path_buf();
+    }
+    PathBuf::from(plain)
+}
+
+/// Fails with a targeted message if nothing can be moved into `arch_dir`,
+/// rather than letting the move surface a bare permission error.
+fn ensure_writable(arch_dir: &Path, ops: &dyn FileOps) -> Result<()> {
+    if !ops.is_writable(arch_dir) {
+        return Err(SlugpmError::Other(format!(
+            "archive directory {} is not writable; check its permissions (e.g. `chmod u+w {}`)",
+            arch_dir.display(), arch_dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Parses the `on_conflict = "<policy>"` setting out of a `.slugpm-policy` file.
+pub fn parse_policy_file(contents: &str) -> Result<Option<ConflictPolicy>> {
+    let table: toml::Table = contents.parse().map_err(|e| SlugpmError::Other(format!("{e}")))?;
+    match table.get("on_conflict") {
+        None => Ok(None),
+        Some(value) => {
+            let name = value.as_str().ok_or_else(|| SlugpmError::Other("on_conflict must be a string".into()))?;
+            let policy = <ConflictPolicy as clap::ValueEnum>::from_str(name, true)
+                .map_err(|_| SlugpmError::Other(format!("unknown on_conflict policy {name:?}")))?;
+            Ok(Some(policy))
+        }
+    }
+}
+
+/// The conflict policy for moves into `arch_dir`: the explicit `cfg.on_conflict`,
+/// else the directory's `.slugpm-policy`, else `error`.
+pub fn conflict_policy_for(arch_dir: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<ConflictPolicy> {
+    if let Some(policy) = cfg.on_conflict {
+        return Ok(policy);
+    }
+    let policy_file = arch_dir.join(POLICY_FILE);
+    if !ops.is_file(&policy_file) {
+        return Ok(ConflictPolicy::default());
+    }
+    let contents = String::from_utf8_lossy(&ops.read(&policy_file)?).into_owned();
+    let policy = parse_policy_file(&contents)
+        .map_err(|e| SlugpmError::Other(format!("reading {}: {e}", policy_file.display())))?;
+    Ok(policy.unwrap_or_default())
+}
+
+/// `path` with `-<suffix>` inserted before its extension: `notes.txt` -> `notes-<suffix>.txt`.
+///
+/// A compressed archive keeps its whole `.tar.zst` extension.
+pub fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    if let Some(stem) = compress::unpacked_name(path) {
+        return path.with_file_name(format!("{stem}-{suffix}{}", compress::COMPRESSED_EXT));
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{suffix}"),
+    };
+    path.with_file_name(name)
+}
+
+/// The first of `<stem>-2<.ext>`, `<stem>-3<.ext>`, ... next to `path` that doesn't exist yet.
+pub fn numbered_path(path: &Path, ops: &dyn FileOps) -> PathBuf {
+    (2..).map(|n| suffixed_path(path, &n.to_string()))
+        .find(|candidate| !ops.exists(candidate))
+        .unwrap()
+}
+
+/// Where to put an item whose natural destination is `dest`, applying `policy` if it is taken.
+fn resolve_conflict(dest: &Path, policy: ConflictPolicy, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if !ops.exists(dest) {
+        return Ok(dest.to_path_buf());
+    }
+    match policy {
+        ConflictPolicy::Error => Err(SlugpmError::Collision(dest.to_path_buf())),
+        ConflictPolicy::Overwrite => {
+            integrity::verify_sidecar(dest, ops)?;
+            Ok(dest.to_path_buf())
+        }
+        ConflictPolicy::Timestamp => {
+            let stamped = suffixed_path(dest, &cfg.clock.now().format("%Y%m%d-%H%M%S").to_string());
+            if ops.exists(&stamped) {
+                return Err(SlugpmError::Collision(stamped));
+            }
+            Ok(stamped)
+        }
+        ConflictPolicy::Number => Ok(numbered_path(dest, ops)),
+    }
+}
+
+/// Moves `file` into `<parent>/archive/`, returning the destination.
+pub fn archive_file(file: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let arch_dir = archive_subdir(cfg.archive_dir_for_file(file.parent().unwrap()), cfg)?;
+    ops.create_dir_all(&arch_dir)?;
+    ensure_writable(&arch_dir, ops)?;
+    let policy = conflict_policy_for(&arch_dir, cfg, ops)?;
+    let dest = resolve_conflict(&arch_dir.join(safe_path::file_segment(file)?), policy, cfg, ops)?;
+    safe_path::ensure_under(&dest, &arch_dir)?;
+    if cfg!(windows) {
+        check_windows_path_length(&dest)?;
+    }
+    let protected = cfg.protect || ops.exists(&integrity::sidecar_path(&dest));
+    let contents = if protected { Some(ops.read(file)?) } else { None };
+    if cfg.link {
+        ops.hard_link(file, &dest)?;
+    } else {
+        move_path_with_progress(file, &dest, cfg.progress.as_ref(), ops)?;
+    }
+    if let Some(contents) = contents {
+        integrity::write_sidecar(&dest, &contents, ops)?;
+    }
+    Ok(dest)
+}
+
+/// Moves `dir` into `<parent>/../archive/` (or packs it there with `cfg.compress`), returning the destination.
+pub fn archive_dir(dir: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if cfg.link {
+        return Err(SlugpmError::Other(format!("cannot hard-link directory {}; --link only applies to files", dir.display())));
+    }
+    // A symlink to a directory (archived with `no_follow`) is moved as it is.
+    let link = ops.is_symlink(dir);
+    if link && cfg.compress {
+        return Err(SlugpmError::Other(format!("cannot compress symlink {}; archive without --compress", dir.display())));
+    }
+    let arch_dir = archive_subdir(cfg.archive_dir_for_dir(dir.parent().unwrap()), cfg)?;
+    ops.create_dir_all(&arch_dir)?;
+    ensure_writable(&arch_dir, ops)?;
+    if cfg.compress {
+        let dest = arch_dir.join(compress::compressed_name(&safe_path::file_segment(dir)?.to_string_lossy()));
+        let dest = resolve_conflict(&dest, conflict_policy_for(&arch_dir, cfg, ops)?, cfg, ops)?;
+        safe_path::ensure_under(&dest, &arch_dir)?;
+        if cfg!(windows) {
+            check_windows_path_length(&dest)?;
+        }
+        compress::pack_dir(dir, &dest, ops)?;
+        copy::remove_tree(dir, ops)?;
+        return Ok(dest);
+    }
+    let dest = arch_dir.join(safe_path::file_segment(dir)?);
+    safe_path::ensure_under(&dest, &arch_dir)?;
+    if cfg.merge && !link && ops.is_dir(&dest) {
+        merge_dir(dir, &dest, cfg, ops)?;
+        return Ok(dest);
+    }
+    // Renaming onto an empty directory succeeds on some platforms and fails on others;
+    // treat it as free everywhere.
+    if ops.is_dir(&dest) && ops.read_dir(&dest)?.is_empty() {
+        ops.remove_dir(&dest)?;
+    }
+    let dest = resolve_conflict(&dest, conflict_policy_for(&arch_dir, cfg, ops)?, cfg, ops)?;
+    if cfg!(windows) {
+        check_windows_path_length(&dest)?;
+    }
+    if ops.is_dir(&dest) && !ops.is_symlink(&dest) {
+        replace_dir(dir, &dest, cfg, ops)?;
+    } else {
+        move_path_with_progress(dir, &dest, cfg.progress.as_ref(), ops)?;
+    }
+    Ok(dest)
+}
+
+/// Moves `dir` onto the existing directory `dest` (an overwrite), which a rename can't do: the
+/// old tree is moved aside first, and removed once `dir` is in place or put back if it can't be.
+fn replace_dir(dir: &Path, dest: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let mut aside = dest.with_file_name(format!(".{name}.replaced"));
+    if ops.exists(&aside) {
+        aside = numbered_path(&aside, ops);
+    }
+    ops.rename(dest, &aside)?;
+    if let Err(e) = move_path_with_progress(dir, dest, cfg.progress.as_ref(), ops) {
+        ops.rename(&aside, dest)?;
+        return Err(e);
+    }
+    copy::remove_tree(&aside, ops)
+}
+
+/// Collects `(source, destination)` pairs for every file under `src`, mirrored under `dest`.
+fn collect_merge_moves(src: &Path, dest: &Path, ops: &dyn FileOps, moves: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    for entry in ops.read_dir(src)? {
+        let target = dest.join(entry.file_name().unwrap());
+        if ops.is_dir(&entry) {
+            if ops.is_file(&target) {
+                return Err(SlugpmError::Other(format!("cannot merge directory {} onto file {}", entry.display(), target.display())));
+            }
+            collect_merge_moves(&entry, &target, ops, moves)?;
+        } else {
+            moves.push((entry, target));
+        }
+    }
+    Ok(())
+}
+
+/// Moves the contents of `src` into the existing directory `dest`, applying the
+/// conflict policy per file, then removes the emptied `src` tree.
+///
+/// Every conflict is checked before anything moves, so a refused merge leaves both trees intact.
+pub fn merge_dir(src: &Path, dest: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    let policy = conflict_policy_for(dest.parent().unwrap_or(dest), cfg, ops)?;
+    let mut moves = Vec::new();
+    collect_merge_moves(src, dest, ops, &mut moves)?;
+    let moves = moves.into_iter()
+        .map(|(from, to)| Ok((from, resolve_conflict(&to, policy, cfg, ops)?)))
+        .collect::<Result<Vec<_>>>()?;
+    for (from, to) in &moves {
+        ops.create_dir_all(to.parent().unwrap())?;
+        move_path(from, to, ops)?;
+    }
+    remove_empty_dirs(src, ops)
+}
+
+fn remove_empty_dirs(dir: &Path, ops: &dyn FileOps) -> Result<()> {
+    for entry in ops.read_dir(dir)? {
+        if ops.is_dir(&entry) {
+            remove_empty_dirs(&entry, ops)?;
+        }
+    }
+    ops.remove_dir(dir)
+}
+
+/// Where an archive operation read from and where the result ended up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveOutcome {
+    pub source: PathBuf,
+    pub dest: PathBuf,
+}
+
+impl ArchiveOutcome {
+    /// The destination, or `source<TAB>dest` when `print_source` is set.
+    pub fn render(&self, print_source: bool) -> String {
+        if print_source {
+            format!("{}\t{}", self.source.display(), self.dest.display())
+        } else {
+            self.dest.display().to_string()
+        }
+    }
+}
+
+/// Archives `target` as a file or a directory, depending on what it is.
+pub fn archive_target(target: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<ArchiveOutcome> {
+    safe_path::file_segment(target)?;
+    refuse_nested_archive(target, cfg)?;
+    let (mut merge, mut compressed) = (false, false);
+    // With `no_follow` a symlink is archived as itself: a link to a directory goes where the
+    // directory would, any other link where a file would.
+    let link = cfg.no_follow && ops.is_symlink(target);
+    let (dest, arch_root) = if ops.is_file(target) || (link && !ops.is_dir(target)) {
+        (archive_file(target, cfg, ops)?, cfg.archive_dir_for_file(target.parent().unwrap()))
+    } else if ops.is_dir(target) {
+        let arch_dir = archive_subdir(cfg.archive_dir_for_dir(target.parent().unwrap()), cfg)?;
+        merge = cfg.merge && !cfg.compress && !link && ops.is_dir(&arch_dir.join(target.file_name().unwrap()));
+        compressed = cfg.compress;
+        (archive_dir(target, cfg, ops)?, cfg.archive_dir_for_dir(target.parent().unwrap()))
+    } else {
+        return Err(SlugpmError::NotFound(format!("{} is neither file nor directory", target.display())));
+    };
+    if cfg.index {
+        let entry = index::IndexEntry {
+            name: target.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            dest: dest.clone(),
+            archived_at: cfg.clock.now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            size: index::tree_size(&dest, ops)?,
+        };
+        index::record(&arch_root, &entry, ops)?;
+    }
+    if cfg.manifest {
+        // A link archived as itself has nothing of its own to hash.
+        let own = !ops.is_symlink(&dest);
+        let sha256 = if own && ops.is_file(&dest) { Some(integrity::sha256_file(&dest, ops)?) } else { None };
+        let files = if own && ops.is_dir(&dest) { integrity::tree_hashes(&dest, ops)? } else { BTreeMap::new() };
+        let entry = manifest::ManifestEntry {
+            from: target.to_path_buf(),
+            path: dest.strip_prefix(&arch_root).unwrap_or(&dest).to_path_buf(),
+            archived_at: cfg.clock.now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            reason: cfg.archive_message.clone(),
+            dir: ops.is_dir(&dest) || compressed,
+            compressed,
+            sha256,
+            files,
+            removed: None,
+        };
+        manifest::record_entry(&arch_root, &entry, ops)?;
+    }
+    if let Some(reason) = &cfg.archive_message {
+        manifest::record_reason(&arch_root, target, &dest, reason, cfg, ops)?;
+    }
+    journal::record(journal::Operation::Archive { from: target.to_path_buf(), to: dest.clone(), link: cfg.link, merge, compressed }, cfg, ops)?;
+    if recent::in_project_root(target, cfg, ops) {
+        recent::record("archive", &dest, cfg, ops);
+    }
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    vcs::commit_paths(&format!("Archive {name}"), &[target, &dest], cfg, ops)?;
+    cfg.hooks.run(&hooks::HookCall { event: hooks::HookEvent::PostArchive, slug: &name, path: &dest, source: Some(target) })?;
+    Ok(ArchiveOutcome { source: target.to_path_buf(), dest })
+}
+
+/// The result of archiving one of several targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetResult {
+    pub source: PathBuf,
+    /// Where the target was archived to, on success.
+    pub dest: Option<PathBuf>,
+    /// Why the target wasn't archived, on failure.
+    pub error: Option<String>,
+    /// The failure's [`SlugpmError::kind_name`] and [`SlugpmError::exit_code`].
+    pub kind: Option<&'static str>,
+    pub code: Option<i32>,
+    /// Problems that didn't stop the target from being archived.
+    pub warnings: Vec<String>,
+}
+
+impl TargetResult {
+    fn failed(source: PathBuf, error: &SlugpmError, warnings: Vec<String>) -> Self {
+        TargetResult { source, dest: None, error: Some(error.to_string()), kind: Some(error.kind_name()), code: Some(error.exit_code()), warnings }
+    }
+}
+
+/// For a symlink, the path it points to if that doesn't exist; `None` for working links and non-links.
+///
+/// Relative link targets are resolved against the link's directory, as the OS does.
+pub fn broken_link_target(path: &Path, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    if !ops.is_symlink(path) {
+        return Ok(None);
+    }
+    let target = ops.read_link(path)?;
+    let resolved = path.parent().unwrap_or(Path::new("")).join(&target);
+    Ok(if ops.exists(&resolved) { None } else { Some(target) })
+}
+
+/// Resolves `target` for archiving: through symlinks with `canonicalize`, or for a symlink with
+/// `no_follow` only lexically (see [`absolute_lexically`]), so the link itself is what gets moved
+/// and it is archived beside where it was found, not beside what it points to.
+fn resolve_target(target: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if cfg.no_follow && ops.is_symlink(target) {
+        return absolute_lexically(target, ops);
+    }
+    ops.canonicalize(target)
+}
+
+/// `path` with `.` components dropped and each `..` removing the component before it, without
+/// looking at the filesystem, so symlinks along the way are kept rather than resolved. Leading
+/// `..`s of a relative path stay, and `..` never climbs above the root.
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => { normalized.pop(); }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// [`normalize_lexically`] after making `path` absolute against the (canonical) working directory.
+pub fn absolute_lexically(path: &Path, ops: &dyn FileOps) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(normalize_lexically(path));
+    }
+    Ok(normalize_lexically(&ops.canonicalize(Path::new("."))?.join(path)))
+}
+
+/// Replaces each `@<file>` argument with the paths listed in `<file>`, one per line; other arguments pass through.
+///
+/// Blank lines are skipped. Listed paths are taken as written, so relative ones resolve against the working directory.
+pub fn expand_response_files(args: &[PathBuf], ops: &dyn FileOps) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let Some(file) = arg.to_str().and_then(|a| a.strip_prefix('@')) else {
+            expanded.push(arg.clone());
+            continue;
+        };
+        let bytes = ops.read(Path::new(file))?;
+        let text = normalize_line_endings(&String::from_utf8_lossy(&bytes));
+        expanded.extend(text.lines().map(str::trim).filter(|l| !l.is_empty()).map(PathBuf::from));
+    }
+    Ok(expanded)
+}
+
+/// The paths in `input`, one per line (or NUL-separated with `null`, as `find -print0` writes
+/// them); blank entries are skipped.
+pub fn read_target_list(input: &mut dyn Read, null: bool) -> Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes).io_context(|| "reading paths".to_string())?;
+    let text = String::from_utf8_lossy(&bytes);
+    let paths: Vec<&str> = if null { text.split('\0').collect() } else { text.lines().map(|l| l.trim_end_matches('\r')).collect() };
+    Ok(paths.into_iter().filter(|p| !p.trim().is_empty()).map(PathBuf::from).collect())
+}
+
+/// Archives each of `targets` independently, without printing; one failure doesn't stop the rest
+/// unless `cfg.fail_fast` is set, in which case the results end at the first failure.
+///
+/// With `cfg.jobs` above 1 the targets are archived on up to that many threads, and the results
+/// still come back in the order of `targets`. Targets headed for the same archive directory are
+/// archived one after another, in order, so they can't race for a name or for its index; with
+/// `cfg.git` everything is, as commits share the repository. After a failure under
+/// `cfg.fail_fast`, no new target is started, but those already under way are reported.
+pub fn archive_many(targets: &[PathBuf], cfg: &Config, ops: &dyn FileOps) -> Vec<TargetResult> {
+    if cfg.jobs > 1 && !cfg.git && targets.len() > 1 {
+        return archive_many_parallel(targets, cfg, ops);
+    }
+    let mut results: Vec<TargetResult> = Vec::new();
+    for target in targets {
+        if cfg.fail_fast && results.last().is_some_and(|r| r.error.is_some()) {
+            break;
+        }
+        results.push(archive_one(target, cfg, ops));
+    }
+    results
+}
+
+fn archive_many_parallel(targets: &[PathBuf], cfg: &Config, ops: &dyn FileOps) -> Vec<TargetResult> {
+    // Indices into `targets`, by archive directory, in order of first appearance.
+    let mut groups: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for (i, target) in targets.iter().enumerate() {
+        let key = archive_group(target, cfg, ops);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, indices)) => indices.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    let next_group = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let slots: Vec<Mutex<Option<TargetResult>>> = targets.iter().map(|_| Mutex::default()).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..cfg.jobs.min(groups.len()) {
+            scope.spawn(|| {
+                while let Some((_, indices)) = groups.get(next_group.fetch_add(1, Ordering::SeqCst)) {
+                    for &i in indices {
+                        if cfg.fail_fast && failed.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        let result = archive_one(&targets[i], cfg, ops);
+                        if result.error.is_some() {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        *slots[i].lock().unwrap() = Some(result);
+                    }
+                }
+            });
+        }
+    });
+    slots.into_iter().filter_map(|slot| slot.into_inner().unwrap()).collect()
+}
+
+/// The archive directory `target` would go to, which [`archive_many_parallel`] never works on
+/// from two threads at once; a target that can't be resolved is a group of its own.
+fn archive_group(target: &Path, cfg: &Config, ops: &dyn FileOps) -> PathBuf {
+    let Ok(resolved) = resolve_target(target, cfg, ops) else {
+        return target.to_path_buf();
+    };
+    let Some(parent) = resolved.parent() else {
+        return resolved;
+    };
+    if ops.is_dir(&resolved) {
+        cfg.archive_dir_for_dir(parent)
+    } else {
+        cfg.archive_dir_for_file(parent)
+    }
+}
+
+fn archive_one(target: &Path, cfg: &Config, ops: &dyn FileOps) -> TargetResult {
+    let target = target.to_path_buf();
+    let mut warnings = Vec::new();
+    if copy::was_interrupted() {
+        // Leave the remaining targets alone after Ctrl-C.
+        return TargetResult::failed(target, &SlugpmError::Interrupted, warnings);
+    }
+    let result = resolve_target(&target, cfg, ops)
+        .and_then(|resolved| {
+            if let Some(dangling) = broken_link_target(&resolved, ops)? {
+                let warning = format!("symlink is broken: {} does not exist", dangling.display());
+                if cfg.strict {
+                    return Err(SlugpmError::Other(warning));
+                }
+                warnings.push(warning);
+            }
+            archive_target(&resolved, cfg, ops)
+        });
+    match result {
+        Ok(outcome) => TargetResult { source: outcome.source, dest: Some(outcome.dest), error: None, kind: None, code: None, warnings },
+        Err(e) => TargetResult::failed(target, &e, warnings),
+    }
+}
+
+pub fn archive_move_file_with(file: &Path, ops: &dyn FileOps) -> Result<()> {
+    archive_file(file, &Config::default(), ops).map(|_| ())
+}
+
+/// Like [`archive_move_file_with`], resolving an existing archived file with `policy`.
+pub fn archive_move_file_with_policy(file: &Path, policy: ConflictPolicy, ops: &dyn FileOps) -> Result<PathBuf> {
+    archive_file(file, &Config { on_conflict: Some(policy), ..Config::default() }, ops)
+}
+
+pub fn archive_move_dir_with(dir: &Path, ops: &dyn FileOps) -> Result<()> {
+    archive_dir(dir, &Config::default(), ops).map(|_| ())
+}
+
+pub fn archive_append_stdin_with(file: &Path, mut input: impl Read, ops: &dyn FileOps) -> Result<()> {
+    archive_append_with(file, &mut input, &Config::default(), ops).map(|_| ())
+}
+
+/// Canonicalizes an append-mode target, which may not exist yet: only its parent has to.
+pub fn resolve_append_target(target: &Path, ops: &dyn FileOps) -> Result<PathBuf> {
+    if ops.exists(target) {
+        return ops.canonicalize(target);
+    }
+    let name = target.file_name()
+        .ok_or_else(|| SlugpmError::InvalidName(format!("invalid file name: {}", target.display())))?;
+    let parent = match target.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let parent = ops.canonicalize(parent)?;
+    Ok(parent.join(name))
+}
+
+/// A Markdown ATX heading: its level (the number of `#`s) and its text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+}
+
+/// `line` as an ATX heading (`## Log`, or with a closing sequence, `## Log ##`), if it is one.
+pub fn parse_heading(line: &str) -> Option<Heading> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let level = rest.len() - rest.trim_start_matches('#').len();
+    let rest = &rest[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let text = rest.trim();
+    let text = match text.trim_end_matches('#') {
+        "" => "",
+        open if open.ends_with([' ', '\t']) => open.trim_end(),
+        _ => text,
+    };
+    Some(Heading { level, text: text.to_string() })
+}
+
+/// A heading and the lines up to the next one; the first section holds what comes before any
+/// heading and has none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarkdownSection {
+    pub heading: Option<Heading>,
+    /// The section's lines as written, heading line included.
+    pub text: String,
+}
+
+/// Splits `doc` into sections at its headings. Lines in front matter and fenced code blocks are
+/// never headings. [`render_sections`] gives `doc` back exactly.
+pub fn parse_sections(doc: &str) -> Vec<MarkdownSection> {
+    let mut sections = vec![MarkdownSection { heading: None, text: String::new() }];
+    let mut front_matter = doc.starts_with("---\n") || doc.starts_with("---\r\n");
+    let mut fence: Option<&str> = None;
+    for (i, line) in doc.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        if front_matter {
+            front_matter = i == 0 || trimmed != "---";
+        } else if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+        } else if let Some(heading) = parse_heading(line) {
+            sections.push(MarkdownSection { heading: Some(heading), text: String::new() });
+        }
+        sections.last_mut().unwrap().text.push_str(line);
+    }
+    sections
+}
+
+/// The document `sections` were parsed from.
+pub fn render_sections(sections: &[MarkdownSection]) -> String {
+    sections.iter().map(|section| section.text.as_str()).collect()
+}
+
+/// Where to put `content` so it ends the section under `heading` (such as `## Log`): the byte
+/// offset in `doc`, after the section's last non-blank line and before the next heading of the
+/// same or a higher level, and the text to insert there. Without that heading in `doc`, the
+/// heading is added at the end with `content` under it.
+///
+/// The inserted text always ends in a line break, in `doc`'s style.
+pub fn insert_under_heading(doc: &str, heading: &str, content: &str) -> Result<(usize, String)> {
+    let target = parse_heading(heading)
+        .filter(|h| !h.text.is_empty())
+        .ok_or_else(|| SlugpmError::InvalidName(format!("{heading:?} is not a Markdown heading such as \"## Log\"")))?;
+    let eol = if doc.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut body = content.to_string();
+    if !body.is_empty() && !body.ends_with('\n') {
+        body += eol;
+    }
+    let sections = parse_sections(doc);
+    let Some(found) = sections.iter().position(|s| s.heading.as_ref() == Some(&target)) else {
+        let mut text = String::new();
+        if !doc.is_empty() && !doc.ends_with('\n') {
+            text += eol;
+        }
+        if !doc.trim().is_empty() && !doc.ends_with(&format!("{eol}{eol}")) {
+            text += eol;
+        }
+        text += &format!("{}{eol}{eol}{body}", heading.trim());
+        return Ok((doc.len(), text));
+    };
+    let start: usize = sections[..found].iter().map(|s| s.text.len()).sum();
+    let len = sections[found + 1..].iter()
+        .take_while(|s| s.heading.as_ref().is_none_or(|h| h.level > target.level))
+        .map(|s| s.text.len())
+        .sum::<usize>() + sections[found].text.len();
+    let mut at = start + len;
+    // Step back over the blank lines that separate the section from the next heading.
+    while let Some(before) = doc[..at].strip_suffix('\n') {
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        if line_start <= start || !doc[line_start..at].trim().is_empty() {
+            break;
+        }
+        at = line_start;
+    }
+    if !doc[..at].ends_with('\n') {
+        body.insert_str(0, eol);
+    }
+    Ok((at, body))
+}
+
+/// What [`archive_append_outcome`] did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppendOutcome {
+    /// The archive file appended to.
+    pub dest: PathBuf,
+    /// Whether the content was left out as a duplicate (`cfg.skip_duplicate`).
+    pub skipped: bool,
+}
+
+/// Appends everything from `input` to `<parent>/archive/<filename>`, returning that path.
+///
+/// With `cfg.append_under`, the input goes at the end of that heading's section instead (see
+/// [`insert_under_heading`]); the file is then rewritten rather than appended to under a lock.
+pub fn archive_append_with(file: &Path, input: &mut dyn Read, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    archive_append_outcome(file, input, cfg, ops).map(|outcome| outcome.dest)
+}
+
+/// [`archive_append_with`], also telling whether `cfg.skip_duplicate` left the input out:
+/// when the archive file already ends with it, or its hash is in the file's block index (see
+/// [`integrity::is_duplicate_block`]). Appends made with `skip_duplicate` add to that index.
+pub fn archive_append_outcome(file: &Path, input: &mut dyn Read, cfg: &Config, ops: &dyn FileOps) -> Result<AppendOutcome> {
+    refuse_nested_archive(file, cfg)?;
+    let arch_dir = with_category(cfg.archive_dir_for_file(file.parent().unwrap()), cfg)?;
+    ops.create_dir_all(&arch_dir)?;
+    let dest = arch_dir.join(file.file_name().unwrap());
+    // All of the input is read before the file is locked, so a slow writer doesn't hold up others.
+    let mut buf = Vec::new();
+    input.read_to_end(&mut buf).io_context(|| "reading input".to_string())?;
+    let existed = ops.is_file(&dest);
+    let block = apply_newline_policy(&buf, cfg.trailing_newline);
+    if cfg.skip_duplicate {
+        if integrity::is_duplicate_block(&dest, &block, ops)? {
+            return Ok(AppendOutcome { dest, skipped: true });
+        }
+        integrity::record_block(&dest, &block, ops)?;
+    }
+    if let Some(heading) = &cfg.append_under {
+        let doc = if existed { ops.read_to_string(&dest)? } else { String::new() };
+        let content = String::from_utf8(block)
+            .map_err(|_| SlugpmError::Other("input to insert under a heading must be UTF-8 text".into()))?;
+        let (at, text) = insert_under_heading(&doc, heading, &content)?;
+        ops.write(&dest, format!("{}{text}{}", &doc[..at], &doc[at..]).as_bytes())?;
+        let op = journal::Operation::Insert { path: dest.clone(), offset: at as u64, text, created: !existed };
+        journal::record(op, cfg, ops)?;
+        return Ok(AppendOutcome { dest, skipped: false });
+    }
+    let start = ops.append_locked(&dest, &block)?;
+    let previous_len = (existed || start > 0).then_some(start);
+    journal::record(journal::Operation::append(&dest, previous_len, &block), cfg, ops)?;
+    Ok(AppendOutcome { dest, skipped: false })
+}
+
+/// Where `restore` puts an archived item, undoing [`archive_dir_for_file_pure`] and
+/// [`archive_dir_for_dir_pure`]; `None` if `archived` isn't inside an `archive` directory.
+///
+/// Files go back next to the archive directory (leaving any category folder). A directory's
+/// original parent isn't recorded, so it goes back under the sibling `project/` directory,
+/// where slugpm creates projects.
+pub fn restore_dest_pure(archived: &Path, is_dir: bool) -> Option<PathBuf> {
+    restore_dest(archived, is_dir, "archive", Path::new("project"))
+}
+
+/// [`restore_dest_pure`] for archive directories named `archive_dir_name`; a relative
+/// `project_root` is taken relative to the archive's parent.
+fn restore_dest(archived: &Path, is_dir: bool, archive_dir_name: &str, project_root: &Path) -> Option<PathBuf> {
+    let arch_dir = archived.ancestors().skip(1).find(|a| a.file_name().is_some_and(|n| n == archive_dir_name))?;
+    let root = arch_dir.parent()?;
+    let name = archived.file_name()?;
+    Some(if is_dir { root.join(project_root).join(name) } else { root.join(name) })
+}
+
+/// Moves an archived file or directory back out of the archive, into `to` if given. A
+/// compressed archive (`<dirname>.tar.zst`) is unpacked into a directory like the one archived.
+///
+/// Without `to`, the item goes back where its archive directory's manifest says it came from
+/// (see [`manifest`]), or if it isn't recorded there, where the archive layout suggests (see
+/// [`restore_dest_pure`]). `archived` can also be just a name that isn't a path here, looked up
+/// in the manifests of the archive next to the project root and then of `./archive`.
+///
+/// Fails rather than replacing anything already at the destination. A file's
+/// `.sha256` sidecar stays behind only as long as the file does, so it is removed too.
+pub fn restore_target(archived: &Path, to: Option<&Path>, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let archived = match archived.to_str() {
+        Some(name) if !ops.exists(archived) && !ops.is_symlink(archived) && safe_path::check_segment(name, "name").is_ok() => {
+            find_archived_by_name(name, cfg, ops)?
+        }
+        _ => archived.to_path_buf(),
+    };
+    // An archived symlink is restored as itself, like `no_follow` archived it.
+    let archived = if ops.is_symlink(&archived) { absolute_lexically(&archived, ops)? } else { ops.canonicalize(&archived)? };
+    let is_dir = ops.is_dir(&archived);
+    let unpacked = if is_dir { None } else { compress::unpacked_name(&archived) };
+    let recorded = match to {
+        Some(_) => None,
+        None => manifest::original_path(&archived, &cfg.archive_dir_name, ops)?,
+    };
+    let dest = match recorded {
+        Some(from) => from,
+        None => {
+            let dest = match to {
+                Some(dir) => dir.join(archived.file_name().unwrap_or_default()),
+                None => restore_dest(&archived, is_dir || unpacked.is_some(), &cfg.archive_dir_name, &cfg.project_root)
+                    .ok_or_else(|| SlugpmError::Other(format!("{} is not inside an archive directory", archived.display())))?,
+            };
+            match &unpacked {
+                Some(name) => dest.with_file_name(name),
+                None => dest,
+            }
+        }
+    };
+    if ops.exists(&dest) {
+        return Err(SlugpmError::Collision(dest));
+    }
+    ops.create_dir_all(dest.parent().unwrap())?;
+    if unpacked.is_some() {
+        compress::unpack_to(&archived, &dest, ops)?;
+        ops.remove_file(&archived)?;
+    } else {
+        move_path_with_progress(&archived, &dest, cfg.progress.as_ref(), ops)?;
+    }
+    let sidecar = integrity::sidecar_path(&archived);
+    if !is_dir && ops.exists(&sidecar) {
+        ops.remove_file(&sidecar)?;
+    }
+    if cfg.manifest {
+        if let Some(arch_root) = enclosing_archive_dir_pure(archived.parent().unwrap_or(&archived), &cfg.archive_dir_name) {
+            manifest::record_removed(arch_root, &archived, true, cfg, ops)?;
+        }
+    }
+    journal::record(journal::Operation::Restore { from: archived.clone(), to: dest.clone(), compressed: unpacked.is_some() }, cfg, ops)?;
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    vcs::commit_paths(&format!("Restore {name}"), &[&archived, &dest], cfg, ops)?;
+    cfg.hooks.run(&hooks::HookCall { event: hooks::HookEvent::PostRestore, slug: &name, path: &dest, source: Some(&archived) })?;
+    Ok(dest)
+}
+
+/// The archived item `name` refers to (see [`manifest::find_archived`]) in the archive next to
+/// the project root, or else in `./archive`.
+fn find_archived_by_name(name: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    for arch_root in [cfg.archive_dir_for_dir(&cfg.project_root), cfg.archive_dir_for_file(Path::new("."))] {
+        if let Some(found) = manifest::find_archived(&arch_root, name, ops)? {
+            return Ok(found);
+        }
+    }
+    Err(SlugpmError::NotFound(format!("{name} does not exist, and no archive manifest records it")))
+}
+
+/// Moves `target` to `cfg.trash` instead of an archive. A symlink is trashed itself, not what it
+/// points to.
+pub fn trash_target(target: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<trash::Trashed> {
+    if !ops.exists(target) && !ops.is_symlink(target) {
+        return Err(SlugpmError::not_found(target));
+    }
+    let path = resolve_append_target(target, ops)?;
+    if path.parent().is_none() || ops.canonicalize(&cfg.project_root).is_ok_and(|root| root == path) {
+        return Err(SlugpmError::Other(format!("refusing to trash {}", path.display())));
+    }
+    let trashed = cfg.trash.trash(&path, cfg.clock.now(), ops)?;
+    journal::record(journal::Operation::Trash { from: path, to: trashed.dest.clone(), info: trashed.info.clone() }, cfg, ops)?;
+    Ok(trashed)
+}
+
+/// Strips a leading `YYYY-MM-DD` (and the following `-`, if any) from a project name; see
+/// [`Config::date_prefixes`] for configured patterns.
+pub fn strip_date_prefix(name: &str) -> String {
+    date_prefix::defaults().strip(name).to_string()
+}
+
+/// A project name taken apart by [`parse_project_name`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedName {
+    /// The date in the name's prefix, if it has one naming a real day.
+    pub date: Option<NaiveDate>,
+    /// The name without its date prefix.
+    pub name: String,
+    /// `name` slugified with the config's slug options.
+    pub slug: String,
+}
+
+/// Splits a project name into the date of its prefix (matched with [`Config::date_prefixes`]),
+/// the rest of the name and that rest's slug.
+pub fn parse_project_name(name: &str, cfg: &Config) -> ParsedName {
+    let (date, rest) = cfg.date_prefixes.split(name);
+    ParsedName { date, name: rest.to_string(), slug: slugify_with(rest, cfg) }
+}
+
+/// Words [`title_from_slug`] leaves lowercase unless they start or end the title.
+pub const DEFAULT_SMALL_WORDS: &[&str] = &["a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to", "vs", "with"];
+
+/// A human title for a project name, reversing the slug: `2025-09-13-my-cool-project` ->
+/// `My Cool Project`. The date prefix is dropped, words split at `-`, `_` and `separator`, and
+/// each is capitalized except the `small_words` in the middle of the title.
+pub fn title_from_slug(name: &str, separator: &str, small_words: &[String]) -> String {
+    let name = strip_date_prefix(name);
+    let words: Vec<&str> = name.split(|c: char| c == '-' || c == '_' || separator.contains(c)).filter(|w| !w.is_empty()).collect();
+    let last = words.len().saturating_sub(1);
+    words.iter().enumerate().map(|(i, word)| {
+        if i != 0 && i != last && small_words.iter().any(|s| s.eq_ignore_ascii_case(word)) {
+            return word.to_lowercase();
+        }
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+    }).collect::<Vec<String>>().join(" ")
+}
+
+/// The title of the project `name`, a directory or a slug under `cfg.project_root`: the title in
+/// its metadata when it has one, else [`title_from_slug`] of its name.
+pub fn project_title(name: &Path, small_words: &[String], cfg: &Config, ops: &dyn FileOps) -> Result<String> {
+    let dir = [name.to_path_buf(), cfg.project_root.join(name)].into_iter().find(|d| ops.is_dir(d));
+    if let Some(meta) = dir.map(|d| metadata::read_metadata(&d, ops)).transpose()?.flatten() {
+        if !meta.title.trim().is_empty() {
+            return Ok(meta.title);
+        }
+    }
+    let base = name.file_name().ok_or_else(|| SlugpmError::InvalidName(format!("invalid project name: {}", name.display())))?;
+    Ok(title_from_slug(cfg.date_prefixes.strip(&base.to_string_lossy()), &cfg.slug.separator, small_words))
+}
+
+/// Converts CRLF and lone CR line endings to LF.
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Reads all of `input` as text with normalized line endings; every piped-title feature reads through this.
+pub fn read_input_text(input: &mut dyn Read) -> Result<String> {
+    let mut buf = String::new();
+    input.read_to_string(&mut buf).io_context(|| "reading input".to_string())?;
+    Ok(normalize_line_endings(&buf))
+}
+
+pub fn slugify_title(title: &str) -> String {
+    slugify(title)
+}
+
+/// Slugification settings beyond the built-in ASCII folding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlugOptions {
+    /// Joins the words of a slug, `-` by default.
+    pub separator: String,
+    /// Longest slug, in bytes; longer slugs are cut after the last whole word that fits.
+    pub max_len: Option<usize>,
+    /// Keep the title's capitals instead of lowercasing.
+    pub keep_case: bool,
+    /// Words (compared case-insensitively) left out of the slug, unless the title has nothing else.
+    pub stopwords: Vec<String>,
+    /// How non-ASCII letters are spelled.
+    pub transliteration: Transliteration,
+}
+
+/// How non-ASCII letters in a title are spelled in its slug.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transliteration {
+    /// Fold to the closest ASCII (`ä` -> `a`; Chinese as pinyin, Japanese kana as romaji)
+    #[default]
+    Ascii,
+    /// German spelling for umlauts and `ß` (`ä` -> `ae`, `ß` -> `ss`), then ASCII
+    German,
+    /// Keep letters and digits of any script, lowercased (`Über` -> `über`)
+    Unicode,
+}
+
+/// The German ASCII spelling of `c`, if it has one.
+fn german_spelling(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ä' => "ae",
+        'ö' => "oe",
+        'ü' => "ue",
+        'Ä' => "Ae",
+        'Ö' => "Oe",
+        'Ü' => "Ue",
+        'ß' => "ss",
+        'ẞ' => "SS",
+        _ => return None,
+    })
+}
+
+impl Default for SlugOptions {
+    fn default() -> Self {
+        SlugOptions { separator: "-".to_string(), max_len: None, keep_case: false, stopwords: Vec::new(), transliteration: Transliteration::default() }
+    }
+}
+
+/// Stopwords for `--stopwords` with no list: English articles, conjunctions and short prepositions.
+pub const DEFAULT_STOPWORDS: &[&str] = &["a", "an", "and", "at", "by", "for", "in", "of", "on", "or", "the", "to", "with"];
+
+/// The words of `title` under `opts`' transliteration and case; [`Transliteration::Ascii`] folds
+/// them the way [`slugify_title`] does.
+pub fn slug_words(title: &str, opts: &SlugOptions) -> Vec<String> {
+    let lower = |w: &str| if opts.keep_case { w.to_string() } else { w.to_lowercase() };
+    if opts.transliteration == Transliteration::Unicode {
+        return title.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(lower).collect();
+    }
+    let mut ascii = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if let Some(spelling) = german_spelling(c).filter(|_| opts.transliteration == Transliteration::German) {
+            ascii.push_str(spelling);
+        } else {
+            ascii.push_str(deunicode::deunicode_char(c).unwrap_or("-"));
+        }
+    }
+    ascii.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).map(lower).collect()
+}
+
+/// Slugifies `title` under `opts`.
+pub fn slugify_title_with(title: &str, opts: &SlugOptions) -> String {
+    let mut words = slug_words(title, opts);
+    if !opts.stopwords.is_empty() {
+        let kept: Vec<String> = words.iter()
+            .filter(|w| !opts.stopwords.iter().any(|s| s.to_lowercase() == w.to_lowercase()))
+            .cloned()
+            .collect();
+        if !kept.is_empty() {
+            words = kept;
+        }
+    }
+    let Some(max) = opts.max_len else {
+        return words.join(&opts.separator);
+    };
+    let mut slug = String::new();
+    for word in &words {
+        let extra = if slug.is_empty() { word.len() } else { opts.separator.len() + word.len() };
+        if slug.len() + extra > max {
+            break;
+        }
+        if !slug.is_empty() {
+            slug += &opts.separator;
+        }
+        slug += word;
+    }
+    if slug.is_empty() {
+        // A first word longer than the limit is cut mid-word rather than dropped.
+        slug = words.first().map(|w| {
+            let end = (0..=w.len().min(max)).rev().find(|&i| w.is_char_boundary(i)).unwrap_or(0);
+            w[..end].to_string()
+        }).unwrap_or_default();
+    }
+    slug
+}
+
+/// How emoji in a title are treated before slugifying.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EmojiMode {
+    /// Remove them
+    #[default]
+    Drop,
+    /// Replace common ones with their names (`🚀` -> `rocket`) and remove the rest
+    Name,
+}
+
+/// Emoji spelled out by [`EmojiMode::Name`].
+const EMOJI_NAMES: &[(char, &str)] = &[
+    ('🚀', "rocket"), ('🔥', "fire"), ('✨', "sparkles"), ('⭐', "star"), ('❤', "heart"),
+    ('🎉', "tada"), ('🐛', "bug"), ('📚', "books"), ('📝', "memo"), ('💡', "bulb"),
+    ('✅', "check"), ('⚠', "warning"), ('🔒', "lock"), ('🔧', "wrench"), ('🔨', "hammer"),
+    ('📦', "package"), ('🎨', "art"), ('⚡', "zap"), ('🚧', "construction"), ('🌍', "globe"),
+    ('📅', "calendar"), ('📈', "chart"), ('💻', "computer"), ('📱', "phone"), ('🎵', "music"),
+    ('📷', "camera"), ('🏠', "house"), ('🚗', "car"), ('☕', "coffee"), ('🍕', "pizza"),
+    ('😄', "smile"), ('👍', "thumbs up"),
+];
+
+/// Whether `c` is an emoji, or one of the modifiers and joiners that combine them.
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF      // pictographs, emoticons, transport, flags, skin tones
+        | 0x2600..=0x27BF      // miscellaneous symbols and dingbats
+        | 0x2B00..=0x2BFF      // arrows and stars
+        | 0x200D | 0xFE0F      // zero-width joiner, emoji presentation selector
+        | 0xE0020..=0xE007F)   // tag sequences
+}
+
+/// Applies `mode` to the emoji in `title`, leaving everything else as is.
+///
+/// Each emoji becomes a space (or its spaced-out name), so neighbouring words stay separate.
+pub fn substitute_emoji(title: &str, mode: EmojiMode) -> String {
+    let mut out = String::with_capacity(title.len());
+    for c in title.chars() {
+        if !is_emoji(c) {
+            out.push(c);
+            continue;
+        }
+        match EMOJI_NAMES.iter().find(|(e, _)| *e == c) {
+            Some((_, name)) if mode == EmojiMode::Name => {
+                out.push(' ');
+                out.push_str(name);
+                out.push(' ');
+            }
+            _ => out.push(' '),
+        }
+    }
+    out
+}
+
+/// Inserts a space at each lower-to-upper case boundary: `getHttpResponse` -> `get Http Response`.
+///
+/// Consecutive capitals are split one per letter unless `keep_acronyms` is set, in which case
+/// a run stays together and only its last capital starts a new word when lowercase follows:
+/// `getHTTPResponse` -> `get HTTP Response`.
+pub fn split_camel_case(title: &str, keep_acronyms: bool) -> String {
+    let chars: Vec<char> = title.chars().collect();
+    let mut out = String::with_capacity(title.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let boundary = if prev.is_uppercase() { !keep_acronyms || next_is_lower }
+                           else { prev.is_lowercase() || prev.is_ascii_digit() };
+            if boundary {
+                out.push(' ');
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Applies `cfg`'s title rewrites (case splitting, then emoji) ahead of slugifying.
+pub fn normalize_title(title: &str, cfg: &Config) -> String {
+    let title = if cfg.split_case { split_camel_case(title, cfg.keep_acronyms) } else { title.to_string() };
+    substitute_emoji(&title, cfg.emoji)
+}
+
+/// Splits `name` into stem and extension at the last `.`, keeping the extension as written.
+///
+/// Dotfiles like `.profile` have no extension.
+pub fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+/// Slugifies a file name's stem, keeping its extension: `My Report.PDF` -> `my-report.pdf`,
+/// or `my-report.PDF` when `ext_lower` is false.
+pub fn slugify_file_name(name: &str, ext_lower: bool) -> String {
+    let (stem, ext) = split_extension(name);
+    match ext {
+        Some(ext) if ext_lower => format!("{}.{}", slugify_title(stem), ext.to_lowercase()),
+        Some(ext) => format!("{}.{}", slugify_title(stem), ext),
+        None => slugify_title(stem),
+    }
+}
+
+/// Longest slug accepted by [`check_title`]: the usual file name limit, in bytes.
+pub const MAX_SLUG_LEN: usize = 255;
+
+/// Names that cannot be used as a directory on some platforms, or that slugpm itself uses.
+const RESERVED_SLUGS: &[&str] = &[
+    "archive", "con", "prn", "aux", "nul",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Why a title can't be used as a project name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidSlug {
+    Empty,
+    TooLong { slug: String, len: usize },
+    Reserved(String),
+}
+
+impl std::fmt::Display for InvalidSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidSlug::Empty => write!(f, "title produces an empty slug"),
+            InvalidSlug::TooLong { len, .. } => write!(f, "slug is {len} bytes long (max {MAX_SLUG_LEN})"),
+            InvalidSlug::Reserved(slug) => write!(f, "slug {slug:?} is a reserved name"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidSlug {}
+
+/// Checks that `title` produces a nonempty, length-valid, non-reserved slug under `cfg` (as
+/// [`slugify_with`] makes it), returning the slug.
+pub fn check_title(title: &str, cfg: &Config) -> std::result::Result<String, InvalidSlug> {
+    let slug = slugify_with(title, cfg);
+    if slug.is_empty() {
+        Err(InvalidSlug::Empty)
+    } else if slug.len() > MAX_SLUG_LEN {
+        Err(InvalidSlug::TooLong { len: slug.len(), slug })
+    } else if RESERVED_SLUGS.contains(&slug.as_str()) {
+        Err(InvalidSlug::Reserved(slug))
+    } else {
+        Ok(slug)
+    }
+}
+
+/// Slugifies `title` under `cfg` ([`slugify_with`]), applying `cfg.fallback_slug` if the
+/// result is empty.
+pub fn slug_or_fallback(title: &str, cfg: &Config) -> Result<String> {
+    let slug = slugify_with(title, cfg);
+    if !slug.is_empty() {
+        return Ok(slug);
+    }
+    // Hash the original title, so different all-emoji titles still get different slugs.
+    fallback_slug(title, cfg.fallback_slug)
+}
+
+/// [`slugify_title_with`] `cfg`'s slug options, after its title rewrites.
+pub fn slugify_with(title: &str, cfg: &Config) -> String {
+    slugify_title_with(&normalize_title(title, cfg), &cfg.slug)
+}
+
+/// The project slug for `title` under `cfg`'s title rewrites and fallback setting.
+pub fn project_slug(title: &str, cfg: &Config) -> Result<String> {
+    if let Some(cmd) = &cfg.slugify_cmd {
+        return external_slug(cmd, title, cfg.runner.as_ref());
+    }
+    slug_or_fallback(title, cfg)
+}
+
+/// The first line `cmd` prints for `title` on its STDIN, which must be a usable single path segment.
+pub fn external_slug(cmd: &str, title: &str, runner: &dyn CommandRunner) -> Result<String> {
+    let stdout = runner.run(cmd, format!("{title}\n").as_bytes())?;
+    let stdout = String::from_utf8_lossy(&stdout);
+    let slug = stdout.lines().next().unwrap_or("").trim();
+    if slug.is_empty() {
+        return Err(SlugpmError::InvalidName(format!("{cmd:?} printed an empty slug for {title:?}")));
+    }
+    if slug.contains(['/', '\\']) || slug == "." || slug == ".." {
+        return Err(SlugpmError::InvalidName(format!("{cmd:?} printed {slug:?} for {title:?}, which is not a single path segment")));
+    }
+    Ok(slug.to_string())
+}
+
+fn fallback_slug(title: &str, mode: FallbackSlug) -> Result<String> {
+    match mode {
+        FallbackSlug::Hash => Ok(format!("untitled-{}", &integrity::sha256_hex(title.as_bytes())[..8])),
+        FallbackSlug::Error => Err(SlugpmError::InvalidName(format!("title {title:?} produces an empty slug"))),
+        FallbackSlug::Literal => Ok("untitled".to_string()),
+    }
+}
+
+/// One `--project-log` line: `<timestamp>\t<title>\t<dir>\n`.
+///
+/// Tabs and line breaks in the title become spaces so the record stays one TSV row.
+pub fn project_log_record(now: NaiveDateTime, title: &str, dir: &Path) -> String {
+    let title: String = title.chars().map(|c| if matches!(c, '\t' | '\n' | '\r') { ' ' } else { c }).collect();
+    format!("{}\t{}\t{}\n", now.format("%Y-%m-%dT%H:%M:%S"), title, dir.display())
+}
+
+/// Name of the log file `note` appends to in a project directory.
+pub const NOTE_LOG_FILE: &str = "log.md";
+
+/// One `note` line: `- YYYY-MM-DD HH:MM <text>\n`, with line breaks in `text` joined up so
+/// the note stays one Markdown list item.
+pub fn note_line(now: NaiveDateTime, text: &str) -> String {
+    let text = text.split(['\n', '\r']).map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    format!("- {} {text}\n", now.format("%Y-%m-%d %H:%M"))
+}
+
+/// Appends a [`note_line`] for `text` to the project `slug`'s `log.md`, creating it if needed,
+/// and returns the log's path. Like an archive append, it can be undone.
+pub fn add_note(slug: &str, text: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if text.trim().is_empty() {
+        return Err(SlugpmError::Other("the note is empty".into()));
+    }
+    let log = resolve_project(slug, cfg, ops)?.join(NOTE_LOG_FILE);
+    let existed = ops.is_file(&log);
+    let line = note_line(cfg.clock.now(), text);
+    let start = ops.append_locked(&log, line.as_bytes())?;
+    journal::record(journal::Operation::append(&log, (existed || start > 0).then_some(start), line.as_bytes()), cfg, ops)?;
+    recent::record("note", log.parent().unwrap(), cfg, ops);
+    Ok(log)
+}
+
+/// Directory in a project that `today` keeps its daily notes in.
+pub const DAILY_NOTES_DIR: &str = "notes";
+
+/// The project `slug`'s note for today, `notes/YYYY-MM-DD.md`, and whether it was just created.
+///
+/// A new note is rendered from the file template `template` (see [`template::file_template_path`]),
+/// else from `templates/today.md` if that exists, else it's just a `# YYYY-MM-DD` heading. An
+/// existing note is left alone.
+pub fn today_note(slug: &str, template: Option<&str>, cfg: &Config, ops: &dyn FileOps) -> Result<(PathBuf, bool)> {
+    let dir = resolve_project(slug, cfg, ops)?;
+    let today = cfg.clock.now();
+    let note = dir.join(DAILY_NOTES_DIR).join(format!("{}.md", today.format("%Y-%m-%d")));
+    if ops.is_file(&note) {
+        return Ok((note, false));
+    }
+    let template = match template {
+        Some(name) => Some(template::file_template_path(name, cfg, ops)?),
+        None => template::file_template_path(template::DAILY_NOTE_TEMPLATE, cfg, ops).ok(),
+    };
+    let contents = match template {
+        Some(path) => {
+            let small_words: Vec<String> = DEFAULT_SMALL_WORDS.iter().map(|w| w.to_string()).collect();
+            let vars = template::TemplateVars {
+                slug: slug.to_string(),
+                title: project_title(&dir, &small_words, cfg, ops)?,
+                date: today.format(&cfg.date_format).to_string(),
+            };
+            template::render(&ops.read_to_string(&path)?, &vars)
+        }
+        None => format!("# {}\n", today.format("%Y-%m-%d")),
+    };
+    ops.create_dir_all(note.parent().unwrap())?;
+    ops.write(&note, contents.as_bytes())?;
+    journal::record(journal::Operation::append(&note, None, contents.as_bytes()), cfg, ops)?;
+    Ok((note, true))
+}
+
+/// The directory name for `title`: its slug, after the date when `cfg.dated` is set.
+pub fn project_name(title: &str, cfg: &Config) -> Result<String> {
+    let slug = project_slug(title, cfg)?;
+    if !cfg.dated {
+        return Ok(slug);
+    }
+    Ok(format!("{}{}{}", cfg.clock.now().format(&cfg.date_format), cfg.slug.separator, slug))
+}
+
+/// The directory [`create_project_dir_with`] would create for `title`, without creating it.
+/// With `cfg.ids`, the name has the next free ID, which isn't taken, unless a project of that
+/// name already exists.
+pub fn project_dir_for(title: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if let Some(dir) = existing_with_id(title, cfg, ops)? {
+        return Ok(dir);
+    }
+    let id = cfg.ids.then(|| ids::next_id(cfg, ops)).transpose()?;
+    project_dir_with_id(title, id, cfg, ops)
+}
+
+/// With `cfg.ids` (and not `cfg.unique`), the project that already has `title`'s name, with
+/// any ID or none, so a repeated create collides instead of taking a new ID.
+fn existing_with_id(title: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    if !cfg.ids || cfg.unique {
+        return Ok(None);
+    }
+    let name = project_name(title, cfg)?;
+    let plain = safe_path::join_segment(&cfg.project_root, &name, "project name")?;
+    if ops.exists(&plain) {
+        return Ok(Some(plain));
+    }
+    ids::find_numbered(&name, cfg, ops)
+}
+
+fn project_dir_with_id(title: &str, id: Option<u64>, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let mut name = project_name(title, cfg)?;
+    if let Some(id) = id {
+        name = ids::numbered_name(id, &name, cfg);
+    }
+    let dir = safe_path::join_segment(&cfg.project_root, &name, "project name")?;
+    if cfg.unique && ops.exists(&dir) {
+        return Ok(numbered_path(&dir, ops));
+    }
+    Ok(dir)
+}
+
+/// Creates `project/<slug>` for `title` and returns the directory path.
+///
+/// An existing directory is a [`SlugpmError::Collision`], unless `cfg.unique` picks a free
+/// name instead or `cfg.reuse` returns it as it is. With `cfg.ids`, the project takes the next
+/// ID ([`ids::allocate_id`]) and its metadata records it; a project of the same name with any
+/// ID is the existing directory, and no ID is taken for it.
+pub fn create_project_dir_with(title: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let (id, dir) = match existing_with_id(title, cfg, ops)? {
+        Some(dir) => (None, dir),
+        None => {
+            let id = cfg.ids.then(|| ids::allocate_id(cfg, ops)).transpose()?;
+            (id, project_dir_with_id(title, id, cfg, ops)?)
+        }
+    };
+    if ops.exists(&dir) {
+        return if cfg.reuse { Ok(dir) } else { Err(SlugpmError::Collision(dir)) };
+    }
+    let template = cfg.template.as_deref().map(|name| template::template_path(name, cfg, ops)).transpose()?;
+    ops.create_dir_all(&dir)?;
+    let mut written = Vec::new();
+    if let Some(template) = template {
+        let vars = template::TemplateVars {
+            slug: dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            title: title.to_string(),
+            date: cfg.clock.now().format(&cfg.date_format).to_string(),
+        };
+        written = match template::apply_template(&template, &dir, &vars, ops) {
+            Ok(written) => written,
+            Err(e) => {
+                // Don't leave a half-made project behind.
+                ops.remove_dir_all(&dir)?;
+                return Err(e);
+            }
+        };
+    }
+    // A template's own `project.md` keeps its body and extra keys.
+    metadata::update_metadata(&dir, ops, |meta| {
+        meta.title = title.to_string();
+        meta.slug = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        meta.created = cfg.clock.now().format("%Y-%m-%d").to_string();
+        meta.status = metadata::DEFAULT_STATUS.to_string();
+        if let Some(id) = id {
+            meta.set(ids::ID_KEY, id.to_string());
+        }
+    })?;
+    written.push(metadata::metadata_path(&dir));
+    if let Some(log) = &cfg.project_log {
+        let record = project_log_record(cfg.clock.now(), title, &dir);
+        ops.open_append(log)?.write_all(record.as_bytes())
+            .io_context(|| format!("appending to {}", log.display()))?;
+    }
+    let files = written.iter()
+        .map(|file| Ok((file.strip_prefix(&dir).unwrap_or(file).to_path_buf(), integrity::sha256_hex(&ops.read(file)?))))
+        .collect::<Result<_>>()?;
+    if cfg.git {
+        cfg.vcs.init(&dir)?;
+    }
+    journal::record(journal::Operation::Create { path: dir.clone(), files }, cfg, ops)?;
+    recent::record("create", &dir, cfg, ops);
+    let slug = dir.file_name().unwrap_or_default().to_string_lossy();
+    cfg.hooks.run(&hooks::HookCall { event: hooks::HookEvent::PostCreate, slug: &slug, path: &dir, source: None })?;
+    Ok(dir)
+}
+
+/// A project found by [`list_projects`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectEntry {
+    pub slug: String,
+    pub path: PathBuf,
+    /// Whether the project lives in `archive/` rather than `project/`.
+    pub archived: bool,
+    pub modified: SystemTime,
+    /// The title from the project's metadata, if it has readable metadata.
+    pub title: Option<String>,
+    /// The creation date from the project's metadata, `YYYY-MM-DD`.
+    pub created: Option<String>,
+    /// The tags from the project's metadata (see [`tags`]).
+    pub tags: Vec<String>,
+    pub status: status::ProjectStatus,
+}
+
+/// How [`list_projects`] orders its entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProjectSort {
+    /// Least recently modified first
+    #[default]
+    Modified,
+    /// Alphabetically by slug
+    Name,
+    /// By lifecycle status (active, paused, done, archived), then slug
+    Status,
+    /// Oldest date prefix first, then names without one; ties by slug
+    Date,
+}
+
+/// The project directories in `cfg.project_root`, plus with `include_archived` the archived
+/// ones in the archive next to it (where [`archive_dir`] puts them). Missing directories are empty.
+pub fn list_projects(include_archived: bool, sort: ProjectSort, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<ProjectEntry>> {
+    let mut sources = vec![(cfg.project_root.clone(), false)];
+    if include_archived {
+        sources.push((cfg.archive_dir_for_dir(&cfg.project_root), true));
+    }
+    let mut entries = Vec::new();
+    for (dir, archived) in sources {
+        if !ops.is_dir(&dir) {
+            continue;
+        }
+        for path in ops.read_dir(&dir)?.into_iter().filter(|p| ops.is_dir(p)) {
+            let meta = metadata::read_metadata(&path, ops).ok().flatten();
+            entries.push(ProjectEntry {
+                slug: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                modified: ops.modified(&path)?,
+                archived,
+                title: meta.as_ref().map(|meta| meta.title.clone()).filter(|t| !t.is_empty()),
+                created: meta.as_ref().map(|meta| meta.created.clone()).filter(|c| !c.is_empty()),
+                tags: meta.as_ref().map(|meta| meta.tags().into_iter().map(String::from).collect()).unwrap_or_default(),
+                status: status::ProjectStatus::of(meta.as_ref(), archived),
+                path,
+            });
+        }
+    }
+    match sort {
+        ProjectSort::Modified => entries.sort_by(|a, b| (a.modified, &a.slug).cmp(&(b.modified, &b.slug))),
+        ProjectSort::Name => entries.sort_by(|a, b| a.slug.cmp(&b.slug)),
+        ProjectSort::Status => entries.sort_by(|a, b| (a.status, &a.slug).cmp(&(b.status, &b.slug))),
+        ProjectSort::Date => entries.sort_by_cached_key(|e| {
+            let date = parse_project_name(&e.slug, cfg).date;
+            (date.is_none(), date, e.slug.clone())
+        }),
+    }
+    Ok(entries)
+}
+
+/// The slugs of the active projects, alphabetically; what `resolve_project`, `find_project`
+/// and shell completion choose from.
+pub fn project_slugs(cfg: &Config, ops: &dyn FileOps) -> Result<Vec<String>> {
+    Ok(list_projects(false, ProjectSort::Name, cfg, ops)?.into_iter().map(|e| e.slug).collect())
+}
+
+/// Edit distance between `a` and `b`, counted in characters.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (row[j + 1] + 1).min(row[j] + 1).min(diag + usize::from(ca != *cb));
+            diag = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// Up to three of `candidates` that look like typos of `name` (or contain it), closest first.
+pub fn suggest_similar<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let max = (name.chars().count() / 3).max(2);
+    let mut close: Vec<(usize, &str)> = candidates.into_iter()
+        .map(|c| (levenshtein(name, c), c))
+        .filter(|(d, c)| *d <= max || (!name.is_empty() && c.contains(name)))
+        .collect();
+    close.sort();
+    close.into_iter().take(3).map(|(_, c)| c).collect()
+}
+
+/// The directory of the project `slug` under `cfg.project_root`, with suggestions if there's none.
+/// A number that isn't a directory name is looked up as a project ID ([`ids`]).
+pub fn resolve_project(slug: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let dir = safe_path::join_segment(&cfg.project_root, slug, "project")?;
+    if ops.is_dir(&dir) {
+        return Ok(dir);
+    }
+    if let Some(dir) = ids::parse_id(slug).map(|id| ids::find_by_id(id, cfg, ops)).transpose()?.flatten() {
+        return Ok(dir);
+    }
+    let existing = project_slugs(cfg, ops)?;
+    let suggestions = suggest_similar(slug, existing.iter().map(String::as_str));
+    if suggestions.is_empty() {
+        return Err(SlugpmError::NotFound(format!("no project {slug:?} in {}", cfg.project_root.display())));
+    }
+    Err(SlugpmError::NotFound(format!("no project {slug:?} in {}; did you mean {}?", cfg.project_root.display(), suggestions.join(", "))))
+}
+
+/// Renames the project `slug` to the slug of `new_title`, keeping its ID and any date prefix, and returns
+/// the new directory.
+///
+/// The project's metadata, if it has any, gets the new title and slug and lists the old name
+/// under `aliases`. With `symlink`, a link at the old path points to the new one.
+pub fn rename_project(slug: &str, new_title: &str, symlink: bool, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let from = resolve_project(slug, cfg, ops)?;
+    let old_name = from.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let id = metadata::read_metadata(&from, ops)?.and_then(|meta| meta.get(ids::ID_KEY).and_then(ids::parse_id));
+    let id_prefix = id.map(|id| ids::numbered_name(id, "", cfg)).unwrap_or_default();
+    let rest = old_name.strip_prefix(id_prefix.as_str()).unwrap_or(&old_name);
+    let date_prefix = &rest[..cfg.date_prefixes.prefix_len(rest)];
+    let new_name = format!("{id_prefix}{date_prefix}{}", project_slug(new_title, cfg)?);
+    let to = safe_path::join_segment(&cfg.project_root, &new_name, "project name")?;
+    if to != from {
+        if ops.exists(&to) {
+            return Err(SlugpmError::Collision(to));
+        }
+        move_path_with_progress(&from, &to, cfg.progress.as_ref(), ops)?;
+    }
+    let mut previous_title = None;
+    if let Some(mut meta) = metadata::read_metadata(&to, ops)? {
+        previous_title = Some(std::mem::replace(&mut meta.title, new_title.to_string()));
+        meta.slug = new_name.clone();
+        if to != from {
+            let mut aliases: Vec<String> = meta.aliases().into_iter().map(String::from).collect();
+            aliases.push(old_name.clone());
+            meta.set_aliases(&aliases);
+        }
+        metadata::write_metadata(&to, &meta, ops)?;
+    }
+    let symlink = symlink && to != from;
+    if symlink {
+        ops.symlink_dir(Path::new(&new_name), &from)?;
+    }
+    journal::record(journal::Operation::Rename { from, to: to.clone(), previous_title, symlink }, cfg, ops)?;
+    Ok(to)
+}
+
+/// Moves (or with `copy`, copies) the file or directory `source` into the project named by
+/// `dest`, a slug optionally followed by `/`-separated subdirectories (`my-project/refs`),
+/// which are created as needed. Returns where it ended up.
+///
+/// An existing item of the same name is handled like in the archive: by `cfg.on_conflict`, else
+/// the target directory's `.slugpm-policy`, else an error.
+pub fn move_into_project(source: &Path, dest: &str, copy: bool, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let mut parts = dest.split('/').filter(|part| !part.is_empty());
+    let project = resolve_project(parts.next().unwrap_or_default(), cfg, ops)?;
+    let mut dir = project.clone();
+    for part in parts {
+        dir = safe_path::join_segment(&dir, part, "subdirectory")?;
+    }
+    if !ops.exists(source) {
+        return Err(SlugpmError::not_found(source));
+    }
+    let name = safe_path::file_segment(source)?;
+    let real_source = ops.canonicalize(source)?;
+    if ops.is_dir(&real_source) && dir.starts_with(&real_source) {
+        return Err(SlugpmError::InvalidName(format!("cannot move {} into itself", source.display())));
+    }
+    ops.create_dir_all(&dir)?;
+    let policy = conflict_policy_for(&dir, cfg, ops)?;
+    let to = resolve_conflict(&dir.join(name), policy, cfg, ops)?;
+    if ops.is_dir(&to) {
+        return Err(SlugpmError::Collision(to));
+    }
+    safe_path::ensure_under(&to, &project)?;
+    if copy {
+        copy::copy_tree_interruptible(source, &to, ops, cfg.progress.as_ref())?;
+    } else {
+        move_path_with_progress(source, &to, cfg.progress.as_ref(), ops)?;
+    }
+    journal::record(journal::Operation::Move { from: source.to_path_buf(), to: to.clone(), copy }, cfg, ops)?;
+    Ok(to)
+}
+
+/// Lowercase alphanumeric words of `text`, so `My Proj` and `my_proj` compare equal.
+fn match_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase).collect()
+}
+
+/// How well `query` matches `candidate`, best first: exact, prefix, substring, every query word
+/// appears, then the query's letters appear in order. `None` if it doesn't match at all.
+fn match_rank(query: &[String], candidate: &str) -> Option<u8> {
+    let (q, c) = (query.join("-"), match_words(candidate).join("-"));
+    let u = match_words(&strip_date_prefix(candidate)).join("-");
+    if q == c || q == u {
+        Some(0)
+    } else if c.starts_with(&q) || u.starts_with(&q) {
+        Some(1)
+    } else if c.contains(&q) {
+        Some(2)
+    } else if query.iter().all(|w| c.contains(w.as_str())) {
+        Some(3)
+    } else {
+        let mut letters = c.chars();
+        q.chars().filter(|ch| *ch != '-').all(|ch| letters.any(|l| l == ch)).then_some(4)
+    }
+}
+
+/// The `candidates` that fuzzily match `query`, best match first; ties go to the shorter name.
+pub fn fuzzy_rank<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let query = match_words(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut ranked: Vec<(u8, usize, &str)> = candidates.into_iter()
+        .filter_map(|c| Some((match_rank(&query, c)?, c.len(), c)))
+        .collect();
+    ranked.sort();
+    ranked.into_iter().map(|(_, _, c)| c).collect()
+}
+
+/// The directory of the active project with the ID `query`, else the one that best matches
+/// `query` (see [`fuzzy_rank`]).
+pub fn find_project(query: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    if let Some(dir) = ids::parse_id(query).map(|id| ids::find_by_id(id, cfg, ops)).transpose()?.flatten() {
+        return Ok(dir);
+    }
+    let slugs = project_slugs(cfg, ops)?;
+    match fuzzy_rank(query, slugs.iter().map(String::as_str)).first() {
+        Some(slug) => Ok(cfg.project_root.join(slug)),
+        None => Err(SlugpmError::NotFound(format!("no project in {} matches {query:?}", cfg.project_root.display()))),
+    }
+}
+
+/// Result of creating several projects from one input.
+#[derive(Debug, Default)]
+pub struct CreateSummary {
+    pub created: Vec<PathBuf>,
+    /// Titles skipped because an earlier line produced the same slug (unless `unique` is set).
+    pub skipped: Vec<String>,
+}
+
+/// Creates one project per non-empty line of `input`, returning the created paths in order.
+pub fn create_projects_multi_with(input: &str, cfg: &Config, ops: &dyn FileOps) -> Result<CreateSummary> {
+    let mut summary = CreateSummary::default();
+    let mut names = BTreeSet::new();
+    for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if !names.insert(project_name(line, cfg)?) && !cfg.unique {
+            summary.skipped.push(line.to_string());
+            continue;
+        }
+        summary.created.push(create_project_dir_with(line, cfg, ops)?);
+    }
+    Ok(summary)
 }
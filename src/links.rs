@@ -0,0 +1,129 @@
+//! Relations between projects (`slugpm link`), kept as the comma-separated `links` key in each
+//! project's metadata, as `<kind>:<slug>`:
+//!
+//! ```text
+//! ---
+//! title: Parser Rewrite
+//! ...
+//! links: spun-off-from:compiler, blocked-by:test-harness
+//! ---
+//! ```
+//!
+//! Linking records the relation in both projects, the other one seeing its inverse
+//! (`blocked-by` and `blocks`, `spun-off-from` and `spun-off-into`; `related` both ways).
+
+use std::path::Path;
+use crate::{journal, metadata, resolve_project, Config, FileOps, Result, SlugpmError};
+
+/// The metadata key holding a project's links.
+pub const LINKS_KEY: &str = "links";
+
+/// How one project relates to another.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum LinkKind {
+    /// Related, without a direction
+    #[default]
+    Related,
+    /// Can't go on until the other project is done
+    BlockedBy,
+    /// Holds up the other project
+    Blocks,
+    /// Started as part of the other project
+    SpunOffFrom,
+    /// Gave rise to the other project
+    SpunOffInto,
+}
+
+impl LinkKind {
+    /// The relation as the other project sees it.
+    pub fn inverse(self) -> LinkKind {
+        match self {
+            LinkKind::Related => LinkKind::Related,
+            LinkKind::BlockedBy => LinkKind::Blocks,
+            LinkKind::Blocks => LinkKind::BlockedBy,
+            LinkKind::SpunOffFrom => LinkKind::SpunOffInto,
+            LinkKind::SpunOffInto => LinkKind::SpunOffFrom,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkKind::Related => "related",
+            LinkKind::BlockedBy => "blocked-by",
+            LinkKind::Blocks => "blocks",
+            LinkKind::SpunOffFrom => "spun-off-from",
+            LinkKind::SpunOffInto => "spun-off-into",
+        }
+    }
+}
+
+impl std::fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One entry of a project's `links`: it relates to the project `slug` as `kind`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Link {
+    pub kind: LinkKind,
+    pub slug: String,
+}
+
+impl std::fmt::Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.kind, self.slug)
+    }
+}
+
+/// Parses a `links` value. An entry without a known `<kind>:` is `related`.
+pub fn parse_links(value: &str) -> Vec<Link> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(|entry| {
+        let kind = entry.split_once(':').and_then(|(kind, slug)| {
+            <LinkKind as clap::ValueEnum>::from_str(kind, false).ok().map(|kind| (kind, slug))
+        });
+        match kind {
+            Some((kind, slug)) => Link { kind, slug: slug.to_string() },
+            None => Link { kind: LinkKind::Related, slug: entry.to_string() },
+        }
+    }).collect()
+}
+
+/// The links of the project `slug`, in the order they were made.
+pub fn project_links(slug: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<Link>> {
+    let dir = resolve_project(slug, cfg, ops)?;
+    Ok(links_value(&dir, ops)?.map(|value| parse_links(&value)).unwrap_or_default())
+}
+
+/// Links the projects `a` and `b`: `a` gets `kind:<b>` and `b` gets the inverse, `<inverse>:<a>`,
+/// both by directory name. Both projects have to exist and differ; links they already have are
+/// kept as they are. Returns the link added to `a`.
+pub fn link_projects(a: &str, b: &str, kind: LinkKind, cfg: &Config, ops: &dyn FileOps) -> Result<Link> {
+    let (dir_a, dir_b) = (resolve_project(a, cfg, ops)?, resolve_project(b, cfg, ops)?);
+    if dir_a == dir_b {
+        return Err(SlugpmError::InvalidName(format!("can't link {a:?} to itself")));
+    }
+    let name = |dir: &Path| dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let forward = Link { kind, slug: name(&dir_b) };
+    let backward = Link { kind: kind.inverse(), slug: name(&dir_a) };
+    let mut previous = Vec::new();
+    for (dir, link) in [(&dir_a, &forward), (&dir_b, &backward)] {
+        let value = links_value(dir, ops)?;
+        let mut links = value.as_deref().map(parse_links).unwrap_or_default();
+        if links.contains(link) {
+            continue;
+        }
+        links.push(link.clone());
+        let joined = links.iter().map(Link::to_string).collect::<Vec<_>>().join(", ");
+        metadata::update_metadata(dir, ops, |meta| meta.set(LINKS_KEY, joined))?;
+        previous.push((dir.clone(), value));
+    }
+    if !previous.is_empty() {
+        journal::record(journal::Operation::Link { previous }, cfg, ops)?;
+    }
+    Ok(forward)
+}
+
+fn links_value(dir: &Path, ops: &dyn FileOps) -> Result<Option<String>> {
+    Ok(metadata::read_metadata(dir, ops)?.and_then(|meta| meta.get(LINKS_KEY).map(String::from)))
+}
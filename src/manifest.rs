@@ -0,0 +1,244 @@
+//! What was archived, from where and why, at the root of each archive directory.
+//!
+//! `.manifest.jsonl` has a JSON object per line for each item archived while
+//! `Config::manifest` is set (the CLI sets it outside `--dry-run`): its original path, where it
+//! went, when, the reason if one was given, and the SHA-256 of the archived file or of each file
+//! in the archived directory. `archive --list` reads it, `restore` puts items back where it
+//! says they came from, and `verify` checks the hashes (see [`crate::integrity`]).
+//!
+//! Items that leave the archive on purpose (restored, pruned, or archived and then undone) get
+//! another line, a copy of their entry with `removed` set, so `verify` tells them apart from
+//! items that went missing. Undoing a restore adds one without it again.
+//!
+//! For people, `archive -m <reason>` also adds a section to `ARCHIVE.md`, one per archived item:
+//!
+//! ```markdown
+//! # Archive
+//!
+//! ## notes.md
+//!
+//! - archived: 2025-09-13 14:03
+//! - from: /home/me/p/notes.md
+//! - path: notes.md
+//! - reason: superseded by v2
+//! ```
+//!
+//! `path` is where the item ended up, relative to the archive directory.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+use serde::{Deserialize, Serialize};
+use crate::{parse_sections, Config, FileOps, Result, SlugpmError};
+
+/// Name of the machine-readable manifest at the root of an archive directory.
+pub const MANIFEST_FILE: &str = ".manifest.jsonl";
+
+/// Name of the Markdown manifest at the root of an archive directory.
+pub const ARCHIVE_NOTES_FILE: &str = "ARCHIVE.md";
+
+/// The heading a new `ARCHIVE.md` starts with.
+const NOTES_TITLE: &str = "# Archive\n";
+
+/// One item's section of `ARCHIVE.md`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveNote {
+    /// The item's file or directory name before it was archived.
+    pub name: String,
+    /// Local time of archiving, `YYYY-MM-DD HH:MM`.
+    pub archived: String,
+    pub from: PathBuf,
+    /// Where it went, relative to the archive directory.
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// `<archive>/ARCHIVE.md`.
+pub fn notes_path(arch_root: &Path) -> PathBuf {
+    arch_root.join(ARCHIVE_NOTES_FILE)
+}
+
+/// `note` as an `ARCHIVE.md` section, ending in a blank line. Line breaks in the fields
+/// become spaces so each stays one list item.
+pub fn render_note(note: &ArchiveNote) -> String {
+    let one_line = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!(
+        "## {}\n\n- archived: {}\n- from: {}\n- path: {}\n- reason: {}\n\n",
+        one_line(&note.name), note.archived, note.from.display(), note.path.display(), one_line(&note.reason),
+    )
+}
+
+/// The notes in `ARCHIVE.md`'s text, in file order. Sections without a `reason` item aren't
+/// notes (someone's own additions), and unknown items are ignored.
+pub fn parse_notes(text: &str) -> Vec<ArchiveNote> {
+    parse_sections(text).into_iter()
+        .filter_map(|section| {
+            let heading = section.heading.filter(|h| h.level == 2)?;
+            let mut note = ArchiveNote { name: heading.text, ..ArchiveNote::default() };
+            let mut has_reason = false;
+            for line in section.text.lines().skip(1) {
+                let Some((key, value)) = line.strip_prefix("- ").and_then(|item| item.split_once(": ")) else { continue };
+                match key {
+                    "archived" => note.archived = value.to_string(),
+                    "from" => note.from = value.into(),
+                    "path" => note.path = value.into(),
+                    "reason" => (note.reason, has_reason) = (value.to_string(), true),
+                    _ => {}
+                }
+            }
+            has_reason.then_some(note)
+        })
+        .collect()
+}
+
+/// The notes in `arch_root`'s `ARCHIVE.md`, or none if it doesn't exist.
+pub fn read_notes(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<ArchiveNote>> {
+    let path = notes_path(arch_root);
+    if !ops.is_file(&path) {
+        return Ok(Vec::new());
+    }
+    Ok(parse_notes(&ops.read_to_string(&path)?))
+}
+
+/// Records that `source` was archived to `dest` for `reason` in `arch_root`'s `ARCHIVE.md`,
+/// creating it if needed.
+pub fn record_reason(arch_root: &Path, source: &Path, dest: &Path, reason: &str, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    let note = ArchiveNote {
+        name: source.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        archived: cfg.clock.now().format("%Y-%m-%d %H:%M").to_string(),
+        from: source.to_path_buf(),
+        path: dest.strip_prefix(arch_root).unwrap_or(dest).to_path_buf(),
+        reason: reason.to_string(),
+    };
+    let path = notes_path(arch_root);
+    let mut text = String::new();
+    if !ops.is_file(&path) {
+        text = format!("{NOTES_TITLE}\n");
+    }
+    text += &render_note(&note);
+    ops.append_locked(&path, text.as_bytes()).map(drop)
+}
+
+/// One archived item in `.manifest.jsonl`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The item's original path.
+    pub from: PathBuf,
+    /// Where it went, relative to the archive directory (such as `2025/09/notes.md`).
+    pub path: PathBuf,
+    /// Local time of archiving, `YYYY-MM-DDTHH:MM:SS`.
+    pub archived_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Whether the item was a directory.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dir: bool,
+    /// Whether it was packed into a `.tar.zst`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compressed: bool,
+    /// SHA-256 of the archived file (or `.tar.zst`), for `slugpm verify`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// SHA-256 of each file in an archived directory, by path relative to it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+    /// Local time the item left the archive, `YYYY-MM-DDTHH:MM:SS`, if it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<String>,
+}
+
+/// `<archive>/.manifest.jsonl`.
+pub fn manifest_path(arch_root: &Path) -> PathBuf {
+    arch_root.join(MANIFEST_FILE)
+}
+
+/// Parses the manifest's text, oldest first; blank lines are skipped.
+pub fn parse_manifest(text: &str) -> Result<Vec<ManifestEntry>> {
+    text.lines().filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| SlugpmError::Other(format!("parsing {MANIFEST_FILE}: {e}"))))
+        .collect()
+}
+
+/// The entries of `arch_root`'s manifest, or none if it doesn't exist.
+pub fn read_manifest(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<ManifestEntry>> {
+    let path = manifest_path(arch_root);
+    if !ops.is_file(&path) {
+        return Ok(Vec::new());
+    }
+    parse_manifest(&ops.read_to_string(&path)?).map_err(|e| SlugpmError::Other(format!("in {}: {e}", path.display())))
+}
+
+/// Appends `entry` to `arch_root`'s manifest.
+pub fn record_entry(arch_root: &Path, entry: &ManifestEntry, ops: &dyn FileOps) -> Result<()> {
+    let mut line = serde_json::to_string(entry).map_err(|e| SlugpmError::Other(e.to_string()))?;
+    line.push('\n');
+    ops.append_locked(&manifest_path(arch_root), line.as_bytes()).map(drop)
+}
+
+/// For each `path` in `arch_root`'s manifest its latest entry, oldest first, including those
+/// for items that were removed.
+fn latest_entries(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<ManifestEntry>> {
+    let mut latest: Vec<ManifestEntry> = Vec::new();
+    for entry in read_manifest(arch_root, ops)? {
+        latest.retain(|e| e.path != entry.path);
+        latest.push(entry);
+    }
+    Ok(latest)
+}
+
+/// What should be in `arch_root` according to its manifest, oldest first: for each `path`, its
+/// latest entry, unless the item was removed since. Items that have gone missing are included.
+pub fn archived_entries(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<ManifestEntry>> {
+    let mut entries = latest_entries(arch_root, ops)?;
+    entries.retain(|e| e.removed.is_none());
+    Ok(entries)
+}
+
+/// What is still in `arch_root` according to its manifest, oldest first: for each `path`, its
+/// latest entry, if something is still there.
+pub fn list_archive(arch_root: &Path, ops: &dyn FileOps) -> Result<Vec<ManifestEntry>> {
+    let mut listed = archived_entries(arch_root, ops)?;
+    listed.retain(|e| ops.exists(&arch_root.join(&e.path)));
+    Ok(listed)
+}
+
+/// Records that the items recorded at or under `path` in `arch_root` have left the archive
+/// (`removed`) or come back to it, by appending a copy of each one's latest entry with
+/// `removed` set or cleared. Items already in that state, or never recorded, are left alone.
+pub fn record_removed(arch_root: &Path, path: &Path, removed: bool, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    let relative = path.strip_prefix(arch_root).unwrap_or(path);
+    let now = cfg.clock.now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    for mut entry in latest_entries(arch_root, ops)? {
+        if entry.path.starts_with(relative) && entry.removed.is_some() != removed {
+            entry.removed = removed.then(|| now.clone());
+            record_entry(arch_root, &entry, ops)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the archived item `archived` came from, by the manifest of the nearest enclosing
+/// directory named `archive_dir_name`; `None` if no manifest records it.
+pub fn original_path(archived: &Path, archive_dir_name: &str, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    let Some(arch_root) = crate::enclosing_archive_dir_pure(archived.parent().unwrap_or(archived), archive_dir_name) else {
+        return Ok(None);
+    };
+    let entries = read_manifest(arch_root, ops)?;
+    Ok(entries.into_iter().rev().find(|e| arch_root.join(&e.path) == archived).map(|e| e.from))
+}
+
+/// The item in `arch_root` that `name` refers to by its manifest: its path there (such as
+/// `2025/09/notes.md`), its name in the archive (`beta.tar.zst`) or its original name (`beta`).
+/// Fails if `name` fits more than one item.
+pub fn find_archived(arch_root: &Path, name: &str, ops: &dyn FileOps) -> Result<Option<PathBuf>> {
+    let matches: Vec<PathBuf> = list_archive(arch_root, ops)?.into_iter()
+        .filter(|e| e.path == Path::new(name) || e.path.file_name().is_some_and(|n| n == name) || e.from.file_name().is_some_and(|n| n == name))
+        .map(|e| arch_root.join(e.path))
+        .collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [found] => Ok(Some(found.clone())),
+        _ => {
+            let list: Vec<String> = matches.iter().map(|p| p.display().to_string()).collect();
+            Err(SlugpmError::InvalidName(format!("{name:?} matches several archived items: {}", list.join(", "))))
+        }
+    }
+}
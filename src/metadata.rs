@@ -0,0 +1,170 @@
+//! The `project.md` that `create` writes into each project, with the project's details as
+//! frontmatter:
+//!
+//! ```text
+//! ---
+//! title: My Project
+//! slug: my-project
+//! created: 2025-09-13
+//! status: active
+//! ---
+//! ```
+//!
+//! Other frontmatter keys and the Markdown body below it belong to the user and are kept as is
+//! when the metadata is updated.
+
+use std::path::{Path, PathBuf};
+use crate::{normalize_line_endings, FileOps, Result, SlugpmError};
+
+/// Name of the metadata file in a project directory.
+pub const METADATA_FILE: &str = "project.md";
+
+/// Status of a newly created project.
+pub const DEFAULT_STATUS: &str = "active";
+
+/// A project's metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProjectMeta {
+    /// The title the project was created from.
+    pub title: String,
+    pub slug: String,
+    /// Creation date, `YYYY-MM-DD`.
+    pub created: String,
+    pub status: String,
+    /// Other frontmatter keys, in order.
+    pub extra: Vec<(String, String)>,
+    /// Everything after the frontmatter.
+    pub body: String,
+}
+
+impl ProjectMeta {
+    /// The value of the extra key `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.extra.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the extra key `key`, adding it at the end if it's new.
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        match self.extra.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.into(),
+            None => self.extra.push((key.to_string(), value.into())),
+        }
+    }
+
+    /// Removes the extra key `key`.
+    pub fn remove(&mut self, key: &str) {
+        self.extra.retain(|(k, _)| k != key);
+    }
+
+    /// Earlier names of the project, oldest first (the comma-separated `aliases` key).
+    pub fn aliases(&self) -> Vec<&str> {
+        self.get("aliases").map(|a| a.split(',').map(str::trim).filter(|a| !a.is_empty()).collect()).unwrap_or_default()
+    }
+
+    /// The project's tags (the comma-separated `tags` key), in the order they were added.
+    pub fn tags(&self) -> Vec<&str> {
+        self.get("tags").map(|t| t.split(',').map(str::trim).filter(|t| !t.is_empty()).collect()).unwrap_or_default()
+    }
+
+    /// Replaces the `tags` key, removing it when `tags` is empty.
+    pub fn set_tags(&mut self, tags: &[impl AsRef<str>]) {
+        if tags.is_empty() {
+            self.remove("tags");
+        } else {
+            self.set("tags", tags.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(", "));
+        }
+    }
+
+    /// Replaces the `aliases` key, removing it when `aliases` is empty.
+    pub fn set_aliases(&mut self, aliases: &[impl AsRef<str>]) {
+        if aliases.is_empty() {
+            self.remove("aliases");
+        } else {
+            self.set("aliases", aliases.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(", "));
+        }
+    }
+}
+
+/// `<dir>/project.md`.
+pub fn metadata_path(dir: &Path) -> PathBuf {
+    dir.join(METADATA_FILE)
+}
+
+/// Parses a metadata file: `key: value` lines between `---` lines at the top.
+///
+/// Text without frontmatter is all body.
+pub fn parse_metadata(text: &str) -> Result<ProjectMeta> {
+    let mut meta = ProjectMeta::default();
+    let Some(rest) = text.strip_prefix("---\n") else {
+        meta.body = text.to_string();
+        return Ok(meta);
+    };
+    let mut front = Vec::new();
+    let mut body = None;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            body = Some(&rest[offset..]);
+            break;
+        }
+        front.push(line);
+    }
+    let body = body.ok_or_else(|| SlugpmError::Other("metadata frontmatter has no closing `---`".into()))?;
+    for line in front.iter().map(|l| l.trim_end()).filter(|l| !l.is_empty()) {
+        let (key, value) = line.split_once(':')
+            .ok_or_else(|| SlugpmError::Other(format!("metadata line {line:?} is not `key: value`")))?;
+        let value = value.trim().to_string();
+        match key.trim() {
+            "title" => meta.title = value,
+            "slug" => meta.slug = value,
+            "created" => meta.created = value,
+            "status" => meta.status = value,
+            key => meta.extra.push((key.to_string(), value)),
+        }
+    }
+    meta.body = body.to_string();
+    Ok(meta)
+}
+
+/// The metadata file's text.
+pub fn render_metadata(meta: &ProjectMeta) -> String {
+    // Values are single lines; a title with line breaks is joined up.
+    let one_line = |v: &str| v.split(['\n', '\r']).map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    let mut text = String::from("---\n");
+    for (key, value) in [("title", &meta.title), ("slug", &meta.slug), ("created", &meta.created), ("status", &meta.status)] {
+        text += &format!("{key}: {}\n", one_line(value));
+    }
+    for (key, value) in &meta.extra {
+        text += &format!("{key}: {}\n", one_line(value));
+    }
+    text += "---\n";
+    text += &meta.body;
+    text
+}
+
+/// The metadata of the project in `dir`, or `None` if it has no metadata file.
+pub fn read_metadata(dir: &Path, ops: &dyn FileOps) -> Result<Option<ProjectMeta>> {
+    let path = metadata_path(dir);
+    if !ops.is_file(&path) {
+        return Ok(None);
+    }
+    let text = normalize_line_endings(&String::from_utf8_lossy(&ops.read(&path)?));
+    parse_metadata(&text)
+        .map(Some)
+        .map_err(|e| SlugpmError::Other(format!("in {}: {e}", path.display())))
+}
+
+/// Writes `meta` to the project in `dir`.
+pub fn write_metadata(dir: &Path, meta: &ProjectMeta, ops: &dyn FileOps) -> Result<()> {
+    ops.write(&metadata_path(dir), render_metadata(meta).as_bytes())
+}
+
+/// Applies `change` to the metadata of the project in `dir` (starting from empty metadata if it
+/// has none) and writes the result back, returning it.
+pub fn update_metadata(dir: &Path, ops: &dyn FileOps, change: impl FnOnce(&mut ProjectMeta)) -> Result<ProjectMeta> {
+    let mut meta = read_metadata(dir, ops)?.unwrap_or_default();
+    change(&mut meta);
+    write_metadata(dir, &meta, ops)?;
+    Ok(meta)
+}
@@ -0,0 +1,37 @@
+//! The one place commands print results: plain text, or with `--json` one JSON object per line.
+
+use std::{fmt::Display, io::Write};
+use anyhow::Result;
+use serde_json::Value;
+
+/// Prints each result as `text`, or as `record` in JSON mode.
+pub(crate) struct Printer<'a> {
+    out: &'a mut dyn Write,
+    json: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(out: &'a mut dyn Write, json: bool) -> Self {
+        Printer { out, json }
+    }
+
+    pub(crate) fn json(&self) -> bool {
+        self.json
+    }
+
+    /// Prints one result. `record` is only built in JSON mode.
+    pub(crate) fn emit(&mut self, text: impl Display, record: impl FnOnce() -> Value) -> Result<()> {
+        if self.json {
+            serde_json::to_writer(&mut *self.out, &record())?;
+            writeln!(self.out)?;
+        } else {
+            writeln!(self.out, "{text}")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! The state behind `slugpm pick`: narrowing the projects as a query is typed, moving the
+//! selection, and carrying out what was chosen. The terminal UI (the `tui` feature) only draws
+//! a [`Picker`] and feeds it keys, so everything here works and is tested without a terminal.
+
+use std::path::PathBuf;
+use crate::{archive_target, recent, fuzzy_rank, list_projects, rename_project, restore_target, Config, FileOps, ProjectEntry, ProjectSort, Result, SlugpmError};
+
+/// What to do with the picked project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PickAction {
+    /// Print its path.
+    Open,
+    Archive,
+    Restore,
+    /// Rename it to the given title.
+    Rename(String),
+}
+
+impl PickAction {
+    /// The action's name in output: `open`, `archive`, `restore` or `rename`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PickAction::Open => "open",
+            PickAction::Archive => "archive",
+            PickAction::Restore => "restore",
+            PickAction::Rename(_) => "rename",
+        }
+    }
+}
+
+/// The projects on offer, the query narrowing them, and the selection.
+#[derive(Clone, Debug, Default)]
+pub struct Picker {
+    projects: Vec<ProjectEntry>,
+    query: String,
+    selected: usize,
+}
+
+impl Picker {
+    /// Offers `projects` in the given order while the query is empty.
+    pub fn new(projects: Vec<ProjectEntry>) -> Self {
+        Picker { projects, ..Picker::default() }
+    }
+
+    /// Offers every project [`list_projects`] finds, archived ones included: the recently used
+    /// ones first (see [`recent`]), then the rest most recently modified first.
+    pub fn load(cfg: &Config, ops: &dyn FileOps) -> Result<Self> {
+        let mut projects = list_projects(true, ProjectSort::Modified, cfg, ops)?;
+        projects.reverse();
+        let recent = recent::recent_projects(cfg, ops)?;
+        // Stable, so the rest keep their order.
+        projects.sort_by_key(|p| recent.iter().position(|r| r.path == p.path).unwrap_or(usize::MAX));
+        Ok(Picker::new(projects))
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The projects matching the query, best match first (see [`fuzzy_rank`]); all of them when it's empty.
+    pub fn visible(&self) -> Vec<&ProjectEntry> {
+        if self.query.trim().is_empty() {
+            return self.projects.iter().collect();
+        }
+        // Match by identity rather than slug, so an active and an archived project with the same slug both show.
+        fuzzy_rank(&self.query, self.projects.iter().map(|p| p.slug.as_str()))
+            .into_iter()
+            .filter_map(|slug| self.projects.iter().find(|p| std::ptr::eq(p.slug.as_str(), slug)))
+            .collect()
+    }
+
+    /// Index of the selection in [`Picker::visible`].
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&ProjectEntry> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// Types `c` into the query, selecting the new best match.
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    /// Deletes the query's last character.
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the selection `delta` rows, stopping at either end.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+}
+
+/// Carries out `action` on `project`, returning the project's path afterwards.
+pub fn apply_pick(project: &ProjectEntry, action: &PickAction, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    match action {
+        PickAction::Open => {
+            recent::record("open", &project.path, cfg, ops);
+            Ok(project.path.clone())
+        }
+        PickAction::Archive if project.archived => Err(SlugpmError::AlreadyArchived(project.path.clone())),
+        PickAction::Archive => Ok(archive_target(&project.path, cfg, ops)?.dest),
+        PickAction::Restore if !project.archived => Err(SlugpmError::Other(format!("{} is not archived", project.slug))),
+        PickAction::Restore => restore_target(&project.path, None, cfg, ops),
+        PickAction::Rename(_) if project.archived => Err(SlugpmError::Other(format!("restore {} before renaming it", project.slug))),
+        PickAction::Rename(title) => rename_project(&project.slug, title, false, cfg, ops),
+    }
+}
@@ -0,0 +1,32 @@
+//! The progress bar for moves that fall back to copying across file systems, drawn on stderr
+//! (and only when it is a terminal) so stdout keeps to paths.
+
+use std::sync::Mutex;
+use indicatif::{ProgressBar, ProgressStyle};
+use crate::copy::{CopyProgress, Progress};
+
+const TEMPLATE: &str = "{spinner} copying {msg} {wide_bar} {bytes}/{total_bytes} ({eta})";
+
+/// Shows each copy's bar from its first update until it finishes.
+#[derive(Debug, Default)]
+pub(crate) struct BarProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl Progress for BarProgress {
+    fn update(&self, progress: &CopyProgress) {
+        let mut bar = self.bar.lock().unwrap_or_else(|e| e.into_inner());
+        let bar = bar.get_or_insert_with(|| {
+            let style = ProgressStyle::with_template(TEMPLATE).unwrap_or_else(|_| ProgressStyle::default_bar());
+            ProgressBar::new(progress.total_bytes).with_style(style)
+        });
+        bar.set_position(progress.bytes);
+        bar.set_message(format!("{}/{} files", progress.files, progress.total_files));
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            bar.finish_and_clear();
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! `slugpm prune`: deleting, or packing with `--compress`, archived items that haven't been
+//! modified for a while.
+//!
+//! An item is an entry directly in an archive directory, or in a dated archive's
+//! `YYYY/YYYY-MM-DD/` folders. Dotfiles such as `.index.json` and `.slugpm-policy` are never
+//! items, and a file's `.sha256` sidecar goes with it.
+
+use std::{path::{Path, PathBuf}, sync::LazyLock, time::{Duration, SystemTime}};
+use chrono::{Local, TimeZone};
+use regex::Regex;
+use crate::{compress, copy, index, integrity, manifest::{self, ARCHIVE_NOTES_FILE}, Config, FileOps, Result, SlugpmError};
+
+/// An item [`prune`] removed or packed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pruned {
+    pub path: PathBuf,
+    /// The `.tar.zst` it was packed into, when compressing.
+    pub dest: Option<PathBuf>,
+    /// Bytes the item took up before pruning.
+    pub size: u64,
+}
+
+/// Parses an age such as `90d`, `2w` or `12h`; a bare number is days.
+pub fn parse_age(text: &str) -> Result<Duration> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => text.split_at(i),
+        None => (text, "d"),
+    };
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        _ => return Err(SlugpmError::Other(format!("age {text:?} must be a number of hours, days or weeks, like `90d`"))),
+    };
+    let number: u64 = number.parse()
+        .map_err(|_| SlugpmError::Other(format!("age {text:?} must be a number of hours, days or weeks, like `90d`")))?;
+    Ok(Duration::from_secs(number * hours * 3600))
+}
+
+/// The entries last modified before `cutoff`, in their original order.
+pub fn older_than(entries: &[(PathBuf, SystemTime)], cutoff: SystemTime) -> Vec<PathBuf> {
+    entries.iter().filter(|(_, modified)| *modified < cutoff).map(|(path, _)| path.clone()).collect()
+}
+
+/// The items in `arch_dir` with their modification times; empty if it doesn't exist.
+pub fn archive_entries(arch_dir: &Path, ops: &dyn FileOps) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut entries = Vec::new();
+    if ops.is_dir(arch_dir) {
+        collect_items(arch_dir, 0, ops, &mut entries)?;
+    }
+    Ok(entries)
+}
+
+/// Names of a dated archive's folders, by depth: `YYYY`, then `YYYY-MM-DD`.
+static DATED_DIRS: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [r"^\d{4}$", r"^\d{4}-\d{2}-\d{2}$"].map(|pattern| Regex::new(pattern).expect("dated folder patterns compile"))
+});
+
+fn collect_items(dir: &Path, depth: usize, ops: &dyn FileOps, out: &mut Vec<(PathBuf, SystemTime)>) -> Result<()> {
+    let dated = &*DATED_DIRS;
+    for entry in ops.read_dir(dir)? {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if name.starts_with('.') || (depth == 0 && name == ARCHIVE_NOTES_FILE) || (name.ends_with(".sha256") && ops.exists(&entry.with_file_name(name.trim_end_matches(".sha256")))) {
+            continue;
+        }
+        if depth < dated.len() && ops.is_dir(&entry) && !ops.is_symlink(&entry) && dated[depth].is_match(&name) {
+            collect_items(&entry, depth + 1, ops, out)?;
+        } else {
+            out.push((entry.clone(), ops.modified(&entry)?));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the items in `arch_dir` not modified within `age` of the config clock's now, or with
+/// `compress` packs the directories among them into `<name>.tar.zst` (files are left alone).
+/// Dated folders left empty are removed too. Every `.tar.zst` name is checked before anything is
+/// removed, so a collision leaves the archive as it was.
+pub fn prune(arch_dir: &Path, age: Duration, compress: bool, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<Pruned>> {
+    let now = Local.from_local_datetime(&cfg.clock.now()).earliest().map(SystemTime::from).unwrap_or_else(SystemTime::now);
+    let cutoff = now.checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut items = Vec::new();
+    for path in older_than(&archive_entries(arch_dir, ops)?, cutoff) {
+        if !compress {
+            items.push((path, None));
+        } else if ops.is_dir(&path) && !ops.is_symlink(&path) {
+            let dest = path.with_file_name(compress::compressed_name(&path.file_name().unwrap_or_default().to_string_lossy()));
+            if ops.exists(&dest) {
+                return Err(SlugpmError::Collision(dest));
+            }
+            items.push((path, Some(dest)));
+        }
+    }
+    let mut pruned = Vec::new();
+    for (path, dest) in items {
+        let size = index::tree_size(&path, ops)?;
+        if let Some(dest) = &dest {
+            compress::pack_dir(&path, dest, ops)?;
+        }
+        copy::remove_tree(&path, ops)?;
+        let sidecar = integrity::sidecar_path(&path);
+        if ops.is_file(&sidecar) {
+            ops.remove_file(&sidecar)?;
+        }
+        if cfg.manifest {
+            manifest::record_removed(arch_dir, &path, true, cfg, ops)?;
+        }
+        remove_empty_dated_dirs(path.parent().unwrap_or(arch_dir), arch_dir, ops)?;
+        pruned.push(Pruned { path, dest, size });
+    }
+    Ok(pruned)
+}
+
+/// Removes `dir` and then its parents while they are empty, stopping at `arch_dir`.
+fn remove_empty_dated_dirs(dir: &Path, arch_dir: &Path, ops: &dyn FileOps) -> Result<()> {
+    let mut dir = dir.to_path_buf();
+    while dir != arch_dir && dir.starts_with(arch_dir) && ops.is_dir(&dir) && ops.read_dir(&dir)?.is_empty() {
+        ops.remove_dir(&dir)?;
+        dir = match dir.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => break,
+        };
+    }
+    Ok(())
+}
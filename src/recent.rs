@@ -0,0 +1,103 @@
+//! Recently used projects: `<project_root>/.slugpm-recent`, one JSON object per line for each
+//! time slugpm created, opened, archived or wrote a note in a project, most recent last.
+//! `slugpm recent` lists them, and the picker offers them first.
+//!
+//! Nothing is recorded unless `Config::track_recent` is set (the CLI sets it outside
+//! `--dry-run`), or while the project root doesn't exist. Recording is best effort: a project
+//! root that can't be written doesn't fail the command that touched the project.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::{Config, FileOps, Result, SlugpmError};
+
+/// Name of the state file in the project root.
+pub const RECENT_FILE: &str = ".slugpm-recent";
+
+/// Once the file is this long, it is rewritten with only the latest [`RECENT_KEEP`] projects.
+const COMPACT_AT_BYTES: u64 = 32 * 1024;
+
+/// Projects kept when compacting.
+const RECENT_KEEP: usize = 100;
+
+/// One use of a project.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentEntry {
+    /// Local time, `YYYY-MM-DDTHH:MM:SS`.
+    pub at: String,
+    /// What was done: `create`, `open`, `archive` or `note`.
+    pub action: String,
+    pub slug: String,
+    /// The project directory afterwards.
+    pub path: PathBuf,
+}
+
+/// `<project_root>/.slugpm-recent`.
+pub fn recent_path(cfg: &Config) -> PathBuf {
+    cfg.project_root.join(RECENT_FILE)
+}
+
+/// Records that `action` was done to the project directory `path`.
+pub fn record(action: &str, path: &Path, cfg: &Config, ops: &dyn FileOps) {
+    if !cfg.track_recent || !ops.is_dir(&cfg.project_root) {
+        return;
+    }
+    // Best effort; see the module docs.
+    let _ = try_record(action, path, cfg, ops);
+}
+
+fn try_record(action: &str, path: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    let entry = RecentEntry {
+        at: cfg.clock.now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+        action: action.to_string(),
+        slug: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        path: path.to_path_buf(),
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| SlugpmError::Other(e.to_string()))?;
+    line.push('\n');
+    let file = recent_path(cfg);
+    if ops.append_locked(&file, line.as_bytes())? < COMPACT_AT_BYTES {
+        return Ok(());
+    }
+    let mut kept = latest_per_project(parse_recent(&ops.read_to_string(&file)?)?);
+    kept.truncate(RECENT_KEEP);
+    let text: String = kept.iter().rev()
+        .map(|e| serde_json::to_string(e).map(|l| l + "\n"))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| SlugpmError::Other(e.to_string()))?;
+    ops.write(&file, text.as_bytes())
+}
+
+/// Whether `path` is directly in the project root, compared by real path.
+pub fn in_project_root(path: &Path, cfg: &Config, ops: &dyn FileOps) -> bool {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    matches!((ops.canonicalize(parent), ops.canonicalize(&cfg.project_root)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Parses the state file's text, oldest first; blank lines are skipped.
+pub fn parse_recent(text: &str) -> Result<Vec<RecentEntry>> {
+    text.lines().filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| SlugpmError::Other(format!("parsing {RECENT_FILE}: {e}"))))
+        .collect()
+}
+
+/// The last entry for each slug, most recent first.
+pub fn latest_per_project(entries: Vec<RecentEntry>) -> Vec<RecentEntry> {
+    let mut latest: Vec<RecentEntry> = Vec::new();
+    for entry in entries.into_iter().rev() {
+        if !latest.iter().any(|e| e.slug == entry.slug) {
+            latest.push(entry);
+        }
+    }
+    latest
+}
+
+/// The recently used projects that still exist where they were last seen, most recent first.
+pub fn recent_projects(cfg: &Config, ops: &dyn FileOps) -> Result<Vec<RecentEntry>> {
+    let file = recent_path(cfg);
+    if !ops.is_file(&file) {
+        return Ok(Vec::new());
+    }
+    let mut entries = latest_per_project(parse_recent(&ops.read_to_string(&file)?)?);
+    entries.retain(|e| ops.is_dir(&e.path));
+    Ok(entries)
+}
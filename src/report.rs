@@ -0,0 +1,65 @@
+//! `slugpm report`: the projects as a Markdown or CSV table for a weekly review, one row per
+//! project with its title, slug, creation date, status, tags and last modification.
+
+use chrono::{DateTime, Local};
+use crate::ProjectEntry;
+
+/// The report's table format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// A Markdown (GitHub-flavored) table
+    #[default]
+    Md,
+    /// Comma-separated values with a header row (RFC 4180 quoting)
+    Csv,
+}
+
+/// The column headers.
+pub const REPORT_COLUMNS: [&str; 6] = ["Title", "Slug", "Created", "Status", "Tags", "Modified"];
+
+/// `entry`'s cells, in [`REPORT_COLUMNS`] order. A project without a title in its metadata
+/// gets an empty one; the modification time is local, `YYYY-MM-DD HH:MM`.
+pub fn report_row(entry: &ProjectEntry) -> [String; 6] {
+    [
+        entry.title.clone().unwrap_or_default(),
+        entry.slug.clone(),
+        entry.created.clone().unwrap_or_default(),
+        entry.status.to_string(),
+        entry.tags.join(", "),
+        DateTime::<Local>::from(entry.modified).format("%Y-%m-%d %H:%M").to_string(),
+    ]
+}
+
+/// The table of `entries`, in their order, ending in a newline.
+pub fn render_report(entries: &[ProjectEntry], format: ReportFormat) -> String {
+    let rows = entries.iter().map(report_row);
+    match format {
+        ReportFormat::Md => {
+            let line = |cells: &[String]| format!("| {} |\n", cells.iter().map(|c| markdown_cell(c)).collect::<Vec<_>>().join(" | "));
+            let mut text = line(&REPORT_COLUMNS.map(String::from));
+            text += &format!("|{}\n", "---|".repeat(REPORT_COLUMNS.len()));
+            rows.for_each(|row| text += &line(&row));
+            text
+        }
+        ReportFormat::Csv => {
+            let line = |cells: &[String]| format!("{}\r\n", cells.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+            let mut text = line(&REPORT_COLUMNS.map(String::from));
+            rows.for_each(|row| text += &line(&row));
+            text
+        }
+    }
+}
+
+/// `text` made safe inside a Markdown table cell: pipes escaped, line breaks as spaces.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// `text` as a CSV field, quoted when it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
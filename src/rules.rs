@@ -0,0 +1,112 @@
+//! Filing rules (`slugpm file`): the config file's `[rules]` table maps file-name patterns to
+//! the project, and optionally subdirectory, that matching files belong in.
+//!
+//! ```toml
+//! [rules]
+//! "*.pdf" = "reading"
+//! "invoice-*" = "finances/invoices"
+//! "*" = "inbox"
+//! ```
+//!
+//! Patterns match the whole file name, with `*` for any run of characters and `?` for any one
+//! character, case-sensitively. Rules are tried in the order they are written and the first
+//! match wins. A rule that can never match because an earlier pattern matches everything it
+//! does (`"*.pdf"` after `"*"`) is refused as a conflict when the config is loaded.
+
+use std::{fmt, path::{Path, PathBuf}};
+use regex::Regex;
+use serde::{de::{MapAccess, Visitor}, Deserialize, Deserializer};
+use crate::{move_into_project, Config, FileOps, Result, SlugpmError};
+
+/// The `[rules]` table as written: `(pattern, destination)` pairs in file order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleTable(pub Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for RuleTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct InOrder;
+        impl<'de> Visitor<'de> for InOrder {
+            type Value = RuleTable;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a table of file-name patterns to project slugs")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<RuleTable, A::Error> {
+                let mut rules = Vec::new();
+                while let Some(rule) = map.next_entry()? {
+                    rules.push(rule);
+                }
+                Ok(RuleTable(rules))
+            }
+        }
+        deserializer.deserialize_map(InOrder)
+    }
+}
+
+/// A checked rule: files named like `pattern` go to `dest`, `<project>[/<subdir>...]`.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub pattern: String,
+    pub dest: String,
+    regex: Regex,
+}
+
+impl Rule {
+    /// Whether the file name `name` matches the rule's pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+/// A regex matching whole names against `pattern`, where `*` is any run of characters and
+/// `?` any one character.
+pub fn wildcard_regex(pattern: &str) -> Regex {
+    let body: String = pattern.chars().map(|c| match c {
+        '*' => ".*".to_string(),
+        '?' => ".".to_string(),
+        c => regex::escape(c.encode_utf8(&mut [0; 4])),
+    }).collect();
+    Regex::new(&format!("^{body}$")).expect("escaped wildcard pattern compiles")
+}
+
+/// Checks and compiles `table` in order. Empty patterns or destinations, patterns with a path
+/// separator and rules shadowed by an earlier one ([`shadows`]) are errors.
+pub fn parse_rules(table: &RuleTable) -> Result<Vec<Rule>> {
+    let mut rules: Vec<Rule> = Vec::new();
+    for (pattern, dest) in &table.0 {
+        let invalid = |why: String| SlugpmError::InvalidName(format!("rule {pattern:?} = {dest:?}: {why}"));
+        if pattern.is_empty() || pattern.contains(['/', '\\']) {
+            return Err(invalid("the pattern must be a non-empty file name pattern".into()));
+        }
+        if dest.trim_matches('/').is_empty() {
+            return Err(invalid("the destination must be a project slug".into()));
+        }
+        if let Some(earlier) = rules.iter().find(|earlier| shadows(&earlier.pattern, pattern)) {
+            return Err(invalid(format!("it can never match, {:?} comes first and matches everything it does", earlier.pattern)));
+        }
+        rules.push(Rule { pattern: pattern.clone(), dest: dest.clone(), regex: wildcard_regex(pattern) });
+    }
+    Ok(rules)
+}
+
+/// Whether every name `later` matches is matched by `earlier` too. Conservative: it can
+/// only tell when `earlier` has no `?`, and then compares `later` as written, whose wildcards
+/// `earlier`'s `*` has to cover.
+pub fn shadows(earlier: &str, later: &str) -> bool {
+    !earlier.contains('?') && wildcard_regex(earlier).is_match(later)
+}
+
+/// The first of `rules` matching `path`'s file name.
+pub fn rule_for<'r>(rules: &'r [Rule], path: &Path) -> Option<&'r Rule> {
+    let name = path.file_name()?.to_string_lossy();
+    rules.iter().find(|rule| rule.matches(&name))
+}
+
+/// Moves `path` (or copies it, with `copy`) into the destination of the first rule in
+/// `cfg.rules` that matches it, as [`move_into_project`] would. Returns the rule and where the
+/// file ended up; a file no rule matches is [`SlugpmError::NotFound`].
+pub fn file_by_rules(path: &Path, copy: bool, cfg: &Config, ops: &dyn FileOps) -> Result<(Rule, PathBuf)> {
+    let rule = rule_for(&cfg.rules, path)
+        .ok_or_else(|| SlugpmError::NotFound(format!("no rule matches {}", path.display())))?;
+    let dest = move_into_project(path, &rule.dest, copy, cfg, ops)?;
+    Ok((rule.clone(), dest))
+}
@@ -0,0 +1,54 @@
+//! Keeping names from the command line inside the tree they are meant for.
+//!
+//! A slug, title or generated name becomes one path segment under a root: the project root for
+//! `create` and `rename`, the archive directory for `archive`. [`check_segment`] refuses
+//! anything that isn't a single plain name (separators, `.` and `..`, absolute paths, NUL), and
+//! [`ensure_under`] checks the final path lexically before anything is created or moved.
+//! Both fail with [`SlugpmError::InvalidName`].
+
+use std::{ffi::OsStr, path::{Component, Path, PathBuf}};
+use crate::{normalize_lexically, Result, SlugpmError};
+
+/// Fails unless `name` is a single plain path segment; `what` names it in the error.
+pub fn check_segment(name: &str, what: &str) -> Result<()> {
+    let problem = if name.is_empty() {
+        "is empty"
+    } else if name == "." || name == ".." {
+        "is not a name"
+    } else if name.contains(['/', '\\']) {
+        "must not contain path separators"
+    } else if name.contains('\0') {
+        "must not contain NUL"
+    } else if !matches!(Path::new(name).components().collect::<Vec<_>>()[..], [Component::Normal(_)]) {
+        "is not a single path segment"
+    } else {
+        return Ok(());
+    };
+    Err(SlugpmError::InvalidName(format!("{what} {name:?} {problem}")))
+}
+
+/// Fails unless `path` is strictly inside `root`, comparing the two after
+/// [`normalize_lexically`], so `..` can't climb out. Symlinks are not resolved.
+pub fn ensure_under(path: &Path, root: &Path) -> Result<()> {
+    let (path_n, root_n) = (normalize_lexically(path), normalize_lexically(root));
+    if path_n != root_n && path_n.starts_with(&root_n) {
+        return Ok(());
+    }
+    Err(SlugpmError::InvalidName(format!("{} is outside {}", path.display(), root.display())))
+}
+
+/// `root/name`, after checking `name` with [`check_segment`] and the result with [`ensure_under`].
+pub fn join_segment(root: &Path, name: &str, what: &str) -> Result<PathBuf> {
+    check_segment(name, what)?;
+    let path = root.join(name);
+    ensure_under(&path, root)?;
+    Ok(path)
+}
+
+/// The final component of `path` as a plain name, refusing paths that end in `..` or are a root.
+pub fn file_segment(path: &Path) -> Result<&OsStr> {
+    match path.components().next_back() {
+        Some(Component::Normal(name)) => Ok(name),
+        _ => Err(SlugpmError::InvalidName(format!("{} does not name a file or directory", path.display()))),
+    }
+}
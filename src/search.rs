@@ -0,0 +1,140 @@
+//! `slugpm search`: finding lines in the Markdown and text files of the projects.
+//!
+//! Files are searched one at a time and each match is handed to a callback as it's found, so
+//! output starts before the walk ends. Files larger than [`SearchOptions::max_file_size`] and
+//! ones that look binary (a NUL byte in their first [`BINARY_SNIFF_LEN`] bytes) are skipped,
+//! as are dotfiles, dot directories such as `.git`, and symlinks.
+
+use std::path::{Path, PathBuf};
+use regex::{Regex, RegexBuilder};
+use crate::{list_projects, Config, FileOps, ProjectSort, Result, SlugpmError};
+
+/// Extensions of the files searched, compared case-insensitively.
+pub const SEARCH_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "text", "org"];
+
+/// Files larger than this are skipped unless [`SearchOptions::max_file_size`] says otherwise.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// How many leading bytes are checked for a NUL when deciding a file is binary.
+pub const BINARY_SNIFF_LEN: usize = 8192;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Treat the query as a regular expression rather than literal text.
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Search the archived projects too.
+    pub archived: bool,
+    pub max_file_size: u64,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions { regex: false, ignore_case: false, archived: false, max_file_size: DEFAULT_MAX_FILE_SIZE }
+    }
+}
+
+/// A matching line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    /// Slug of the project holding the file.
+    pub slug: String,
+    pub path: PathBuf,
+    /// 1-based line number.
+    pub line: usize,
+    /// The line, without its line ending.
+    pub text: String,
+}
+
+/// What a search looked at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub files: usize,
+    pub matches: usize,
+    /// Files passed over for being too large or binary.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// The pattern `query` stands for under `opts`.
+pub fn query_pattern(query: &str, opts: &SearchOptions) -> Result<Regex> {
+    if query.is_empty() {
+        return Err(SlugpmError::Other("search query must not be empty".into()));
+    }
+    let pattern = if opts.regex { query.to_string() } else { regex::escape(query) };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(opts.ignore_case)
+        .build()
+        .map_err(|e| SlugpmError::Other(format!("invalid search pattern {query:?}: {e}")))
+}
+
+/// Whether `contents` looks binary: a NUL byte among its first [`BINARY_SNIFF_LEN`] bytes.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Whether `path` has one of the [`SEARCH_EXTENSIONS`].
+pub fn is_searchable(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| SEARCH_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// Searches the projects' files for `query`, calling `found` with each match in project, then
+/// path, then line order. An error from `found` stops the search.
+pub fn search_projects(
+    query: &str,
+    opts: &SearchOptions,
+    cfg: &Config,
+    ops: &dyn FileOps,
+    found: &mut dyn FnMut(&SearchMatch) -> Result<()>,
+) -> Result<SearchStats> {
+    let pattern = query_pattern(query, opts)?;
+    let mut stats = SearchStats::default();
+    for project in list_projects(opts.archived, ProjectSort::Name, cfg, ops)? {
+        if ops.is_symlink(&project.path) {
+            continue;
+        }
+        search_dir(&project.slug, &project.path, &pattern, opts, ops, &mut stats, found)?;
+    }
+    Ok(stats)
+}
+
+fn search_dir(
+    slug: &str,
+    dir: &Path,
+    pattern: &Regex,
+    opts: &SearchOptions,
+    ops: &dyn FileOps,
+    stats: &mut SearchStats,
+    found: &mut dyn FnMut(&SearchMatch) -> Result<()>,
+) -> Result<()> {
+    let mut entries = ops.read_dir(dir)?;
+    entries.sort();
+    for entry in entries {
+        if entry.file_name().is_none_or(|n| n.to_string_lossy().starts_with('.')) || ops.is_symlink(&entry) {
+            continue;
+        }
+        if ops.is_dir(&entry) {
+            search_dir(slug, &entry, pattern, opts, ops, stats, found)?;
+            continue;
+        }
+        if !is_searchable(&entry) {
+            continue;
+        }
+        if ops.metadata(&entry)?.len > opts.max_file_size {
+            stats.skipped.push(entry);
+            continue;
+        }
+        let contents = ops.read(&entry)?;
+        if is_binary(&contents) {
+            stats.skipped.push(entry);
+            continue;
+        }
+        stats.files += 1;
+        for (i, line) in String::from_utf8_lossy(&contents).lines().enumerate() {
+            if pattern.is_match(line) {
+                stats.matches += 1;
+                found(&SearchMatch { slug: slug.to_string(), path: entry.clone(), line: i + 1, text: line.to_string() })?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `slugpm status`: how many projects are active and archived, which one was touched last, and
+//! which have sat untouched for a while.
+//!
+//! A project counts as touched when anything inside it was modified, not just the directory
+//! itself, so editing a nested file keeps it fresh.
+//!
+//! Also each project's place in its lifecycle, [`ProjectStatus`]: the `status` in its metadata
+//! (`slugpm set-status`), or `archived` once it's in the archive.
+
+use std::{fmt, path::{Path, PathBuf}, time::SystemTime};
+use chrono::{DateTime, Local, NaiveDateTime};
+use crate::{journal, list_projects, metadata, resolve_project, Config, FileOps, ProjectSort, Result, SlugpmError};
+
+/// Where a project is in its lifecycle, in order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum)]
+pub enum ProjectStatus {
+    #[default]
+    Active,
+    Paused,
+    Done,
+    /// In the archive; set by archiving rather than in metadata.
+    Archived,
+}
+
+impl ProjectStatus {
+    /// The status as written in metadata: `active`, `paused`, `done` or `archived`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProjectStatus::Active => "active",
+            ProjectStatus::Paused => "paused",
+            ProjectStatus::Done => "done",
+            ProjectStatus::Archived => "archived",
+        }
+    }
+
+    /// Parses a metadata `status` value, ignoring case and surrounding space.
+    pub fn parse(text: &str) -> Option<Self> {
+        [ProjectStatus::Active, ProjectStatus::Paused, ProjectStatus::Done, ProjectStatus::Archived]
+            .into_iter()
+            .find(|status| text.trim().eq_ignore_ascii_case(status.as_str()))
+    }
+
+    /// The status of a project with metadata `meta`: `Archived` for an archived one, else its
+    /// metadata's status; missing or unknown statuses count as `Active`.
+    pub fn of(meta: Option<&metadata::ProjectMeta>, archived: bool) -> Self {
+        if archived {
+            return ProjectStatus::Archived;
+        }
+        meta.and_then(|meta| ProjectStatus::parse(&meta.status)).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ProjectStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Records `status` in the metadata of the active project `slug`, returning the status it had.
+/// Archiving is what makes a project `archived`, so that status is refused here.
+pub fn set_project_status(slug: &str, status: ProjectStatus, cfg: &Config, ops: &dyn FileOps) -> Result<ProjectStatus> {
+    if status == ProjectStatus::Archived {
+        return Err(SlugpmError::InvalidName(format!("archive {slug} with `slugpm archive` rather than setting its status")));
+    }
+    let dir = resolve_project(slug, cfg, ops)?;
+    let meta = metadata::read_metadata(&dir, ops)?;
+    let previous = ProjectStatus::of(meta.as_ref(), false);
+    metadata::update_metadata(&dir, ops, |meta| meta.status = status.as_str().to_string())?;
+    // The value as written, so undo restores even a status slugpm doesn't know.
+    journal::record(journal::Operation::Status { dir, previous: meta.map(|meta| meta.status).unwrap_or_default() }, cfg, ops)?;
+    Ok(previous)
+}
+
+/// An active project and when it was last touched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectActivity {
+    pub slug: String,
+    pub path: PathBuf,
+    /// Local time of the newest modification in the project.
+    pub touched: NaiveDateTime,
+    /// Whole days between `touched` and the config clock's now.
+    pub idle_days: i64,
+}
+
+/// What [`project_status`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatusReport {
+    pub active: usize,
+    pub archived: usize,
+    /// The active project touched most recently.
+    pub most_recent: Option<ProjectActivity>,
+    /// Active projects untouched for at least the given number of days, least recently touched first.
+    pub stale: Vec<ProjectActivity>,
+}
+
+/// The newest modification time of `dir` and everything under it. Symlinks are not followed.
+pub fn last_touched(dir: &Path, ops: &dyn FileOps) -> Result<SystemTime> {
+    let mut newest = ops.modified(dir)?;
+    for entry in ops.read_dir(dir)? {
+        if ops.is_symlink(&entry) {
+            continue;
+        }
+        let meta = ops.metadata(&entry)?;
+        let time = if meta.is_dir { last_touched(&entry, ops)? } else { meta.modified };
+        newest = newest.max(time);
+    }
+    Ok(newest)
+}
+
+/// Summarizes the projects [`list_projects`] finds, counting those idle for `stale_days` or more as stale.
+pub fn project_status(stale_days: u32, cfg: &Config, ops: &dyn FileOps) -> Result<StatusReport> {
+    let now = cfg.clock.now();
+    let mut report = StatusReport::default();
+    let mut active = Vec::new();
+    for entry in list_projects(true, ProjectSort::Name, cfg, ops)? {
+        if entry.archived {
+            report.archived += 1;
+            continue;
+        }
+        report.active += 1;
+        let touched = DateTime::<Local>::from(last_touched(&entry.path, ops)?).naive_local();
+        active.push(ProjectActivity { idle_days: (now - touched).num_days(), slug: entry.slug, path: entry.path, touched });
+    }
+    active.sort_by(|a, b| (a.touched, &a.slug).cmp(&(b.touched, &b.slug)));
+    report.most_recent = active.last().cloned();
+    report.stale = active.into_iter().filter(|p| p.idle_days >= i64::from(stale_days)).collect();
+    Ok(report)
+}
@@ -0,0 +1,85 @@
+//! Project tags (`slugpm tag`), kept as the comma-separated `tags` key in each project's
+//! metadata:
+//!
+//! ```text
+//! ---
+//! title: My Project
+//! ...
+//! tags: work, rust
+//! ---
+//! ```
+//!
+//! Tags are lowercase; `Work` and `work` are the same tag.
+
+use std::collections::BTreeMap;
+use crate::{journal, list_projects, metadata, resolve_project, Config, FileOps, ProjectEntry, ProjectSort, Result, SlugpmError};
+
+/// `tag` trimmed and lowercased; empty tags and ones with commas or line breaks are refused.
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || tag.contains([',', '\n', '\r']) {
+        return Err(SlugpmError::InvalidName(format!("tag {tag:?} must be non-empty, without commas or line breaks")));
+    }
+    Ok(tag)
+}
+
+/// The tags of the project `slug`.
+pub fn project_tags(slug: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<String>> {
+    let dir = resolve_project(slug, cfg, ops)?;
+    Ok(metadata::read_metadata(&dir, ops)?.map(|meta| meta.tags().into_iter().map(String::from).collect()).unwrap_or_default())
+}
+
+/// Tags the project `slug` with `tag`, returning its tags afterwards. Adding a tag it already
+/// has changes nothing.
+pub fn add_tag(slug: &str, tag: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<String>> {
+    let tag = normalize_tag(tag)?;
+    change_tags(slug, cfg, ops, |tags| {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+        Ok(())
+    })
+}
+
+/// Removes `tag` from the project `slug`, returning its tags afterwards.
+pub fn remove_tag(slug: &str, tag: &str, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<String>> {
+    let tag = normalize_tag(tag)?;
+    change_tags(slug, cfg, ops, |tags| {
+        let before = tags.len();
+        tags.retain(|t| *t != tag);
+        if tags.len() == before {
+            return Err(SlugpmError::NotFound(format!("{slug} is not tagged {tag:?}")));
+        }
+        Ok(())
+    })
+}
+
+fn change_tags(slug: &str, cfg: &Config, ops: &dyn FileOps, change: impl FnOnce(&mut Vec<String>) -> Result<()>) -> Result<Vec<String>> {
+    let dir = resolve_project(slug, cfg, ops)?;
+    let previous = project_tags(slug, cfg, ops)?;
+    let mut tags = previous.clone();
+    change(&mut tags)?;
+    if tags == previous {
+        return Ok(tags);
+    }
+    metadata::update_metadata(&dir, ops, |meta| meta.set_tags(&tags))?;
+    journal::record(journal::Operation::Tag { dir, previous }, cfg, ops)?;
+    Ok(tags)
+}
+
+/// Whether `project` is tagged `tag` (compared as [`normalize_tag`] would).
+pub fn has_tag(project: &ProjectEntry, tag: &str) -> bool {
+    let tag = tag.trim().to_lowercase();
+    project.tags.contains(&tag)
+}
+
+/// Every tag on the active projects, alphabetically, with how many projects have it.
+pub fn tag_counts(cfg: &Config, ops: &dyn FileOps) -> Result<Vec<(String, usize)>> {
+    let mut counts = BTreeMap::new();
+    for project in list_projects(false, ProjectSort::Name, cfg, ops)? {
+        for tag in project.tags {
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+    }
+    Ok(counts.into_iter().collect())
+}
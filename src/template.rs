@@ -0,0 +1,87 @@
+//! Directory skeletons that `create --template <name>` copies into a new project.
+//!
+//! A template is a directory under `templates/` next to the config file, e.g.
+//! `~/.config/slugpm/templates/rust/`. `{{slug}}`, `{{title}}` and `{{date}}` are replaced in
+//! file names and in the contents of UTF-8 files; other files are copied byte for byte.
+//!
+//! `today` uses file templates instead: `templates/<name>.md`, rendered the same way, with
+//! `templates/today.md` as the default when it exists.
+
+use std::path::{Path, PathBuf};
+use crate::{config_file, safe_path, Config, FileOps, Result, SlugpmError};
+
+/// Values substituted into a template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateVars {
+    /// The new project's directory name.
+    pub slug: String,
+    pub title: String,
+    /// Today in the config's `date_format`.
+    pub date: String,
+}
+
+/// Replaces each `{{slug}}`, `{{title}}` and `{{date}}` in `text`.
+pub fn render(text: &str, vars: &TemplateVars) -> String {
+    text.replace("{{slug}}", &vars.slug)
+        .replace("{{title}}", &vars.title)
+        .replace("{{date}}", &vars.date)
+}
+
+/// `cfg.templates_dir`, else `templates/` beside the default config file.
+pub fn templates_dir(cfg: &Config) -> Option<PathBuf> {
+    cfg.templates_dir.clone()
+        .or_else(|| Some(config_file::default_config_path()?.parent()?.join("templates")))
+}
+
+/// Name of the file template `today` uses when it isn't given one.
+pub const DAILY_NOTE_TEMPLATE: &str = "today";
+
+/// The directory of the template called `name`, which must exist.
+pub fn template_path(name: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let path = existing_templates_dir(name, cfg)?.join(name);
+    if !ops.is_dir(&path) {
+        return Err(SlugpmError::NotFound(format!("no template {name:?} in {}", path.parent().unwrap().display())));
+    }
+    Ok(path)
+}
+
+/// The file template `<name>.md`, which must exist.
+pub fn file_template_path(name: &str, cfg: &Config, ops: &dyn FileOps) -> Result<PathBuf> {
+    let path = existing_templates_dir(name, cfg)?.join(format!("{name}.md"));
+    if !ops.is_file(&path) {
+        return Err(SlugpmError::NotFound(format!("no template {name:?} ({}) in {}", path.file_name().unwrap().to_string_lossy(), path.parent().unwrap().display())));
+    }
+    Ok(path)
+}
+
+/// The templates directory, after checking that `name` is a single path segment.
+fn existing_templates_dir(name: &str, cfg: &Config) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(SlugpmError::InvalidName(format!("template name {name:?} must be a single name")));
+    }
+    templates_dir(cfg).ok_or_else(|| SlugpmError::NotFound("no templates directory: cannot find the config directory".into()))
+}
+
+/// Copies the contents of `template` into the existing directory `dest`, rendering names and text,
+/// and returns the files it wrote. A rendered name that isn't a single plain segment (a title
+/// like `../x`) is [`SlugpmError::InvalidName`].
+pub fn apply_template(template: &Path, dest: &Path, vars: &TemplateVars, ops: &dyn FileOps) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for entry in ops.read_dir(template)? {
+        let name = render(&entry.file_name().unwrap().to_string_lossy(), vars);
+        let target = safe_path::join_segment(dest, &name, "template file")?;
+        if ops.is_dir(&entry) {
+            ops.create_dir_all(&target)?;
+            written.extend(apply_template(&entry, &target, vars, ops)?);
+            continue;
+        }
+        let bytes = ops.read(&entry)?;
+        let contents = match String::from_utf8(bytes) {
+            Ok(text) => render(&text, vars).into_bytes(),
+            Err(err) => err.into_bytes(),
+        };
+        ops.write(&target, &contents)?;
+        written.push(target);
+    }
+    Ok(written)
+}
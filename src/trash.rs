@@ -0,0 +1,114 @@
+//! `slugpm trash`: moving throwaway items to the desktop trash instead of an archive directory.
+//!
+//! The trash goes through the injectable [`Trash`] on `Config`, so tests can point it at an
+//! in-memory directory. [`SystemTrash`] picks the platform's trash: the Freedesktop trash
+//! (`$XDG_DATA_HOME/Trash`, with a `.trashinfo` file per item so file managers can restore it) on
+//! Linux and other Unix systems, and `~/.Trash` on macOS.
+
+use std::{fmt::Debug, iter, path::{Path, PathBuf}};
+use chrono::NaiveDateTime;
+use crate::{move_path, suffixed_path, FileOps, Result, SlugpmError};
+
+/// Where [`Trash::trash`] put an item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trashed {
+    pub dest: PathBuf,
+    /// The item's `.trashinfo` file, for trashes that keep one.
+    pub info: Option<PathBuf>,
+}
+
+pub trait Trash: Debug + Send + Sync {
+    /// Moves the absolute path `path` into the trash, recording `now` as its deletion time.
+    fn trash(&self, path: &Path, now: NaiveDateTime, ops: &dyn FileOps) -> Result<Trashed>;
+}
+
+/// A Freedesktop.org trash directory, with `files/` holding the items and `info/` their
+/// `<name>.trashinfo` records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FreedesktopTrash {
+    pub dir: PathBuf,
+}
+
+impl FreedesktopTrash {
+    /// The home trash: `$XDG_DATA_HOME/Trash`, else `~/.local/share/Trash`.
+    pub fn home() -> Option<Self> {
+        let data = std::env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()).map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+        Some(FreedesktopTrash { dir: data.join("Trash") })
+    }
+}
+
+impl Trash for FreedesktopTrash {
+    fn trash(&self, path: &Path, now: NaiveDateTime, ops: &dyn FileOps) -> Result<Trashed> {
+        let (files, info_dir) = (self.dir.join("files"), self.dir.join("info"));
+        ops.create_dir_all(&files)?;
+        ops.create_dir_all(&info_dir)?;
+        let info_for = |dest: &Path| info_dir.join(format!("{}.trashinfo", dest.file_name().unwrap_or_default().to_string_lossy()));
+        // A name is taken while either the item or its record exists.
+        let dest = free_path(&files.join(path.file_name().unwrap_or_default()), ops, |p| ops.exists(&info_for(p)));
+        let info = info_for(&dest);
+        let record = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encode_path(path), now.format("%Y-%m-%dT%H:%M:%S"));
+        // The spec writes the record first, so an item in `files/` always has one.
+        ops.write(&info, record.as_bytes())?;
+        if let Err(e) = move_path(path, &dest, ops) {
+            ops.remove_file(&info)?;
+            return Err(e);
+        }
+        Ok(Trashed { dest, info: Some(info) })
+    }
+}
+
+/// A trash that is a plain directory of items, such as macOS's `~/.Trash`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirTrash {
+    pub dir: PathBuf,
+}
+
+impl Trash for DirTrash {
+    fn trash(&self, path: &Path, _now: NaiveDateTime, ops: &dyn FileOps) -> Result<Trashed> {
+        ops.create_dir_all(&self.dir)?;
+        let dest = free_path(&self.dir.join(path.file_name().unwrap_or_default()), ops, |_| false);
+        move_path(path, &dest, ops)?;
+        Ok(Trashed { dest, info: None })
+    }
+}
+
+/// The current platform's trash for the current user.
+#[derive(Clone, Debug, Default)]
+pub struct SystemTrash;
+
+impl Trash for SystemTrash {
+    fn trash(&self, path: &Path, now: NaiveDateTime, ops: &dyn FileOps) -> Result<Trashed> {
+        if cfg!(windows) {
+            return Err(SlugpmError::Other("the Recycle Bin is not supported; use `slugpm archive` instead".into()));
+        }
+        if cfg!(target_os = "macos") {
+            let home = std::env::var_os("HOME").ok_or_else(|| SlugpmError::Other("cannot find the trash: $HOME is not set".into()))?;
+            return DirTrash { dir: Path::new(&home).join(".Trash") }.trash(path, now, ops);
+        }
+        FreedesktopTrash::home()
+            .ok_or_else(|| SlugpmError::Other("cannot find the trash: neither $XDG_DATA_HOME nor $HOME is set".into()))?
+            .trash(path, now, ops)
+    }
+}
+
+/// `path`, or the first of `<stem>-2<.ext>`, `<stem>-3<.ext>`, ... that neither exists nor is `taken`.
+fn free_path(path: &Path, ops: &dyn FileOps, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    iter::once(path.to_path_buf())
+        .chain((2..).map(|n| suffixed_path(path, &n.to_string())))
+        .find(|candidate| !ops.exists(candidate) && !taken(candidate))
+        .unwrap()
+}
+
+/// `path` percent-encoded for a `.trashinfo` `Path=` line.
+pub fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded += &format!("%{byte:02X}");
+        }
+    }
+    encoded
+}
@@ -0,0 +1,94 @@
+//! The terminal UI for `slugpm pick`, drawn on stderr so the picked path can be captured from
+//! stdout (`cd "$(slugpm pick)"`).
+
+use std::io::{self, Stderr};
+use anyhow::Result;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, execute, terminal},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{List, ListState, Paragraph},
+    Frame, Terminal,
+};
+use crate::pick::{PickAction, Picker};
+use crate::ProjectEntry;
+
+const HELP: &str = "enter open · ^a archive · ^r restore · ^e rename · esc quit";
+
+/// Runs the picker until a project and action are chosen, or `None` if it was dismissed.
+pub(crate) fn pick(mut picker: Picker) -> Result<Option<(ProjectEntry, PickAction)>> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stderr(), terminal::EnterAlternateScreen)?;
+    let result = Terminal::new(CrosstermBackend::new(io::stderr()))
+        .map_err(anyhow::Error::from)
+        .and_then(|mut term| event_loop(&mut term, &mut picker));
+    // Put the terminal back even when drawing failed.
+    terminal::disable_raw_mode()?;
+    execute!(io::stderr(), terminal::LeaveAlternateScreen)?;
+    result
+}
+
+fn event_loop(term: &mut Terminal<CrosstermBackend<Stderr>>, picker: &mut Picker) -> Result<Option<(ProjectEntry, PickAction)>> {
+    // The new title while renaming.
+    let mut renaming: Option<String> = None;
+    loop {
+        term.draw(|frame| draw(frame, picker, renaming.as_deref()))?;
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(title) = &mut renaming {
+            match key.code {
+                KeyCode::Esc => renaming = None,
+                KeyCode::Enter if !title.trim().is_empty() => {
+                    let title = title.clone();
+                    return Ok(picker.selected().map(|p| (p.clone(), PickAction::Rename(title))));
+                }
+                KeyCode::Backspace => { title.pop(); }
+                KeyCode::Char(c) if !ctrl => title.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        let chosen = |action| picker.selected().map(|p| (p.clone(), action));
+        match key {
+            KeyEvent { code: KeyCode::Esc, .. } => return Ok(None),
+            KeyEvent { code: KeyCode::Char('c'), .. } if ctrl => return Ok(None),
+            KeyEvent { code: KeyCode::Enter, .. } => return Ok(chosen(PickAction::Open)),
+            KeyEvent { code: KeyCode::Char('a'), .. } if ctrl => return Ok(chosen(PickAction::Archive)),
+            KeyEvent { code: KeyCode::Char('r'), .. } if ctrl => return Ok(chosen(PickAction::Restore)),
+            KeyEvent { code: KeyCode::Char('e'), .. } if ctrl => {
+                renaming = picker.selected().map(|p| p.title.clone().unwrap_or_else(|| p.slug.clone()));
+            }
+            KeyEvent { code: KeyCode::Up, .. } => picker.move_by(-1),
+            KeyEvent { code: KeyCode::Char('p'), .. } if ctrl => picker.move_by(-1),
+            KeyEvent { code: KeyCode::Down, .. } => picker.move_by(1),
+            KeyEvent { code: KeyCode::Char('n'), .. } if ctrl => picker.move_by(1),
+            KeyEvent { code: KeyCode::Backspace, .. } => picker.pop(),
+            KeyEvent { code: KeyCode::Char(c), .. } if !ctrl => picker.push(c),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, picker: &Picker, renaming: Option<&str>) {
+    let [prompt, list, help] = Layout::vertical([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let prompt_text = match renaming {
+        Some(title) => format!("rename to: {title}"),
+        None => format!("> {}", picker.query()),
+    };
+    frame.render_widget(Paragraph::new(prompt_text), prompt);
+    let rows: Vec<Line> = picker.visible().into_iter().map(|p| {
+        let line = Line::from(match &p.title {
+            Some(title) => format!("{}  {title}", p.slug),
+            None => p.slug.clone(),
+        });
+        if p.archived { line.dim() } else { line }
+    }).collect();
+    let mut state = ListState::default().with_selected(Some(picker.selected_index()));
+    frame.render_stateful_widget(List::new(rows).highlight_style(Style::new().reversed()).highlight_symbol("> "), list, &mut state);
+    frame.render_widget(Paragraph::new(HELP).dim(), help);
+}
@@ -0,0 +1,95 @@
+//! Git integration (`--git`, or `git = true` in the config file): `create` runs `git init` in the
+//! new project, and `archive` and `restore` commit their changes to the repository that holds
+//! the project root, if there is one.
+//!
+//! Git is reached through the injectable [`Vcs`] on `Config`, so tests can run without it.
+
+use std::{fmt::Debug, path::{Path, PathBuf}, process::{Command, Output, Stdio}};
+use crate::{error::IoContext, Config, FileOps, Result, SlugpmError};
+
+pub trait Vcs: Debug + Send + Sync {
+    /// Makes `dir` a new repository.
+    fn init(&self, dir: &Path) -> Result<()>;
+    /// The top of the work tree containing the directory `dir`, if it is in one.
+    fn repo_root(&self, dir: &Path) -> Option<PathBuf>;
+    /// Commits the current state of `paths` (including their removal) in `repo`. Paths the
+    /// repository doesn't know and that no longer exist are skipped; with nothing to commit
+    /// this does nothing.
+    fn commit(&self, repo: &Path, paths: &[PathBuf], message: &str) -> Result<()>;
+}
+
+/// Runs the `git` on `$PATH`.
+#[derive(Clone, Debug, Default)]
+pub struct GitVcs;
+
+impl GitVcs {
+    fn run(dir: &Path, args: &[&str], paths: &[&Path]) -> Result<Output> {
+        let mut command = Command::new("git");
+        command.current_dir(dir).args(args).stdin(Stdio::null());
+        if !paths.is_empty() {
+            command.arg("--").args(paths);
+        }
+        command.output()
+            .io_context(|| format!("running git {} in {}", args[0], dir.display()))
+    }
+
+    fn check(dir: &Path, args: &[&str], paths: &[&Path]) -> Result<Output> {
+        let output = Self::run(dir, args, paths)?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(SlugpmError::Other(format!("git {} failed in {}: {}", args[0], dir.display(), stderr.trim())));
+        }
+        Ok(output)
+    }
+}
+
+impl Vcs for GitVcs {
+    fn init(&self, dir: &Path) -> Result<()> {
+        Self::check(dir, &["init", "--quiet"], &[]).map(drop)
+    }
+
+    fn repo_root(&self, dir: &Path) -> Option<PathBuf> {
+        let output = Self::run(dir, &["rev-parse", "--show-toplevel"], &[]).ok()?;
+        let root = String::from_utf8(output.stdout).ok()?;
+        output.status.success().then(|| PathBuf::from(root.trim_end_matches(['\n', '\r'])))
+    }
+
+    fn commit(&self, repo: &Path, paths: &[PathBuf], message: &str) -> Result<()> {
+        let known: Vec<&Path> = paths.iter()
+            .filter(|path| path.exists() || Self::check(repo, &["ls-files"], &[path]).is_ok_and(|o| !o.stdout.is_empty()))
+            .map(PathBuf::as_path)
+            .collect();
+        if known.is_empty() {
+            return Ok(());
+        }
+        Self::check(repo, &["add", "--all"], &known)?;
+        // `diff --quiet` exits 1 when something is staged.
+        if Self::run(repo, &["diff", "--cached", "--quiet"], &known)?.status.success() {
+            return Ok(());
+        }
+        Self::check(repo, &["commit", "--quiet", "-m", message], &known).map(drop)
+    }
+}
+
+/// With `cfg.git`, commits `paths` to the repository holding `cfg.project_root`. Paths outside
+/// that repository are left out, and without one nothing happens.
+pub fn commit_paths(message: &str, paths: &[&Path], cfg: &Config, ops: &dyn FileOps) -> Result<()> {
+    if !cfg.git {
+        return Ok(());
+    }
+    let Some(repo) = ops.canonicalize(&cfg.project_root).ok().and_then(|root| cfg.vcs.repo_root(&root)) else {
+        return Ok(());
+    };
+    // Compare real paths, as git reports the repository's; a moved-away path's parent still exists.
+    let paths: Vec<PathBuf> = paths.iter()
+        .filter_map(|path| {
+            let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            Some(ops.canonicalize(parent).ok()?.join(path.file_name()?))
+        })
+        .filter(|path| path.starts_with(&repo))
+        .collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+    cfg.vcs.commit(&repo, &paths, message)
+}
@@ -0,0 +1,184 @@
+//! `slugpm watch`: filing what lands in an inbox directory by rules, as a small daemon.
+//!
+//! A rule is `<matcher> -> <action>`, and the first rule matching an item decides what
+//! happens to it:
+//!
+//! ```text
+//! ext:pdf,epub -> file:reading          # by extension, case-insensitively
+//! name:Screenshot* -> file:images/shots # by file name, `*` and `?` wildcards
+//! age:30d -> archive                    # unmodified for 30 days (see `prune::parse_age`)
+//! * -> archive                          # anything
+//! ```
+//!
+//! `archive` archives the item as `slugpm archive` would; `file:<project>[/<subdir>...]` moves it
+//! into a project as `slugpm mv` would. Items are the inbox's direct entries except dotfiles
+//! and its archive directory; ones modified within the settle time are left for a later pass,
+//! so files still being written aren't moved. [`file_inbox`] is one pass; with the `watch`
+//! feature, [`watch_inbox`] repeats it whenever the inbox changes.
+
+use std::{path::{Path, PathBuf}, time::{Duration, SystemTime}};
+use chrono::{Local, TimeZone};
+use regex::Regex;
+use crate::{archive_target, move_into_project, prune::parse_age, rules::wildcard_regex, Config, FileOps, Result, SlugpmError};
+
+/// Which items a rule applies to.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// Every item.
+    Any,
+    /// Files with one of these extensions, lowercase and without the dot.
+    Extension(Vec<String>),
+    /// Items whose name matches this wildcard pattern (compiled).
+    Name(Regex),
+    /// Items unmodified for at least this long.
+    OlderThan(Duration),
+}
+
+/// What a rule does with an item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Archive it next to the inbox.
+    Archive,
+    /// Move it into the project directory `<project>[/<subdir>...]`.
+    File(String),
+}
+
+/// One `<matcher> -> <action>` rule.
+#[derive(Clone, Debug)]
+pub struct WatchRule {
+    /// The rule as written, for messages.
+    pub spec: String,
+    pub matcher: Matcher,
+    pub action: Action,
+}
+
+impl WatchRule {
+    /// Whether the rule applies to the item `name`, last modified `age` ago.
+    pub fn matches(&self, name: &str, is_dir: bool, age: Duration) -> bool {
+        match &self.matcher {
+            Matcher::Any => true,
+            Matcher::Extension(exts) => !is_dir && Path::new(name).extension()
+                .is_some_and(|ext| exts.iter().any(|e| ext.to_string_lossy().eq_ignore_ascii_case(e))),
+            Matcher::Name(re) => re.is_match(name),
+            Matcher::OlderThan(min) => age >= *min,
+        }
+    }
+}
+
+/// Parses a rule; see the module docs for the syntax.
+pub fn parse_rule(spec: &str) -> Result<WatchRule> {
+    let invalid = |why: &str| SlugpmError::Other(format!("watch rule {spec:?}: {why}"));
+    let (matcher, action) = spec.split_once("->").ok_or_else(|| invalid("expected `<matcher> -> <action>`"))?;
+    let matcher = match matcher.trim() {
+        "*" => Matcher::Any,
+        m => match m.split_once(':') {
+            Some(("ext", exts)) => {
+                let exts: Vec<String> = exts.split(',').map(|e| e.trim().trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()).collect();
+                if exts.is_empty() {
+                    return Err(invalid("`ext:` needs at least one extension"));
+                }
+                Matcher::Extension(exts)
+            }
+            Some(("name", pattern)) if !pattern.trim().is_empty() => Matcher::Name(wildcard_regex(pattern.trim())),
+            Some(("age", age)) => Matcher::OlderThan(parse_age(age.trim()).map_err(|e| invalid(&e.to_string()))?),
+            _ => return Err(invalid("the matcher must be `*`, `ext:<exts>`, `name:<pattern>` or `age:<age>`")),
+        },
+    };
+    let action = match action.trim() {
+        "archive" => Action::Archive,
+        a => match a.strip_prefix("file:").map(str::trim) {
+            Some(dest) if !dest.trim_matches('/').is_empty() => Action::File(dest.to_string()),
+            _ => return Err(invalid("the action must be `archive` or `file:<project>[/<subdir>]`")),
+        },
+    };
+    Ok(WatchRule { spec: spec.trim().to_string(), matcher, action })
+}
+
+/// The first of `rules` that applies to the item `name`.
+pub fn rule_for<'r>(rules: &'r [WatchRule], name: &str, is_dir: bool, age: Duration) -> Option<&'r WatchRule> {
+    rules.iter().find(|rule| rule.matches(name, is_dir, age))
+}
+
+/// What happened to one inbox item in a pass.
+#[derive(Debug)]
+pub struct Filed {
+    pub source: PathBuf,
+    /// The spec of the rule that applied.
+    pub rule: String,
+    /// Where the item went, or why it couldn't.
+    pub result: Result<PathBuf>,
+}
+
+/// The inbox's items with their modification times: its direct entries except dotfiles and
+/// its archive directory.
+pub fn inbox_items(inbox: &Path, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut items = Vec::new();
+    for path in ops.read_dir(inbox)? {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') || name == cfg.archive_dir_name.as_str() {
+            continue;
+        }
+        let modified = ops.modified(&path)?;
+        items.push((path, modified));
+    }
+    items.sort();
+    Ok(items)
+}
+
+/// One pass over `inbox`: each item older than `settle` that a rule matches is archived or
+/// filed. Items are independent; one's failure is in its [`Filed::result`] and doesn't stop
+/// the others.
+pub fn file_inbox(inbox: &Path, rules: &[WatchRule], settle: Duration, cfg: &Config, ops: &dyn FileOps) -> Result<Vec<Filed>> {
+    let now = Local.from_local_datetime(&cfg.clock.now()).earliest().map(SystemTime::from).unwrap_or_else(SystemTime::now);
+    let mut filed = Vec::new();
+    for (path, modified) in inbox_items(inbox, cfg, ops)? {
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age < settle {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let Some(rule) = rule_for(rules, &name, ops.is_dir(&path), age) else { continue };
+        let result = match &rule.action {
+            Action::Archive => archive_target(&path, cfg, ops).map(|outcome| outcome.dest),
+            Action::File(dest) => move_into_project(&path, dest, false, cfg, ops),
+        };
+        filed.push(Filed { source: path, rule: rule.spec.clone(), result });
+    }
+    Ok(filed)
+}
+
+/// Runs [`file_inbox`] now, then again whenever `inbox` changes and at least every `settle`
+/// (for items still settling and `age:` rules), reporting each item to `report`. An item that
+/// keeps failing is reported once until it's modified again. Runs until `report` fails or the
+/// watch ends.
+#[cfg(feature = "watch")]
+pub fn watch_inbox(inbox: &Path, rules: &[WatchRule], settle: Duration, cfg: &Config, ops: &dyn FileOps, report: &mut dyn FnMut(&Filed) -> Result<()>) -> Result<()> {
+    use std::{collections::BTreeMap, sync::mpsc::{channel, RecvTimeoutError}};
+    use notify::{RecursiveMode, Watcher};
+
+    let watch_error = |e: notify::Error| SlugpmError::Other(format!("watching {}: {e}", inbox.display()));
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+    watcher.watch(inbox, RecursiveMode::NonRecursive).map_err(watch_error)?;
+    let mut failed: BTreeMap<PathBuf, SystemTime> = BTreeMap::new();
+    loop {
+        let items = inbox_items(inbox, cfg, ops)?;
+        failed.retain(|path, modified| items.iter().any(|(p, m)| p == path && m == modified));
+        for item in file_inbox(inbox, rules, settle, cfg, ops)? {
+            if item.result.is_err() {
+                let known = failed.contains_key(&item.source);
+                if let Some((_, modified)) = items.iter().find(|(p, _)| *p == item.source) {
+                    failed.insert(item.source.clone(), *modified);
+                }
+                if known {
+                    continue;
+                }
+            }
+            report(&item)?;
+        }
+        match rx.recv_timeout(settle.max(Duration::from_secs(1))) {
+            Ok(_) | Err(RecvTimeoutError::Timeout) => while rx.try_recv().is_ok() {},
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use slugpm::*;
-use std::path::Path;
+use std::{io, path::Path, sync::Arc};
+use chrono::NaiveDate;
+
+fn args(list: &[&str]) -> Vec<String> {
+    std::iter::once("slugpm").chain(list.iter().copied()).map(String::from).collect()
+}
 
 #[test]
 fn test_archive_dir_for_file_pure() {
@@ -35,3 +40,2613 @@ fn test_slugify_title() {
     let slug = slugify_title(title);
     assert_eq!(slug, "my-project");
 }
+
+#[test]
+fn test_create_projects_multi_prints_paths_in_order() {
+    let input = "Alpha\n\nBeta Two\nGamma!\n";
+    let summary = create_projects_multi_with(input, &Config::default(), &MockFileOps).unwrap();
+    assert_eq!(summary.created, [Path::new("project/alpha"), Path::new("project/beta-two"), Path::new("project/gamma")]);
+    assert!(summary.skipped.is_empty());
+
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["create", "--multi", "--project-root", "project"]), &mut input.as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/alpha\nproject/beta-two\nproject/gamma\n");
+}
+
+#[test]
+fn test_archive_protect_writes_sidecar() {
+    let ops = MemoryFileOps::new().with_file("/p/notes.txt", "hello");
+    let cfg = Config { protect: true, ..Config::default() };
+    let dest = archive_file(Path::new("/p/notes.txt"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/notes.txt"));
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"hello");
+    let sidecar = String::from_utf8(ops.contents("/p/archive/notes.txt.sha256").unwrap()).unwrap();
+    assert_eq!(sidecar, format!("{}  notes.txt\n", integrity::sha256_hex(b"hello")));
+}
+
+#[test]
+fn test_archive_overwrite_refuses_tampered_file() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/archive/notes.txt", "tampered")
+        .with_file("/p/archive/notes.txt.sha256", integrity::sidecar_contents(Path::new("notes.txt"), b"original"))
+        .with_file("/p/notes.txt", "new");
+    let cfg = Config { on_conflict: Some(ConflictPolicy::Overwrite), ..Config::default() };
+    let err = archive_file(Path::new("/p/notes.txt"), &cfg, &ops).unwrap_err();
+    assert!(err.to_string().contains("refusing to overwrite"));
+    assert_eq!(ops.contents("/p/notes.txt").unwrap(), b"new");
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"tampered");
+}
+
+#[test]
+fn test_run_create_from_piped_input() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&[]), &mut "My Project\nignored\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "project/my-project\n");
+    assert!(ops.is_dir(Path::new("project/my-project")));
+}
+
+#[test]
+fn test_run_archive_file() {
+    let dir = std::env::temp_dir().join(format!("slugpm-run-archive-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = RealFileOps.canonicalize(&dir).unwrap();
+    std::fs::write(dir.join("notes.txt"), "hi").unwrap();
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run(args(&["archive", dir.join("notes.txt").to_str().unwrap()]), &mut io::empty(), &mut out, &mut err).unwrap();
+    assert_eq!(code, 0);
+    let dest = dir.join("archive").join("notes.txt");
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", dest.display()));
+    assert!(dest.is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_run_reports_usage_errors_as_exit_code() {
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    let code = run_with(args(&["archive", "--bogus"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_INVALID_INPUT);
+    assert!(!err.is_empty());
+}
+
+#[test]
+fn test_slug_fallback_modes_for_empty_slug_title() {
+    // With emoji dropped, an all-emoji title slugifies to nothing.
+    let title = "🚀🔥";
+    let cfg = |fallback_slug| Config { emoji: EmojiMode::Drop, fallback_slug, ..Config::default() };
+    let hashed = project_slug(title, &cfg(FallbackSlug::Hash)).unwrap();
+    assert_eq!(hashed, format!("untitled-{}", &integrity::sha256_hex(title.as_bytes())[..8]));
+    assert_eq!(hashed, slug_or_fallback(title, &cfg(FallbackSlug::Hash)).unwrap());
+    assert_ne!(hashed, project_slug("🎉", &cfg(FallbackSlug::Hash)).unwrap());
+    assert_eq!(project_slug(title, &cfg(FallbackSlug::Literal)).unwrap(), "untitled");
+    assert!(project_slug(title, &cfg(FallbackSlug::Error)).unwrap_err().to_string().contains("empty slug"));
+    assert_eq!(project_slug("🚀 Launch!", &cfg(FallbackSlug::Error)).unwrap(), "launch");
+}
+
+#[test]
+fn test_archive_dir_merge_applies_conflict_policy_per_file() {
+    let tree = || MemoryFileOps::new()
+        .with_file("/p/archive/foo/a.txt", "old")
+        .with_file("/p/archive/foo/b.txt", "kept")
+        .with_file("/p/work/foo/a.txt", "new")
+        .with_file("/p/work/foo/sub/c.txt", "added");
+
+    let ops = tree();
+    let cfg = Config { merge: true, ..Config::default() };
+    assert!(archive_dir(Path::new("/p/work/foo"), &cfg, &ops).unwrap_err().to_string().contains("already exists"));
+    assert_eq!(ops.contents("/p/work/foo/sub/c.txt").unwrap(), b"added");
+
+    let ops = tree();
+    let cfg = Config { merge: true, on_conflict: Some(ConflictPolicy::Overwrite), ..Config::default() };
+    let dest = archive_dir(Path::new("/p/work/foo"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/foo"));
+    assert_eq!(ops.contents("/p/archive/foo/a.txt").unwrap(), b"new");
+    assert_eq!(ops.contents("/p/archive/foo/b.txt").unwrap(), b"kept");
+    assert_eq!(ops.contents("/p/archive/foo/sub/c.txt").unwrap(), b"added");
+    assert!(!ops.exists(Path::new("/p/work/foo")));
+}
+
+#[test]
+fn test_run_archive_print_source_is_tab_separated() {
+    let ops = MemoryFileOps::new().with_file("/p/notes.txt", "hi");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "--print-source", "/p/notes.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/notes.txt\t/p/archive/notes.txt\n");
+}
+
+#[test]
+fn test_archive_into_readonly_dir_names_the_archive_dir() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "hi")
+        .with_readonly_dir("/p/archive");
+    let err = archive_file(Path::new("/p/notes.txt"), &Config::default(), &ops).unwrap_err();
+    assert!(err.to_string().starts_with("archive directory /p/archive is not writable"));
+    assert!(ops.exists(Path::new("/p/notes.txt")));
+}
+
+#[test]
+fn test_archive_with_category() {
+    let ops = MemoryFileOps::new().with_file("/p/report.pdf", "pdf");
+    let cfg = Config { category: Some("invoices".into()), ..Config::default() };
+    let dest = archive_file(Path::new("/p/report.pdf"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/invoices/report.pdf"));
+}
+
+#[test]
+fn test_archive_category_is_slugified() {
+    let ops = MemoryFileOps::new().with_file("/p/report.pdf", "pdf");
+    let cfg = Config { category: Some("Tax Returns 2024".into()), ..Config::default() };
+    let dest = archive_file(Path::new("/p/report.pdf"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/tax-returns-2024/report.pdf"));
+}
+
+#[test]
+fn test_archive_category_rejects_separators() {
+    let ops = MemoryFileOps::new().with_file("/p/report.pdf", "pdf");
+    let cfg = Config { category: Some("a/b".into()), ..Config::default() };
+    let err = archive_file(Path::new("/p/report.pdf"), &cfg, &ops).unwrap_err();
+    assert!(err.to_string().contains("path separators"));
+    assert!(ops.exists(Path::new("/p/report.pdf")));
+}
+
+#[test]
+fn test_apply_newline_policy() {
+    assert_eq!(apply_newline_policy(b"a\n", NewlinePolicy::Ensure), b"a\n");
+    assert_eq!(apply_newline_policy(b"a", NewlinePolicy::Ensure), b"a\n");
+    assert_eq!(apply_newline_policy(b"a\r\n\n", NewlinePolicy::Strip), b"a");
+    assert_eq!(apply_newline_policy(b"a", NewlinePolicy::Strip), b"a");
+    assert_eq!(apply_newline_policy(b"a\n", NewlinePolicy::Keep), b"a\n");
+    assert_eq!(apply_newline_policy(b"a", NewlinePolicy::Keep), b"a");
+    assert_eq!(apply_newline_policy(b"", NewlinePolicy::Ensure), b"");
+}
+
+#[test]
+fn test_archive_policy_file_applies_unless_flag_given() {
+    let tree = || MemoryFileOps::new()
+        .with_file("/p/archive/.slugpm-policy", "on_conflict = \"timestamp\"\n")
+        .with_file("/p/archive/notes.txt", "old")
+        .with_file("/p/notes.txt", "new");
+    let clock = Arc::new(FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap()));
+
+    let ops = tree();
+    let cfg = Config { clock: clock.clone(), ..Config::default() };
+    let dest = archive_file(Path::new("/p/notes.txt"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/notes-20250913-140205.txt"));
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"old");
+
+    let ops = tree();
+    let cfg = Config { on_conflict: Some(ConflictPolicy::Overwrite), clock, ..Config::default() };
+    let dest = archive_file(Path::new("/p/notes.txt"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/notes.txt"));
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"new");
+}
+
+#[test]
+fn test_archive_overwrite_replaces_directory() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/archive/proj/old.md", "old")
+        .with_file("/w/project/proj/new.md", "new");
+    // Like a real file system, a rename can't replace a directory that has something in it.
+    assert!(ops.rename(Path::new("/w/project/proj"), Path::new("/w/archive/proj")).is_err());
+    assert!(ops.rename(Path::new("/w/project/proj/new.md"), Path::new("/w/archive/proj")).is_err());
+
+    let cfg = Config { project_root: "/w/project".into(), on_conflict: Some(ConflictPolicy::Overwrite), ..Config::default() };
+    let dest = archive_dir(Path::new("/w/project/proj"), &cfg, &ops).unwrap();
+    assert_eq!(dest, Path::new("/w/archive/proj"));
+    assert_eq!(ops.tree("/w/archive"), ["proj/", "proj/new.md"]);
+    assert!(!ops.exists(Path::new("/w/project/proj")));
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    ops.create_dir_all(Path::new("/w/project/proj")).unwrap();
+    ops.write(Path::new("/w/project/proj/newer.md"), b"newer").unwrap();
+    let code = run_with(args(&["archive", "--force", "--project-root", "/w/project", "/w/project/proj"]), &mut io::empty(), &mut Vec::new(), &mut Vec::new(), &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(ops.tree("/w/archive"), [".manifest.jsonl", "proj/", "proj/newer.md"]);
+}
+
+fn run_slug_check(title: &str) -> (i32, String, String) {
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["slug", "--check", title]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+}
+
+#[test]
+fn test_slug_check_valid_title() {
+    assert_eq!(check_title("My Title", &Config::default()), Ok("my-title".to_string()));
+    assert_eq!(run_slug_check("My Title"), (0, "my-title\n".to_string(), String::new()));
+}
+
+#[test]
+fn test_slug_check_uses_slug_options() {
+    let cfg = Config { slug: SlugOptions { separator: "_".into(), ..SlugOptions::default() }, ..Config::default() };
+    assert_eq!(check_title("My Title", &cfg), Ok(slugify_with("My Title", &cfg)));
+    assert_eq!(check_title("My Title", &cfg), Ok("my_title".to_string()));
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    let mut out = Vec::new();
+    run_with(args(&["--slug-separator", "_", "slug", "--check", "My Title"]), &mut io::empty(), &mut out, &mut Vec::new(), &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "my_title\n");
+}
+
+#[test]
+fn test_slug_check_empty_slug() {
+    assert_eq!(check_title("?!", &Config::default()), Err(InvalidSlug::Empty));
+    let (code, out, err) = run_slug_check("?!");
+    assert_eq!((code, out.as_str()), (EXIT_INVALID_INPUT, ""));
+    assert!(err.contains("empty slug"));
+}
+
+#[test]
+fn test_slug_check_over_length() {
+    let title = "word ".repeat(60);
+    assert!(matches!(check_title(&title, &Config::default()), Err(InvalidSlug::TooLong { len: 299, .. })));
+    let (code, _, err) = run_slug_check(&title);
+    assert_eq!(code, EXIT_INVALID_INPUT);
+    assert!(err.contains("299 bytes long"));
+}
+
+#[test]
+fn test_crlf_input_for_create_and_multi() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&[]), &mut "Foo\r\nBar\r\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/foo\n");
+
+    let mut out = Vec::new();
+    run_with(args(&["create", "--multi", "--reuse"]), &mut "Foo\r\nBar\r\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/foo\nproject/bar\n");
+    assert_eq!(normalize_line_endings("a\rb\r\nc"), "a\nb\nc");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_archive_link_shares_inode_with_source() {
+    use std::os::unix::fs::MetadataExt;
+    let dir = std::env::temp_dir().join(format!("slugpm-link-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("notes.txt"), "hi").unwrap();
+    let cfg = Config { link: true, ..Config::default() };
+    let dest = archive_file(&dir.join("notes.txt"), &cfg, &RealFileOps).unwrap();
+    let (src_meta, dest_meta) = (std::fs::metadata(dir.join("notes.txt")).unwrap(), std::fs::metadata(&dest).unwrap());
+    assert_eq!(src_meta.ino(), dest_meta.ino());
+    assert!(archive_dir(&dir, &cfg, &RealFileOps).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_create_appends_project_log_record() {
+    let ops = MemoryFileOps::new().with_file("/team/projects.tsv", "");
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap();
+    let cfg = Config { project_log: Some("/team/projects.tsv".into()), clock: Arc::new(FixedClock(now)), ..Config::default() };
+    create_project_dir_with("My\tProject", &cfg, &ops).unwrap();
+    create_project_dir_with("Other", &cfg, &ops).unwrap();
+    assert_eq!(
+        String::from_utf8(ops.contents("/team/projects.tsv").unwrap()).unwrap(),
+        "2025-09-13T14:02:05\tMy Project\tproject/my-project\n2025-09-13T14:02:05\tOther\tproject/other\n"
+    );
+}
+
+#[test]
+fn test_create_unique_numbers_existing_slug() {
+    let ops = MemoryFileOps::new();
+    let cfg = Config { unique: true, ..Config::default() };
+    assert_eq!(create_project_dir_with("My Project", &cfg, &ops).unwrap(), Path::new("project/my-project"));
+    assert_eq!(create_project_dir_with("My Project", &cfg, &ops).unwrap(), Path::new("project/my-project-2"));
+    assert!(ops.is_dir(Path::new("project/my-project-2")));
+}
+
+#[test]
+fn test_name_stream_strips_each_line() {
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let input = "2025-09-13-alpha\nbeta\n\n2024-01-02gamma\n2023-03-04-\n";
+    run_with(args(&["name", "--stream"]), &mut input.as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "alpha\nbeta\n\ngamma\n\n");
+
+    // Without directories, piped STDIN is read just the same.
+    let mut out = Vec::new();
+    run_with(args(&["name"]), &mut "2025-09-13-alpha\nbeta\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "alpha\nbeta\n");
+}
+
+#[test]
+fn test_name_accepts_several_dirnames() {
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["name", "project/2025-09-13-alpha", "beta/", "/w/2024-01-02-gamma"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "alpha\nbeta\ngamma\n");
+    assert_eq!(run_with(args(&["name"]), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap(), EXIT_FAILURE);
+}
+
+#[test]
+fn test_append_to_nonexistent_target_creates_archive_file() {
+    let ops = MemoryFileOps::new().with_dir("/p");
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "/p/log.txt", "-"]), &mut "entry\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/archive/log.txt\n");
+    assert_eq!(ops.contents("/p/archive/log.txt").unwrap(), b"entry\n");
+}
+
+#[test]
+fn test_slugify_file_name_ext_lower_toggle() {
+    assert_eq!(slugify_file_name("My Report.PDF", true), "my-report.pdf");
+    assert_eq!(slugify_file_name("My Report.PDF", false), "my-report.PDF");
+    assert_eq!(slugify_file_name(".profile", true), "profile");
+
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["slug", "--file-name", "--no-ext-lower", "Report.PDF"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "report.PDF\n");
+}
+
+#[test]
+fn test_append_flag_and_legacy_dash_both_append() {
+    let ops = MemoryFileOps::new().with_file("/p/log.txt", "");
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["archive", "/p/log.txt", "-"]), &mut "one\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    run_with(args(&["archive", "--append", "/p/log.txt"]), &mut "two\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    run_with(args(&["archive", "-a", "/p/log.txt"]), &mut "three\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(ops.contents("/p/archive/log.txt").unwrap(), b"one\ntwo\nthree\n");
+    assert!(ops.exists(Path::new("/p/log.txt")));
+}
+
+#[test]
+fn test_untitled_name_uses_clock() {
+    let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap());
+    assert_eq!(untitled_name(&clock), "untitled-20250913-140205");
+}
+
+#[test]
+fn test_create_on_empty_piped_input() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    assert_eq!(run_with(args(&[]), &mut "\n".as_bytes(), &mut out, &mut err, &ctx).unwrap(), EXIT_FAILURE);
+    assert!(String::from_utf8(std::mem::take(&mut err)).unwrap().contains("STDIN is empty"));
+
+    run_with(args(&["--on-empty", "untitled"]), &mut "".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    let created = String::from_utf8(out).unwrap();
+    assert!(created.starts_with("project/untitled-"));
+    assert!(ops.is_dir(Path::new(created.trim_end())));
+}
+
+#[test]
+fn test_check_windows_path_length() {
+    let long = format!("C:\\{}\\archive\\notes.txt", "d".repeat(250));
+    let err = check_windows_path_length(Path::new(&long)).unwrap_err();
+    assert!(err.to_string().contains("over the Windows limit of 259"));
+    assert!(check_windows_path_length(Path::new(&format!(r"\\?\{long}"))).is_ok());
+    assert!(check_windows_path_length(Path::new(r"C:\p\archive\notes.txt")).is_ok());
+}
+
+#[test]
+fn test_strip_verbatim_prefix() {
+    assert_eq!(strip_verbatim_prefix(Path::new(r"\\?\C:\work\notes.txt")), Path::new(r"C:\work\notes.txt"));
+    assert_eq!(strip_verbatim_prefix(Path::new(r"\\?\UNC\server\share\notes.txt")), Path::new(r"\\server\share\notes.txt"));
+    assert_eq!(strip_verbatim_prefix(Path::new(r"\\?\Volume{1234}\notes.txt")), Path::new(r"\\?\Volume{1234}\notes.txt"));
+    let long = format!(r"\\?\C:\{}\notes.txt", "d".repeat(260));
+    assert_eq!(strip_verbatim_prefix(Path::new(&long)), Path::new(&long));
+    assert_eq!(strip_verbatim_prefix(Path::new("/p/notes.txt")), Path::new("/p/notes.txt"));
+}
+
+#[test]
+fn test_append_stdin_reads_given_input() {
+    let ops = MemoryFileOps::new().with_file("/p/notes.txt", "");
+    archive_append_stdin_with(Path::new("/p/notes.txt"), "first\n".as_bytes(), &ops).unwrap();
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"first\n");
+}
+
+#[cfg(windows)]
+#[test]
+fn test_archive_rejects_over_length_destination_on_windows() {
+    let dir = format!("C:\\{}", "d".repeat(250));
+    let ops = MemoryFileOps::new().with_file(format!("{dir}\\notes.txt"), "hi");
+    let err = archive_file(&Path::new(&dir).join("notes.txt"), &Config::default(), &ops).unwrap_err();
+    assert!(err.to_string().contains("over the Windows limit"));
+    assert!(ops.exists(&Path::new(&dir).join("notes.txt")));
+}
+
+#[test]
+fn test_archive_many_reports_each_target() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/a.txt", "a")
+        .with_file("/p/b.txt", "b");
+    let targets = ["/p/a.txt", "/p/missing.txt", "/p/b.txt"].map(std::path::PathBuf::from);
+    let results = archive_many(&targets, &Config::default(), &ops);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0], TargetResult { source: "/p/a.txt".into(), dest: Some("/p/archive/a.txt".into()), error: None, kind: None, code: None, warnings: Vec::new() });
+    assert_eq!(results[1].source, Path::new("/p/missing.txt"));
+    assert_eq!(results[1].dest, None);
+    assert!(results[1].error.as_deref().unwrap().contains("/p/missing.txt does not exist"));
+    assert_eq!(results[2].dest.as_deref(), Some(Path::new("/p/archive/b.txt")));
+}
+
+#[test]
+fn test_archive_paths_from_stdin() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/a.txt", "a")
+        .with_file("/p/b.txt", "b")
+        .with_file("/p/c.txt", "c");
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "--stdin"]), &mut "/p/a.txt\r\n/p/missing.txt\n\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/archive/a.txt\n");
+    assert!(String::from_utf8(err).unwrap().contains("error: /p/missing.txt"));
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "--stdin", "-0", "--fail-fast"]), &mut "/p/missing.txt\0/p/b.txt\0".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_NOT_FOUND);
+    assert!(ops.exists(Path::new("/p/b.txt")));
+    // A batch that fails as a whole exits with its failures' status, if they share one.
+    let code = run_with(args(&["archive", "/p/missing.txt", "/p/gone.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_NOT_FOUND);
+    let code = run_with(args(&["archive", "/p/missing.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_NOT_FOUND);
+    let code = run_with(args(&["archive", "/p/missing.txt", "/p/archive"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_FAILURE);
+    let code = run_with(args(&["archive", "--stdin", "/p/c.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert!(ops.exists(Path::new("/p/archive/c.txt")));
+}
+
+#[test]
+fn test_trash_uses_freedesktop_layout_and_can_be_undone() {
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap();
+    let ops = MemoryFileOps::new()
+        .with_file("/w/notes draft.txt", "a")
+        .with_file("/w/other/notes draft.txt", "b")
+        .with_dir("/w/project");
+    let cfg = Config {
+        trash: Arc::new(trash::FreedesktopTrash { dir: "/home/u/.local/share/Trash".into() }),
+        clock: Arc::new(FixedClock(now)),
+        project_root: "/w/project".into(),
+        journal: true,
+        ..Config::default()
+    };
+    let first = trash_target(Path::new("/w/notes draft.txt"), &cfg, &ops).unwrap();
+    assert_eq!(first.dest, Path::new("/home/u/.local/share/Trash/files/notes draft.txt"));
+    assert_eq!(
+        String::from_utf8(ops.contents("/home/u/.local/share/Trash/info/notes draft.txt.trashinfo").unwrap()).unwrap(),
+        "[Trash Info]\nPath=/w/notes%20draft.txt\nDeletionDate=2025-09-13T14:02:05\n"
+    );
+    let second = trash_target(Path::new("/w/other/notes draft.txt"), &cfg, &ops).unwrap();
+    assert_eq!(second.dest, Path::new("/home/u/.local/share/Trash/files/notes draft-2.txt"));
+    assert!(matches!(trash_target(Path::new("/w/gone.txt"), &cfg, &ops), Err(SlugpmError::NotFound(_))));
+
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/other/notes draft.txt").unwrap(), b"b");
+    assert!(!ops.exists(Path::new("/home/u/.local/share/Trash/info/notes draft-2.txt.trashinfo")));
+}
+
+#[test]
+fn test_compressed_archive_round_trip() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/site/index.md", "# Site\n")
+        .with_file("/w/project/site/assets/logo.bin", vec![0u8, 159, 146, 150])
+        .with_file("/w/project/site/build.sh", "#!/bin/sh\n")
+        .with_mode("/w/project/site/build.sh", 0o755)
+        .with_mode("/w/project/site/assets", 0o700)
+        .with_dir("/w/project/site/empty");
+    let cfg = Config { compress: true, project_root: "/w/project".into(), journal: true, ..Config::default() };
+    let outcome = archive_target(Path::new("/w/project/site"), &cfg, &ops).unwrap();
+    assert_eq!(outcome.dest, Path::new("/w/archive/site.tar.zst"));
+    let tarball = zstd::decode_all(ops.contents(&outcome.dest).unwrap().as_slice()).unwrap();
+    let modes: Vec<(String, u32)> = tar::Archive::new(tarball.as_slice()).entries().unwrap()
+        .map(|e| e.unwrap())
+        .map(|e| (e.path().unwrap().to_string_lossy().into_owned(), e.header().mode().unwrap()))
+        .collect();
+    assert!(modes.contains(&("site/build.sh".into(), 0o755)) && modes.contains(&("site/assets".into(), 0o700)), "{modes:?}");
+    assert!(modes.contains(&("site/index.md".into(), 0o644)), "{modes:?}");
+    assert!(!ops.exists(Path::new("/w/project/site")));
+    assert!(compress::is_compressed(&outcome.dest));
+    assert_eq!(suffixed_path(&outcome.dest, "2"), Path::new("/w/archive/site-2.tar.zst"));
+
+    let restored = restore_target(&outcome.dest, None, &cfg, &ops).unwrap();
+    assert_eq!(restored, Path::new("/w/project/site"));
+    assert_eq!(ops.contents("/w/project/site/index.md").unwrap(), b"# Site\n");
+    assert_eq!(ops.contents("/w/project/site/assets/logo.bin").unwrap(), [0u8, 159, 146, 150]);
+    assert_eq!(ops.mode("/w/project/site/build.sh"), Some(0o755));
+    assert_eq!(ops.mode("/w/project/site/assets"), Some(0o700));
+    assert!(ops.is_dir(Path::new("/w/project/site/empty")));
+    assert!(!ops.exists(&outcome.dest));
+
+    // Undoing the restore packs the directory again, and undoing the archive unpacks it.
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(ops.is_file(&outcome.dest) && !ops.exists(Path::new("/w/project/site")));
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/project/site/index.md").unwrap(), b"# Site\n");
+    assert!(!ops.exists(&outcome.dest));
+
+    // A directory that can't be packed leaves no half-written tarball behind.
+    let ops = ops.with_file("/w/project/links/a.md", "a").with_symlink("/w/project/links/z", "a.md");
+    assert!(compress::pack_dir(Path::new("/w/project/links"), Path::new("/w/archive/links.tar.zst"), &ops).is_err());
+    assert!(!ops.exists(Path::new("/w/archive/links.tar.zst")));
+}
+
+#[test]
+fn test_dated_archive_dirs() {
+    let date = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap();
+    assert_eq!(archive_dir_for_file_dated_pure(Path::new("/foo/bar"), date), Path::new("/foo/bar/archive/2025/2025-09-13"));
+    assert_eq!(archive_dir_for_dir_dated_pure(Path::new("/foo/bar"), date), Path::new("/foo/archive/2025/2025-09-13"));
+
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "hi")
+        .with_dir("/w/project/site");
+    let cfg = Config { dated_archive: true, clock: Arc::new(FixedClock(date.and_hms_opt(9, 0, 0).unwrap())), ..Config::default() };
+    let file = archive_target(Path::new("/p/notes.txt"), &cfg, &ops).unwrap().dest;
+    assert_eq!(file, Path::new("/p/archive/2025/2025-09-13/notes.txt"));
+    assert_eq!(archive_target(Path::new("/w/project/site"), &cfg, &ops).unwrap().dest, Path::new("/w/archive/2025/2025-09-13/site"));
+    assert!(matches!(archive_target(&file, &cfg, &ops), Err(SlugpmError::AlreadyArchived(_))));
+    assert_eq!(restore_target(&file, None, &cfg, &ops).unwrap(), Path::new("/p/notes.txt"));
+    let appended = archive_append_with(Path::new("/p/log.txt"), &mut "x\n".as_bytes(), &cfg, &ops).unwrap();
+    assert_eq!(appended, Path::new("/p/archive/log.txt"));
+}
+
+#[test]
+fn test_prune_old_archive_items() {
+    let at = |secs| std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    assert_eq!(prune::parse_age("90d").unwrap(), std::time::Duration::from_secs(90 * 86400));
+    assert_eq!(prune::parse_age("2w").unwrap(), prune::parse_age("14").unwrap());
+    assert!(prune::parse_age("3 months").is_err());
+    let entries = [("/a/old".into(), at(10)), ("/a/new".into(), at(30))];
+    assert_eq!(prune::older_than(&entries, at(20)), [Path::new("/a/old")]);
+
+    let day = 86400;
+    let ops = MemoryFileOps::new()
+        .with_file("/w/archive/old.txt", "old")
+        .with_file("/w/archive/old.txt.sha256", "x")
+        .with_file("/w/archive/new.txt", "new")
+        .with_file("/w/archive/.index.json", "[]")
+        .with_file("/w/archive/1970/1970-01-02/site/index.md", "# Site")
+        .with_modified("/w/archive/old.txt", at(day))
+        .with_modified("/w/archive/new.txt", at(99 * day))
+        .with_modified("/w/archive/1970/1970-01-02/site", at(day));
+    let now = chrono::DateTime::<chrono::Local>::from(at(100 * day)).naive_local();
+    let cfg = Config { project_root: "/w/project".into(), clock: Arc::new(FixedClock(now)), ..Config::default() };
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["prune", "--dry-run", "--older-than", "30d", "/w/archive"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert!(String::from_utf8(err).unwrap().contains("would remove"));
+    assert!(ops.exists(Path::new("/w/archive/old.txt")));
+
+    let pruned = prune::prune(Path::new("/w/archive"), prune::parse_age("30d").unwrap(), false, &cfg, &ops).unwrap();
+    assert_eq!(pruned.iter().map(|p| p.path.as_path()).collect::<Vec<_>>(), [Path::new("/w/archive/1970/1970-01-02/site"), Path::new("/w/archive/old.txt")]);
+    assert_eq!(pruned[1].size, 3);
+    assert!(!ops.exists(Path::new("/w/archive/old.txt.sha256")));
+    assert!(!ops.exists(Path::new("/w/archive/1970")));
+    assert!(ops.exists(Path::new("/w/archive/new.txt")) && ops.exists(Path::new("/w/archive/.index.json")));
+
+    // A `.tar.zst` name that's taken stops the run before anything is packed or removed.
+    let ops = MemoryFileOps::new()
+        .with_file("/w/archive/alpha/a.md", "a")
+        .with_file("/w/archive/beta/b.md", "b")
+        .with_file("/w/archive/beta.tar.zst", "taken")
+        .with_modified("/w/archive/alpha", at(day))
+        .with_modified("/w/archive/beta", at(day));
+    let e = prune::prune(Path::new("/w/archive"), prune::parse_age("30d").unwrap(), true, &cfg, &ops).unwrap_err();
+    assert!(matches!(e, SlugpmError::Collision(dest) if dest == Path::new("/w/archive/beta.tar.zst")));
+    assert_eq!(ops.tree("/w/archive"), ["alpha/", "alpha/a.md", "beta.tar.zst", "beta/", "beta/b.md"]);
+}
+
+#[test]
+fn test_picker_filters_and_applies_actions() {
+    let at = |secs| std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/garden-plan")
+        .with_dir("/w/project/tax-return")
+        .with_dir("/w/archive/garden-plan")
+        .with_modified("/w/project/garden-plan", at(20))
+        .with_modified("/w/project/tax-return", at(30))
+        .with_modified("/w/archive/garden-plan", at(10));
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    let mut picker = pick::Picker::load(&cfg, &ops).unwrap();
+    let slugs = |picker: &pick::Picker| picker.visible().iter().map(|p| (p.slug.clone(), p.archived)).collect::<Vec<_>>();
+    assert_eq!(slugs(&picker)[0], ("tax-return".to_string(), false));
+    "gard".chars().for_each(|c| picker.push(c));
+    assert_eq!(slugs(&picker), [("garden-plan".to_string(), false), ("garden-plan".to_string(), true)]);
+    picker.move_by(5);
+    assert!(picker.selected().unwrap().archived);
+    let archived = picker.selected().unwrap().clone();
+    assert!(matches!(pick::apply_pick(&archived, &pick::PickAction::Archive, &cfg, &ops), Err(SlugpmError::AlreadyArchived(_))));
+    picker.move_by(-1);
+    let active = picker.selected().unwrap().clone();
+    assert_eq!(pick::apply_pick(&active, &pick::PickAction::Open, &cfg, &ops).unwrap(), Path::new("/w/project/garden-plan"));
+    let tax = pick::Picker::load(&cfg, &ops).unwrap().selected().unwrap().clone();
+    assert_eq!(pick::apply_pick(&tax, &pick::PickAction::Archive, &cfg, &ops).unwrap(), Path::new("/w/archive/tax-return"));
+}
+
+#[test]
+fn test_emoji_modes() {
+    assert_eq!(slugify_title(&substitute_emoji("🚀 Launch", EmojiMode::Drop)), "launch");
+    assert_eq!(slugify_title(&substitute_emoji("🚀 Launch", EmojiMode::Name)), "rocket-launch");
+    assert_eq!(slugify_title(&substitute_emoji("Ship👍it", EmojiMode::Name)), "ship-thumbs-up-it");
+
+    let cfg = Config { emoji: EmojiMode::Drop, fallback_slug: FallbackSlug::Hash, ..Config::default() };
+    assert_ne!(project_slug("🚀", &cfg).unwrap(), project_slug("🔥", &cfg).unwrap());
+}
+
+#[test]
+fn test_archive_targets_from_response_file() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/a.txt", "a")
+        .with_file("/p/b.txt", "b")
+        .with_file("/p/c.txt", "c")
+        .with_file("/lists/targets.txt", "/p/a.txt\r\n\n/p/b.txt\n");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "@/lists/targets.txt", "/p/c.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/archive/a.txt\n/p/archive/b.txt\n/p/archive/c.txt\n");
+    assert!(!ops.exists(Path::new("/p/a.txt")));
+}
+
+#[test]
+fn test_dedupe_groups_identical_files() {
+    let newer = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100);
+    let ops = MemoryFileOps::new()
+        .with_file("/p/archive/a.txt", "same")
+        .with_file("/p/archive/invoices/b.txt", "same")
+        .with_file("/p/archive/c.txt", "unique")
+        .with_file("/p/archive/a.txt.sha256", "sidecar")
+        // Archived in its own right: nothing named `SHA256SUMS` is next to it.
+        .with_file("/p/archive/SHA256SUMS.sha256", "lone")
+        .with_modified("/p/archive/a.txt", newer);
+    let files = dedupe::scan_archive(Path::new("/p/archive"), &ops).unwrap();
+    assert_eq!(files.len(), 4);
+    assert!(files.iter().any(|f| f.path == Path::new("/p/archive/SHA256SUMS.sha256") && f.hash == integrity::sha256_hex(b"lone") && f.size == 4));
+    let groups = dedupe::group_duplicates(&files);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].keep, Path::new("/p/archive/invoices/b.txt"));
+    assert_eq!(groups[0].duplicates, [Path::new("/p/archive/a.txt")]);
+    assert_eq!(groups[0].size, 4);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["dedupe", "/p/archive"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert!(ops.exists(Path::new("/p/archive/a.txt")));
+    assert_eq!(String::from_utf8(std::mem::take(&mut out)).unwrap(), "keep /p/archive/invoices/b.txt\n  duplicate /p/archive/a.txt\n");
+    run_with(args(&["--json", "dedupe", "/p/archive", "--yes"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(record["removed"], serde_json::json!(["/p/archive/a.txt"]));
+    assert!(!ops.exists(Path::new("/p/archive/a.txt")));
+    assert!(!ops.exists(Path::new("/p/archive/a.txt.sha256")));
+    assert!(ops.exists(Path::new("/p/archive/invoices/b.txt")));
+}
+
+#[test]
+fn test_index_append_to_empty_and_existing() {
+    let entry = |name: &str| index::IndexEntry {
+        name: name.into(),
+        dest: Path::new("/p/archive").join(name),
+        archived_at: "2025-09-13T14:02:05".into(),
+        size: 3,
+    };
+    let first = index::append_to_index(None, &entry("a.txt")).unwrap();
+    assert_eq!(index::parse_index(&first).unwrap(), [entry("a.txt")]);
+    let second = index::append_to_index(Some(&first), &entry("b.txt")).unwrap();
+    let entries = index::parse_index(&second).unwrap();
+    assert_eq!(entries, [entry("a.txt"), entry("b.txt")]);
+    assert_eq!(index::search_index(&entries, "B.TXT"), [&entry("b.txt")]);
+}
+
+#[test]
+fn test_archive_with_index_records_and_searches() {
+    let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap());
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "hello")
+        .with_file("/p/docs/a.md", "abc");
+    let cfg = Config { index: true, clock: Arc::new(clock), ..Config::default() };
+    archive_target(Path::new("/p/notes.txt"), &cfg, &ops).unwrap();
+    archive_target(Path::new("/p/docs"), &cfg, &ops).unwrap();
+    let entries = index::read_index(Path::new("/p/archive"), &ops).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].size, 5);
+    // A directory's archive is one level up.
+    assert_eq!(index::read_index(Path::new("/archive"), &ops).unwrap()[0].size, 3);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["index-search", "NOTES", "--archive-dir", "/p/archive"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(std::mem::take(&mut out)).unwrap(), "2025-09-13T14:02:05\tnotes.txt\t/p/archive/notes.txt\n");
+    // Any other directory means its archive, and no directory the project root's.
+    run_with(args(&["index-search", "notes", "--archive-dir", "/p"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(std::mem::take(&mut out)).unwrap(), "2025-09-13T14:02:05\tnotes.txt\t/p/archive/notes.txt\n");
+    run_with(args(&["index-search", "docs", "--project-root", "/p"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "2025-09-13T14:02:05\tdocs\t/archive/docs\n");
+}
+
+#[test]
+fn test_archive_dir_onto_empty_destination() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/proj/notes.txt", "hi")
+        .with_dir("/archive/proj");
+    archive_move_dir_with(Path::new("/p/proj"), &ops).unwrap();
+    assert_eq!(ops.contents("/archive/proj/notes.txt").unwrap(), b"hi");
+    assert!(!ops.exists(Path::new("/p/proj")));
+    assert_eq!(ops.read_dir(Path::new("/archive")).unwrap(), [Path::new("/archive/proj")]);
+
+    let ops = MemoryFileOps::new()
+        .with_file("/p/proj/notes.txt", "hi")
+        .with_file("/archive/proj/old.txt", "old");
+    assert!(archive_move_dir_with(Path::new("/p/proj"), &ops).is_err());
+}
+
+#[test]
+fn test_create_confirm_prompt() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--confirm", "My", "Project"]), &mut "n\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(err).unwrap(), "project/my-project\ncreate? [Y/n] ");
+    assert!(out.is_empty());
+    assert!(!ops.exists(Path::new("project/my-project")));
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["create", "--confirm", "My", "Project"]), &mut "y\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/my-project\n");
+    assert!(ops.is_dir(Path::new("project/my-project")));
+}
+
+#[test]
+fn test_split_camel_case_acronyms() {
+    assert_eq!(slugify_title(&split_camel_case("getHTTPResponse", true)), "get-http-response");
+    assert_eq!(slugify_title(&split_camel_case("getHTTPResponse", false)), "get-h-t-t-p-response");
+    assert_eq!(split_camel_case("parseJSON", true), "parse JSON");
+    assert_eq!(split_camel_case("HTTPServer2Go", true), "HTTP Server2 Go");
+
+    let cfg = Config { split_case: true, keep_acronyms: true, ..Config::default() };
+    assert_eq!(project_slug("myAPIClient", &cfg).unwrap(), "my-api-client");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_archive_broken_symlink_warns() {
+    let dir = std::env::temp_dir().join(format!("slugpm-broken-link-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = std::fs::canonicalize(dir).unwrap();
+    std::os::unix::fs::symlink("gone.txt", dir.join("dead.txt")).unwrap();
+    assert_eq!(broken_link_target(&dir.join("dead.txt"), &RealFileOps).unwrap(), Some("gone.txt".into()));
+
+    let link = dir.join("dead.txt");
+    let strict = Config { no_follow: true, strict: true, ..Config::default() };
+    let results = archive_many(std::slice::from_ref(&link), &strict, &RealFileOps);
+    assert!(results[0].error.as_deref().unwrap().contains("symlink is broken"));
+    assert!(link.is_symlink());
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run(args(&["archive", "--no-follow", link.to_str().unwrap()]), &mut io::empty(), &mut out, &mut err).unwrap();
+    assert_eq!(code, 0);
+    assert!(String::from_utf8(err).unwrap().contains("warning: "));
+    assert!(dir.join("archive/dead.txt").is_symlink());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[derive(Debug)]
+struct CannedRunner(&'static str);
+impl CommandRunner for CannedRunner {
+    fn run(&self, cmd: &str, stdin: &[u8]) -> slugpm::Result<Vec<u8>> {
+        assert_eq!((cmd, stdin), ("my-slugger", &b"Q3 Report\n"[..]));
+        Ok(self.0.as_bytes().to_vec())
+    }
+
+    fn launch(&self, cmd: &str, _path: &Path) -> slugpm::Result<()> {
+        panic!("unexpected launch of {cmd:?}")
+    }
+}
+
+#[test]
+fn test_create_with_external_slugifier() {
+    let ops = MemoryFileOps::new();
+    let cfg = Config { slugify_cmd: Some("my-slugger".into()), runner: Arc::new(CannedRunner("q3_report\nignored\n")), ..Config::default() };
+    let dir = create_project_dir_with("Q3 Report", &cfg, &ops).unwrap();
+    assert_eq!(dir, Path::new("project/q3_report"));
+    assert!(ops.is_dir(&dir));
+
+    for bad in ["\n", "../escape\n", "a/b\n"] {
+        let cfg = Config { runner: Arc::new(CannedRunner(bad)), ..cfg.clone() };
+        assert!(create_project_dir_with("Q3 Report", &cfg, &ops).is_err());
+    }
+}
+
+#[test]
+fn test_interrupted_copy_removes_partial_destination() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/big/a.bin", "aaa")
+        .with_file("/p/big/b.bin", "bbb")
+        .with_file("/p/big/c.bin", "ccc");
+    let flag = std::sync::atomic::AtomicBool::new(false);
+    let checks = std::cell::Cell::new(0);
+    // Interrupt after the first file has been copied: it's checked before the file and after
+    // its one chunk.
+    let interrupted = || {
+        checks.set(checks.get() + 1);
+        if checks.get() == 3 {
+            flag.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+        flag.load(std::sync::atomic::Ordering::SeqCst)
+    };
+    let err = copy::copy_tree(Path::new("/p/big"), Path::new("/archive/big"), &ops, &interrupted, &copy::NoProgress).unwrap_err();
+    assert!(matches!(err, SlugpmError::Interrupted));
+    assert!(!ops.exists(Path::new("/archive/big")));
+    for name in ["a.bin", "b.bin", "c.bin"] {
+        assert!(ops.is_file(&Path::new("/p/big").join(name)));
+    }
+
+    copy::copy_tree(Path::new("/p/big"), Path::new("/archive/big"), &ops, &|| false, &copy::NoProgress).unwrap();
+    assert_eq!(ops.contents("/archive/big/c.bin").unwrap(), b"ccc");
+}
+
+#[test]
+fn test_cross_device_move_keeps_links_and_modes() {
+    let time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    let ops = MemoryFileOps::new()
+        .with_file("/p/tool/run.sh", "#!/bin/sh\n")
+        .with_mode("/p/tool/run.sh", 0o755)
+        .with_modified("/p/tool/run.sh", time)
+        .with_symlink("/p/tool/latest", "run.sh")
+        .with_symlink("/p/tool/shared", "/elsewhere/shared")
+        .with_mount("/nas");
+    move_path(Path::new("/p/tool"), Path::new("/nas/tool"), &ops).unwrap();
+    assert_eq!(ops.tree("/nas/tool"), ["latest -> run.sh", "run.sh", "shared -> /elsewhere/shared"]);
+    assert_eq!(ops.mode("/nas/tool/run.sh"), Some(0o755));
+    assert_eq!(ops.modified(Path::new("/nas/tool/run.sh")).unwrap(), time);
+    assert!(!ops.exists(Path::new("/p/tool")));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_copy_tree_recreates_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("slugpm-copy-links-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/run.sh"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(dir.join("src/run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    // A link back up the tree would make a following copy recurse forever.
+    std::os::unix::fs::symlink("..", dir.join("src/up")).unwrap();
+    copy::copy_tree(&dir.join("src"), &dir.join("dest"), &RealFileOps, &|| false, &copy::NoProgress).unwrap();
+    assert_eq!(std::fs::read_link(dir.join("dest/up")).unwrap(), Path::new(".."));
+    assert_eq!(std::fs::metadata(dir.join("dest/run.sh")).unwrap().permissions().mode() & 0o777, 0o755);
+    let modified = |p: &str| std::fs::metadata(dir.join(p)).unwrap().modified().unwrap();
+    assert_eq!(modified("dest/run.sh"), modified("src/run.sh"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_restore_reverses_archive() {
+    assert_eq!(restore_dest_pure(Path::new("/p/archive/invoices/a.pdf"), false), Some("/p/a.pdf".into()));
+    assert_eq!(restore_dest_pure(Path::new("/archive/proj"), true), Some("/project/proj".into()));
+    assert_eq!(restore_dest_pure(Path::new("/p/notes.txt"), false), None);
+
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "hi")
+        .with_file("/root/project/proj/todo.md", "x");
+    let cfg = Config { protect: true, ..Config::default() };
+    let archived = archive_file(Path::new("/p/notes.txt"), &cfg, &ops).unwrap();
+    assert_eq!(restore_target(&archived, None, &Config::default(), &ops).unwrap(), Path::new("/p/notes.txt"));
+    assert_eq!(ops.contents("/p/notes.txt").unwrap(), b"hi");
+    assert!(!ops.exists(Path::new("/p/archive/notes.txt.sha256")));
+
+    let archived = archive_dir(Path::new("/root/project/proj"), &cfg, &ops).unwrap();
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["restore", archived.to_str().unwrap()]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "/root/project/proj\n");
+    assert!(ops.is_file(Path::new("/root/project/proj/todo.md")));
+
+    let ops = ops.with_file("/p/archive/notes.txt", "again");
+    assert!(restore_target(Path::new("/p/archive/notes.txt"), None, &Config::default(), &ops).unwrap_err().to_string().contains("already exists"));
+}
+
+#[test]
+fn test_archive_collision_strategies() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "new")
+        .with_file("/p/archive/notes.txt", "old")
+        .with_file("/p/archive/notes-2.txt", "older");
+    assert!(archive_move_file_with_policy(Path::new("/p/notes.txt"), ConflictPolicy::Error, &ops).is_err());
+    let dest = archive_move_file_with_policy(Path::new("/p/notes.txt"), ConflictPolicy::Number, &ops).unwrap();
+    assert_eq!(dest, Path::new("/p/archive/notes-3.txt"));
+
+    let ops = ops.with_file("/p/notes.txt", "newest");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    assert_eq!(run_with(args(&["archive", "--force", "/p/notes.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap(), 0);
+    assert_eq!(ops.contents("/p/archive/notes.txt").unwrap(), b"newest");
+}
+
+#[test]
+fn test_errors_are_matchable() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "new")
+        .with_file("/p/archive/notes.txt", "old")
+        .with_dir("/w/project/alpha")
+        .with_mount("/nas");
+    let cfg = Config::default();
+    let err = archive_target(Path::new("/p/notes.txt"), &cfg, &ops).unwrap_err();
+    assert!(matches!(&err, SlugpmError::Collision(dest) if dest == Path::new("/p/archive/notes.txt")), "{err:?}");
+    let err = archive_target(Path::new("/p/archive/notes.txt"), &cfg, &ops).unwrap_err();
+    assert!(matches!(err, SlugpmError::AlreadyArchived(_)), "{err:?}");
+    let cfg = Config { project_root: "/w/project".into(), ..cfg };
+    assert!(matches!(resolve_project("alpah", &cfg, &ops), Err(SlugpmError::NotFound(_))));
+    assert!(matches!(category_segment("a/b"), Err(SlugpmError::InvalidName(_))));
+    let err = ops.rename(Path::new("/p/notes.txt"), Path::new("/nas/notes.txt")).unwrap_err();
+    assert!(is_cross_device(&err) && matches!(err, SlugpmError::Io { .. }), "{err:?}");
+}
+
+#[test]
+fn test_undo_reverts_most_recent_change() {
+    let ops = MemoryFileOps::new().with_file("/w/project/alpha/notes.txt", "hi").with_dir("/w/project/alpha/old");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str], input: &str| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let mut list = list.to_vec();
+        list.extend(["--project-root", "/w/project"]);
+        let code = run_with(args(&list), &mut input.as_bytes(), &mut out, &mut err, &ctx).unwrap();
+        (code, String::from_utf8(out).unwrap())
+    };
+    run(&["create", "Beta"], "");
+    run(&["archive", "/w/project/alpha/notes.txt", "/w/project/alpha/old"], "");
+    run(&["archive", "--append", "/w/project/alpha/log.txt"], "one\n");
+    run(&["archive", "--append", "/w/project/alpha/log.txt"], "two\n");
+    let journal = journal::read_journal(&Config { project_root: "/w/project".into(), ..Config::default() }, &ops).unwrap();
+    assert_eq!(journal.len(), 5);
+    assert!(matches!(&journal[0].op, journal::Operation::Create { path, files } if path == Path::new("/w/project/beta") && files.contains_key(Path::new("project.md"))));
+
+    assert_eq!(run(&["undo"], ""), (0, "undid append to /w/project/alpha/archive/log.txt\n".to_string()));
+    assert_eq!(ops.contents("/w/project/alpha/archive/log.txt").unwrap(), b"one\n");
+    run(&["undo"], "");
+    assert!(!ops.exists(Path::new("/w/project/alpha/archive/log.txt")));
+    run(&["undo"], "");
+    assert!(ops.is_dir(Path::new("/w/project/alpha/old")));
+    run(&["undo"], "");
+    assert_eq!(ops.contents("/w/project/alpha/notes.txt").unwrap(), b"hi");
+    run(&["undo"], "");
+    assert!(!ops.exists(Path::new("/w/project/beta")));
+    assert!(!ops.exists(Path::new("/w/project/.slugpm-journal")));
+    let mut err = Vec::new();
+    assert_eq!(run_with(args(&["undo", "--project-root", "/w/project"]), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap(), EXIT_NOT_FOUND);
+    assert_eq!(String::from_utf8(err).unwrap(), "error: nothing to undo\n");
+}
+
+#[test]
+fn test_undo_append_keeps_later_writes() {
+    let ops = MemoryFileOps::new().with_dir("/w/project").with_dir("/w/archive");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+    archive_append_with(Path::new("/w/notes.txt"), &mut "first\n".as_bytes(), &cfg, &ops).unwrap();
+    // Written by something else, which the journal doesn't know about.
+    ops.append_locked(Path::new("/w/archive/notes.txt"), b"second\n").unwrap();
+    let e = journal::undo_last(&cfg, &ops).unwrap_err();
+    assert!(e.to_string().contains("has changed since the append"), "{e}");
+    assert_eq!(ops.contents("/w/archive/notes.txt").unwrap(), b"first\nsecond\n");
+
+    // Same length, different bytes.
+    ops.write(Path::new("/w/archive/notes.txt"), b"FIRST\n").unwrap();
+    assert!(journal::undo_last(&cfg, &ops).is_err());
+    ops.write(Path::new("/w/archive/notes.txt"), b"first\n").unwrap();
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(!ops.exists(Path::new("/w/archive/notes.txt")));
+
+    // Journals written before the appended bytes were recorded still undo.
+    ops.write(Path::new("/w/archive/notes.txt"), b"old\nnew\n").unwrap();
+    journal::record(journal::Operation::Append { path: "/w/archive/notes.txt".into(), previous_len: Some(4), appended: None, sha256: None }, &cfg, &ops).unwrap();
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/archive/notes.txt").unwrap(), b"old\n");
+}
+
+#[test]
+fn test_list_projects_with_archived() {
+    let at = |secs| std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/beta")
+        .with_dir("/w/project/alpha")
+        .with_file("/w/project/stray.txt", "")
+        .with_dir("/w/archive/old")
+        .with_modified("/w/project/alpha", at(30))
+        .with_modified("/w/project/beta", at(20))
+        .with_modified("/w/archive/old", at(10));
+    let slugs = |entries: Vec<ProjectEntry>| entries.into_iter().map(|e| e.slug).collect::<Vec<_>>();
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    assert_eq!(slugs(list_projects(false, ProjectSort::Modified, &cfg, &ops).unwrap()), ["beta", "alpha"]);
+    assert_eq!(slugs(list_projects(true, ProjectSort::Modified, &cfg, &ops).unwrap()), ["old", "beta", "alpha"]);
+    let entries = list_projects(true, ProjectSort::Name, &cfg, &ops).unwrap();
+    assert_eq!(entries[2], ProjectEntry { slug: "old".into(), path: "/w/archive/old".into(), archived: true, modified: at(10), title: None, created: None, tags: vec![], status: status::ProjectStatus::Archived });
+    let cfg = Config { project_root: "/empty/project".into(), ..Config::default() };
+    assert!(list_projects(true, ProjectSort::Name, &cfg, &ops).unwrap().is_empty());
+}
+
+#[test]
+fn test_status_counts_and_stale_projects() {
+    use chrono::TimeZone;
+    let day = |d| chrono::Local.from_local_datetime(&NaiveDate::from_ymd_opt(2025, 9, d).unwrap().and_hms_opt(12, 0, 0).unwrap()).unwrap().into();
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/alpha/notes.md", "")
+        .with_dir("/w/project/beta")
+        .with_dir("/w/archive/old")
+        .with_modified("/w/project/alpha", day(1))
+        .with_modified("/w/project/alpha/notes.md", day(12))
+        .with_modified("/w/project/beta", day(2));
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let cfg = Config { project_root: "/w/project".into(), clock: Arc::new(FixedClock(now)), ..Config::default() };
+    let report = status::project_status(10, &cfg, &ops).unwrap();
+    assert_eq!((report.active, report.archived), (2, 1));
+    assert_eq!(report.most_recent.unwrap().slug, "alpha");
+    assert_eq!(report.stale.iter().map(|p| (p.slug.as_str(), p.idle_days)).collect::<Vec<_>>(), [("beta", 11)]);
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    run_with(args(&["status", "--project-root", "/w/project", "--stale-days", "0"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("active: 2\narchived: 1\nmost recent: alpha ("), "{text}");
+    assert!(text.contains("untouched for 0+ days:\n  beta ("), "{text}");
+}
+
+#[test]
+fn test_config_file_layout_with_flag_override() {
+    let file = config_file::parse_config_file("project_root = \"/w/projects\"\narchive_dir_name = \"attic\"\nslug_separator = \"_\"\n").unwrap();
+    assert_eq!(file.date_format, None);
+    assert!(config_file::parse_config_file("archive = \"x\"\n").is_err());
+    assert!(config_file::parse_config_file("archive_dir_name = \"a/b\"\n").unwrap().apply(&mut Config::default()).is_err());
+
+    let ops = MemoryFileOps::new()
+        .with_file("/etc/slugpm.toml", "project_root = \"/w/projects\"\narchive_dir_name = \"attic\"\nslug_separator = \"_\"\n")
+        .with_file("/w/notes.txt", "hi");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--config", "/etc/slugpm.toml", "My Project"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    run_with(args(&["archive", "/w/notes.txt", "--config", "/etc/slugpm.toml", "--archive-dir-name", "old"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/projects/my_project\n/w/old/notes.txt\n");
+    assert!(ops.is_dir(Path::new("/w/projects/my_project")));
+}
+
+#[test]
+fn test_create_dated_project() {
+    let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap());
+    let ops = MemoryFileOps::new();
+    let cfg = Config { dated: true, clock: Arc::new(clock), ..Config::default() };
+    let dir = create_project_dir_with("My Project", &cfg, &ops).unwrap();
+    assert_eq!(dir, Path::new("project/2025-09-13-my-project"));
+    assert_eq!(strip_date_prefix(dir.file_name().unwrap().to_str().unwrap()), "my-project");
+
+    let cfg = Config { date_format: "%Y%m%d".into(), slug: SlugOptions { separator: "_".into(), ..SlugOptions::default() }, ..cfg };
+    assert_eq!(project_name("My Project", &cfg).unwrap(), "20250913_my_project");
+    assert!(config_file::parse_config_file("date_format = \"%Q\"\n").unwrap().apply(&mut Config::default()).is_err());
+}
+
+#[test]
+fn test_slug_options() {
+    let opts = SlugOptions { separator: "_".into(), ..SlugOptions::default() };
+    assert_eq!(slugify_title_with("Über Project!", &opts), "uber_project");
+    assert_eq!(slugify_title_with("Über Project!", &SlugOptions::default()), slugify_title("Über Project!"));
+    let opts = SlugOptions { max_len: Some(12), ..SlugOptions::default() };
+    assert_eq!(slugify_title_with("quarterly planning notes", &opts), "quarterly");
+    assert_eq!(slugify_title_with("Supercalifragilistic", &opts), "supercalifra");
+    let opts = SlugOptions { keep_case: true, stopwords: vec!["the".into(), "of".into()], ..SlugOptions::default() };
+    assert_eq!(slugify_title_with("The Lord of the Rings", &opts), "Lord-Rings");
+    assert_eq!(slugify_title_with("The", &opts), "The");
+    let german = SlugOptions { transliteration: Transliteration::German, ..SlugOptions::default() };
+    assert_eq!(slugify_title_with("Größe & Übermaß", &german), "groesse-uebermass");
+    assert_eq!(slugify_title_with("Größe & Übermaß", &SlugOptions::default()), "grosse-ubermass");
+    assert_eq!(slugify_title_with("北京 ひらがな", &SlugOptions::default()), "bei-jing-hiragana");
+    let unicode = SlugOptions { transliteration: Transliteration::Unicode, max_len: Some(5), ..SlugOptions::default() };
+    assert_eq!(slugify_title_with("Über Größe", &unicode), "über");
+    assert_eq!(slugify_title_with("Übermaß", &unicode), "über");
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: true };
+    run_with(args(&["slug", "--slug-stopwords", "The Lord of the Rings"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    run_with(args(&["slug", "--slug-stopwords=lord", "--slug-max-len", "10", "The Lord of the Rings"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "lord-rings\nthe-of-the\n");
+    let file = config_file::parse_config_file("slug_max_len = 20\nslug_keep_case = true\nslug_stopwords = [\"a\"]\n").unwrap();
+    let mut cfg = Config::default();
+    file.apply(&mut cfg).unwrap();
+    assert_eq!(cfg.slug, SlugOptions { max_len: Some(20), keep_case: true, stopwords: vec!["a".into()], ..SlugOptions::default() });
+
+    // The flags override a config file that keeps case, either way, and the last one wins.
+    let ops = MemoryFileOps::new().with_file("/c.toml", "slug_keep_case = true\n");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let slug = |flags: &[&str]| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let mut list = vec!["--config", "/c.toml", "slug"];
+        list.extend(flags);
+        list.push("My Title");
+        run_with(args(&list), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(slug(&[]), "My-Title\n");
+    assert_eq!(slug(&["--no-slug-keep-case"]), "my-title\n");
+    assert_eq!(slug(&["--no-slug-keep-case", "--slug-keep-case"]), "My-Title\n");
+}
+
+#[test]
+fn test_create_from_template() {
+    let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap());
+    let ops = MemoryFileOps::new()
+        .with_file("/t/rust/Cargo.toml", "[package]\nname = \"{{slug}}\"\n")
+        .with_file("/t/rust/src/{{slug}}.rs", "//! {{title}}, started {{date}}\n")
+        .with_file("/t/rust/logo.bin", vec![0xff, 0x7b, 0x7b]);
+    let cfg = Config { template: Some("rust".into()), templates_dir: Some("/t".into()), clock: Arc::new(clock), ..Config::default() };
+    let dir = create_project_dir_with("My Project", &cfg, &ops).unwrap();
+    assert_eq!(ops.contents(dir.join("Cargo.toml")).unwrap(), b"[package]\nname = \"my-project\"\n");
+    assert_eq!(ops.contents(dir.join("src/my-project.rs")).unwrap(), b"//! My Project, started 2025-09-13\n");
+    assert_eq!(ops.contents(dir.join("logo.bin")).unwrap(), [0xff, 0x7b, 0x7b]);
+
+    let cfg = Config { template: Some("go".into()), ..cfg };
+    assert!(create_project_dir_with("Other", &cfg, &ops).unwrap_err().to_string().contains("no template \"go\""));
+    assert!(!ops.exists(Path::new("project/other")));
+}
+
+#[test]
+fn test_template_names_stay_inside_the_project() {
+    let ops = MemoryFileOps::new().with_file("/t/notes/{{title}}.md", "# {{title}}\n").with_dir("/w/project");
+    let cfg = Config { project_root: "/w/project".into(), template: Some("notes".into()), templates_dir: Some("/t".into()), ..Config::default() };
+    let e = create_project_dir_with("../../../evil", &cfg, &ops).unwrap_err();
+    assert!(matches!(e, SlugpmError::InvalidName(_)), "{e}");
+    // Nothing is written outside the project, and the half-made project is removed.
+    assert_eq!(ops.files(), [Path::new("/t/notes/{{title}}.md")]);
+    assert!(!ops.exists(Path::new("/w/project/evil")));
+    assert!(create_project_dir_with("Fine", &cfg, &ops).is_ok());
+    assert!(ops.exists(Path::new("/w/project/fine/Fine.md")));
+}
+
+#[test]
+fn test_create_writes_project_metadata() {
+    let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap());
+    let ops = MemoryFileOps::new().with_file("/t/notes/project.md", "---\nowner: me\n---\n# {{title}}\n");
+    let cfg = Config { clock: Arc::new(clock), ..Config::default() };
+    let dir = create_project_dir_with("My Project: Part 2", &cfg, &ops).unwrap();
+    assert_eq!(ops.contents(dir.join("project.md")).unwrap(),
+               b"---\ntitle: My Project: Part 2\nslug: my-project-part-2\ncreated: 2025-09-13\nstatus: active\n---\n");
+
+    let cfg = Config { template: Some("notes".into()), templates_dir: Some("/t".into()), ..cfg };
+    let dir = create_project_dir_with("Other", &cfg, &ops).unwrap();
+    let meta = metadata::read_metadata(&dir, &ops).unwrap().unwrap();
+    assert_eq!((meta.title.as_str(), meta.extra.as_slice(), meta.body.as_str()), ("Other", &[("owner".to_string(), "me".to_string())][..], "# Other\n"));
+
+    let meta = metadata::update_metadata(&dir, &ops, |m| m.status = "done".into()).unwrap();
+    assert_eq!(metadata::parse_metadata(&metadata::render_metadata(&meta)).unwrap(), meta);
+    assert_eq!(metadata::read_metadata(&dir, &ops).unwrap().unwrap().status, "done");
+
+    let titles: Vec<_> = list_projects(false, ProjectSort::Name, &Config::default(), &ops).unwrap().into_iter().map(|e| e.title).collect();
+    assert_eq!(titles, [Some("My Project: Part 2".to_string()), Some("Other".to_string())]);
+}
+
+#[test]
+fn test_rename_project_keeps_date_prefix() {
+    let ops = MemoryFileOps::new().with_file("/w/project/2025-09-13-old-name/notes.txt", "hi");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+    metadata::write_metadata(Path::new("/w/project/2025-09-13-old-name"),
+        &metadata::ProjectMeta { title: "Old Name".into(), slug: "2025-09-13-old-name".into(), ..Default::default() }, &ops).unwrap();
+
+    let dir = rename_project("2025-09-13-old-name", "New Name", false, &cfg, &ops).unwrap();
+    assert_eq!(dir, Path::new("/w/project/2025-09-13-new-name"));
+    assert_eq!(ops.contents(dir.join("notes.txt")).unwrap(), b"hi");
+    let meta = metadata::read_metadata(&dir, &ops).unwrap().unwrap();
+    assert_eq!((meta.title.as_str(), meta.slug.as_str(), meta.aliases()), ("New Name", "2025-09-13-new-name", vec!["2025-09-13-old-name"]));
+
+    let ops = ops.with_dir("/w/project/2025-09-13-taken");
+    assert!(matches!(rename_project("2025-09-13-new-name", "Taken", false, &Config { project_root: "/w/project".into(), ..Config::default() }, &ops),
+                     Err(SlugpmError::Collision(_))));
+
+    journal::undo_last(&cfg, &ops).unwrap();
+    let meta = metadata::read_metadata(Path::new("/w/project/2025-09-13-old-name"), &ops).unwrap().unwrap();
+    assert_eq!((meta.title.as_str(), meta.aliases()), ("Old Name", Vec::<&str>::new()));
+    assert!(!ops.exists(Path::new("/w/project/2025-09-13-new-name")));
+
+    #[cfg(unix)]
+    {
+        let root = std::env::temp_dir().join(format!("slugpm-rename-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("alpha")).unwrap();
+        let cfg = Config { project_root: root.clone(), ..Config::default() };
+        rename_project("alpha", "Beta", true, &cfg, &RealFileOps).unwrap();
+        assert_eq!(std::fs::read_link(root.join("alpha")).unwrap(), Path::new("beta"));
+        assert!(root.join("alpha").is_dir());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+#[test]
+fn test_fuzzy_rank_orders_candidates() {
+    let candidates = ["my-project-notes", "my-project", "old-my-project", "2025-09-13-my-proj", "mpj", "unrelated"];
+    assert_eq!(fuzzy_rank("My Project", candidates), ["my-project", "my-project-notes", "old-my-project"]);
+    // Exact after the date prefix, then prefix, then substring, then in-order letters.
+    assert_eq!(fuzzy_rank("my proj", candidates), ["2025-09-13-my-proj", "my-project", "my-project-notes", "old-my-project"]);
+    assert_eq!(fuzzy_rank("notes project", candidates), ["my-project-notes"]);
+    assert_eq!(fuzzy_rank("mpj", candidates), ["mpj", "my-project", "old-my-project", "my-project-notes", "2025-09-13-my-proj"]);
+    assert!(fuzzy_rank("  ", candidates).is_empty());
+}
+
+#[test]
+fn test_open_prints_best_match() {
+    let ops = MemoryFileOps::new().with_dir("project/my-project").with_dir("project/my-project-notes");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["open", "my", "proj"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/my-project\n");
+    assert!(find_project("zzz", &Config::default(), &ops).unwrap_err().to_string().contains("matches \"zzz\""));
+}
+
+#[test]
+fn test_json_output() {
+    let ops = MemoryFileOps::new().with_file("/p/notes.txt", "hi");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let json_lines = |list: &[&str]| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run_with(args(list), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+        let lines = String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect::<Vec<serde_json::Value>>();
+        (code, lines)
+    };
+    let (_, created) = json_lines(&["--json", "My Project"]);
+    assert_eq!(created, [serde_json::json!({ "action": "create", "title": "My Project", "slug": "my-project", "path": "project/my-project", "created": true })]);
+
+    let (code, archived) = json_lines(&["archive", "--json", "/p/notes.txt", "/p/missing.txt"]);
+    assert_eq!(code, 0);
+    assert_eq!(archived[0]["dest"], "/p/archive/notes.txt");
+    assert!(archived[1]["dest"].is_null());
+    assert!(archived[1]["error"].as_str().unwrap().contains("missing.txt"));
+    assert_eq!((&archived[1]["kind"], &archived[1]["code"]), (&serde_json::json!("not_found"), &serde_json::json!(EXIT_NOT_FOUND)));
+
+    let (_, names) = json_lines(&["name", "--json", "2025-09-13-my-project"]);
+    assert_eq!(names, [serde_json::json!({ "input": "2025-09-13-my-project", "date": "2025-09-13", "name": "my-project", "slug": "my-project" })]);
+}
+
+#[test]
+fn test_create_only_runs_for_create() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["slug", "My", "Project"]), &mut "Piped Title\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert!(!ops.exists(Path::new("project")));
+
+    run_with(args(&["create"]), &mut "Piped Title\nsecond line\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "my-project\nproject/piped-title\n");
+    let dirs: Vec<_> = ops.read_dir(Path::new("project")).unwrap().into_iter().filter(|p| ops.is_dir(p)).collect();
+    assert_eq!(dirs, [Path::new("project/piped-title")]);
+}
+
+#[test]
+fn test_archive_by_project_slug() {
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(suggest_similar("my-projet", ["my-project", "other", "my-projects"]), ["my-project", "my-projects"]);
+
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/my-project/todo.md", "x")
+        .with_dir("/w/project/other");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "--project-root", "/w/project", "--project", "my-projet"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_NOT_FOUND);
+    assert!(String::from_utf8(std::mem::take(&mut err)).unwrap().contains("did you mean my-project?"));
+
+    let code = run_with(args(&["archive", "--project-root", "/w/project", "--project", "my-project"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/archive/my-project\n");
+    assert!(ops.is_file(Path::new("/w/archive/my-project/todo.md")));
+}
+
+#[test]
+fn test_dry_run_changes_nothing() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.txt", "hi")
+        .with_file("/p/docs/a.md", "a")
+        .with_file("/p/archive/old.txt", "old");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "--dry-run", "--index", "/p/notes.txt", "/p/docs"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/archive/notes.txt\n/archive/docs\n");
+    let err = String::from_utf8(err).unwrap();
+    assert!(err.contains("would move /p/notes.txt -> /p/archive/notes.txt\n"));
+    assert!(err.contains("would create directory /archive\n"));
+    assert!(ops.is_file(Path::new("/p/notes.txt")));
+    assert!(!ops.exists(Path::new("/archive")));
+    assert!(!ops.exists(Path::new("/p/archive/.index.json")));
+
+    let dry = DryRunFileOps::new(&ops);
+    archive_dir(Path::new("/p/docs"), &Config::default(), &dry).unwrap();
+    assert_eq!(dry.read(Path::new("/archive/docs/a.md")).unwrap(), b"a");
+    assert!(!dry.exists(Path::new("/p/docs/a.md")));
+    restore_target(Path::new("/p/archive/old.txt"), None, &Config::default(), &dry).unwrap();
+    assert_eq!(dry.actions().last().unwrap(), "move /p/archive/old.txt -> /p/old.txt");
+    assert!(ops.is_file(Path::new("/p/archive/old.txt")));
+}
+
+#[test]
+fn test_archive_across_devices_copies_then_removes() {
+    let ops = MemoryFileOps::new()
+        .with_file("/home/p/proj/a.txt", "a")
+        .with_file("/home/p/proj/sub/b.txt", "b")
+        .with_file("/home/p/notes.txt", "n")
+        .with_mount("/home/archive")
+        .with_mount("/home/p/archive");
+    assert!(is_cross_device(&ops.rename(Path::new("/home/p/notes.txt"), Path::new("/home/p/archive/notes.txt")).unwrap_err()));
+
+    assert_eq!(archive_file(Path::new("/home/p/notes.txt"), &Config::default(), &ops).unwrap(), Path::new("/home/p/archive/notes.txt"));
+    assert_eq!(ops.contents("/home/p/archive/notes.txt").unwrap(), b"n");
+    assert!(!ops.exists(Path::new("/home/p/notes.txt")));
+
+    archive_dir(Path::new("/home/p/proj"), &Config::default(), &ops).unwrap();
+    assert_eq!(ops.contents("/home/archive/proj/sub/b.txt").unwrap(), b"b");
+    assert!(!ops.exists(Path::new("/home/p/proj")));
+}
+
+#[test]
+fn test_append_from_file() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/log.md", "")
+        .with_file("/p/archive/log.md", "old\n")
+        .with_file("/tmp/scratch.md", "new\n");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["archive", "/p/log.md", "--from", "/tmp/scratch.md"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(ops.contents("/p/archive/log.md").unwrap(), b"old\nnew\n");
+    assert!(ops.exists(Path::new("/tmp/scratch.md")));
+
+    run_with(args(&["archive", "/p/log.md", "--from", "/tmp/scratch.md", "--delete-source", "--print-source"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(ops.contents("/p/archive/log.md").unwrap(), b"old\nnew\nnew\n");
+    assert!(!ops.exists(Path::new("/tmp/scratch.md")));
+    assert_eq!(String::from_utf8(out).unwrap(), "/p/archive/log.md\n/tmp/scratch.md\t/p/archive/log.md\n");
+}
+
+#[test]
+fn test_completions_script_and_project_slugs() {
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/2025-01-02-beta")
+        .with_dir("/w/project/alpha")
+        .with_file("/w/project/notes.md", "")
+        .with_dir("/w/archive/old");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    assert_eq!(project_slugs(&cfg, &ops).unwrap(), ["2025-01-02-beta", "alpha"]);
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    assert_eq!(run_with(args(&["completions", "zsh"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap(), 0);
+    let script = String::from_utf8(out).unwrap();
+    assert!(script.contains("COMPLETE=\"zsh\"") && script.contains("slugpm"), "{script}");
+    assert_ne!(run_with(args(&["completions", "tcsh"]), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap(), 0);
+}
+
+/// A [`vcs::Vcs`] that records what it was asked to do instead of running git.
+#[derive(Debug, Default)]
+struct RecordingVcs {
+    repo: Option<std::path::PathBuf>,
+    calls: std::sync::Mutex<Vec<String>>,
+}
+
+impl vcs::Vcs for RecordingVcs {
+    fn init(&self, dir: &Path) -> Result<()> {
+        self.calls.lock().unwrap().push(format!("init {}", dir.display()));
+        Ok(())
+    }
+
+    fn repo_root(&self, _dir: &Path) -> Option<std::path::PathBuf> {
+        self.repo.clone()
+    }
+
+    fn commit(&self, repo: &Path, paths: &[std::path::PathBuf], message: &str) -> Result<()> {
+        let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+        self.calls.lock().unwrap().push(format!("commit {} {message}: {}", repo.display(), paths.join(" ")));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_git_init_on_create_and_commit_on_archive_and_restore() {
+    let ops = MemoryFileOps::new().with_dir("/w/project").with_file("/elsewhere/notes.txt", "");
+    let git = Arc::new(RecordingVcs { repo: Some("/w".into()), ..RecordingVcs::default() });
+    let cfg = Config { project_root: "/w/project".into(), git: true, vcs: git.clone(), ..Config::default() };
+    let dir = create_project_dir_with("Hello World", &cfg, &ops).unwrap();
+    let archived = archive_target(&dir, &cfg, &ops).unwrap().dest;
+    restore_target(&archived, None, &cfg, &ops).unwrap();
+    // Outside the repository, nothing is committed.
+    archive_target(Path::new("/elsewhere/notes.txt"), &cfg, &ops).unwrap();
+    assert_eq!(*git.calls.lock().unwrap(), [
+        "init /w/project/hello-world",
+        "commit /w Archive hello-world: /w/project/hello-world /w/archive/hello-world",
+        "commit /w Restore hello-world: /w/archive/hello-world /w/project/hello-world",
+    ]);
+
+    let off = Arc::new(RecordingVcs { repo: Some("/w".into()), ..RecordingVcs::default() });
+    let cfg = Config { git: false, vcs: off.clone(), ..cfg };
+    create_project_dir_with("Other", &cfg, &ops).unwrap();
+    assert!(off.calls.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_normalize_lexically() {
+    assert_eq!(normalize_lexically(Path::new("/w/./project/../link/")), Path::new("/w/link"));
+    assert_eq!(normalize_lexically(Path::new("/../a")), Path::new("/a"));
+    assert_eq!(normalize_lexically(Path::new("../a/./b/..")), Path::new("../a"));
+    assert_eq!(normalize_lexically(Path::new("a/../../b")), Path::new("../b"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_archive_symlinked_project_moves_link_or_target() {
+    let dir = std::env::temp_dir().join(format!("slugpm-symlinked-project-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("project")).unwrap();
+    std::fs::create_dir_all(dir.join("real/notes")).unwrap();
+    std::fs::write(dir.join("real/notes/todo.md"), "x").unwrap();
+    let dir = std::fs::canonicalize(dir).unwrap();
+    let link = dir.join("project/notes");
+    std::os::unix::fs::symlink(dir.join("real/notes"), &link).unwrap();
+
+    // With `no_follow` the link moves to where the directory would, and the target stays put.
+    let cfg = Config { no_follow: true, ..Config::default() };
+    let results = archive_many(std::slice::from_ref(&dir.join("project/./notes")), &cfg, &RealFileOps);
+    assert_eq!(results[0].dest.as_deref(), Some(dir.join("archive/notes").as_path()), "{:?}", results[0].error);
+    assert!(dir.join("archive/notes").is_symlink() && !link.exists());
+    assert!(dir.join("real/notes/todo.md").is_file());
+    let compress = Config { compress: true, ..cfg.clone() };
+    assert!(archive_dir(&dir.join("archive/notes"), &compress, &RealFileOps).is_err());
+
+    // Restoring an archived link restores the link.
+    let cfg = Config { project_root: dir.join("project"), ..cfg };
+    assert_eq!(restore_target(&dir.join("archive/notes"), None, &cfg, &RealFileOps).unwrap(), link);
+    assert!(link.is_symlink() && dir.join("real/notes/todo.md").is_file());
+
+    // By default the link is followed, so the target directory is archived.
+    let results = archive_many(std::slice::from_ref(&link), &Config::default(), &RealFileOps);
+    assert_eq!(results[0].dest.as_deref(), Some(dir.join("archive/notes").as_path()));
+    assert!(!dir.join("archive/notes").is_symlink() && !dir.join("real/notes").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_memory_file_ops_tracks_the_tree() {
+    let ops = MemoryFileOps::new().with_file("/w/project/notes.txt", "old").with_dir("/w/project/alpha");
+    archive_target(Path::new("/w/project/alpha"), &Config { project_root: "/w/project".into(), ..Config::default() }, &ops).unwrap();
+    archive_append_with(Path::new("/w/project/notes.txt"), &mut "more".as_bytes(), &Config::default(), &ops).unwrap();
+    assert_eq!(ops.tree("/w"), ["archive/", "archive/alpha/", "project/", "project/archive/", "project/archive/notes.txt", "project/notes.txt"]);
+    assert_eq!(ops.contents("/w/project/archive/notes.txt").unwrap(), b"more");
+    assert_eq!(ops.files(), [Path::new("/w/project/archive/notes.txt"), Path::new("/w/project/notes.txt")]);
+
+    // Clones share the tree, and missing parents fail as they would on disk.
+    let clone = ops.clone();
+    assert!(clone.write(Path::new("/w/missing/a.txt"), b"").is_err());
+    assert!(clone.open_append(Path::new("/w/missing/a.txt")).is_err());
+    assert!(clone.rename(Path::new("/w/project/notes.txt"), Path::new("/w/missing/notes.txt")).is_err());
+    clone.rename(Path::new("/w/project/notes.txt"), Path::new("/w/archive/notes.txt")).unwrap();
+    assert!(ops.is_file(Path::new("/w/archive/notes.txt")) && !ops.exists(Path::new("/w/project/notes.txt")));
+}
+
+#[test]
+fn test_file_ops_metadata_read_to_string_and_remove_dir_all() {
+    let dir = std::env::temp_dir().join(format!("slugpm-file-ops-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("tree/sub")).unwrap();
+    let memory = MemoryFileOps::new().with_dir(&dir);
+    for ops in [&RealFileOps as &dyn FileOps, &memory] {
+        ops.create_dir_all(&dir.join("tree/sub")).unwrap();
+        ops.write(&dir.join("tree/sub/a.txt"), "héllo".as_bytes()).unwrap();
+        ops.write(&dir.join("tree/bad.bin"), &[0xff, 0xfe]).unwrap();
+        assert_eq!(ops.read_to_string(&dir.join("tree/sub/a.txt")).unwrap(), "héllo");
+        assert!(ops.read_to_string(&dir.join("tree/bad.bin")).is_err());
+
+        let file = ops.metadata(&dir.join("tree/sub/a.txt")).unwrap();
+        assert_eq!((file.len, file.is_dir), (6, false));
+        assert_eq!(file.modified, ops.modified(&dir.join("tree/sub/a.txt")).unwrap());
+        assert!(ops.metadata(&dir.join("tree")).unwrap().is_dir);
+        assert!(ops.metadata(&dir.join("missing")).is_err());
+
+        // A dry run only records the removal.
+        let dry = DryRunFileOps::new(ops);
+        dry.remove_dir_all(&dir.join("tree")).unwrap();
+        assert!(!dry.exists(&dir.join("tree/sub/a.txt")) && ops.exists(&dir.join("tree/sub/a.txt")));
+        assert_eq!(dry.actions(), [format!("remove directory {} and its contents", dir.join("tree").display())]);
+
+        ops.remove_dir_all(&dir.join("tree")).unwrap();
+        assert!(!ops.exists(&dir.join("tree")) && !ops.exists(&dir.join("tree/sub/a.txt")));
+        assert!(ops.is_dir(&dir));
+    }
+    assert_eq!(memory.tree(&dir), Vec::<String>::new());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_concurrent_appends_do_not_interleave() {
+    let dir = std::env::temp_dir().join(format!("slugpm-locked-append-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("log.txt");
+    let line = |writer: usize| format!("{writer}:{}\n", "x".repeat(64 * 1024));
+    std::thread::scope(|scope| {
+        for writer in 0..8 {
+            let (log, line) = (&log, line(writer));
+            scope.spawn(move || archive_append_with(log, &mut line.as_bytes(), &Config::default(), &RealFileOps).unwrap());
+        }
+    });
+    let mut written: Vec<String> = std::fs::read_to_string(dir.join("archive/log.txt")).unwrap().lines().map(|l| format!("{l}\n")).collect();
+    written.sort();
+    assert_eq!(written, (0..8).map(line).collect::<Vec<_>>());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let ops = MemoryFileOps::new().with_file("/w/log.txt", "ab");
+    assert_eq!(ops.append_locked(Path::new("/w/log.txt"), b"cd").unwrap(), 2);
+    assert_eq!(ops.contents("/w/log.txt").unwrap(), b"abcd");
+}
+
+#[test]
+fn test_title_from_slug_and_metadata() {
+    let small: Vec<String> = DEFAULT_SMALL_WORDS.iter().map(|w| w.to_string()).collect();
+    assert_eq!(title_from_slug("2025-09-13-my-cool-project", "-", &small), "My Cool Project");
+    assert_eq!(title_from_slug("the-lord-of-the-rings", "-", &small), "The Lord of the Rings");
+    assert_eq!(title_from_slug("what-to-look-for", "-", &small), "What to Look For");
+    assert_eq!(title_from_slug("über_café", "-", &[]), "Über Café");
+    assert_eq!(title_from_slug("a.b.c", ".", &[]), "A B C");
+
+    let ops = MemoryFileOps::new().with_dir("/w/project/2025-01-02-notes-on-rust");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    let dir = create_project_dir_with("Über Notes: Rust & C++", &cfg, &ops).unwrap();
+    assert_eq!(project_title(&dir, &small, &cfg, &ops).unwrap(), "Über Notes: Rust & C++");
+    assert_eq!(project_title(Path::new("2025-01-02-notes-on-rust"), &small, &cfg, &ops).unwrap(), "Notes on Rust");
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["title", "--project-root", "/w/project", "--capitalize-all", "2025-01-02-notes-on-rust", "a-tale-of-two"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "Notes On Rust\nA Tale Of Two\n");
+}
+
+#[test]
+fn test_note_appends_timestamped_lines() {
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(14, 2, 5).unwrap();
+    assert_eq!(note_line(now, "did the thing\nand more "), "- 2025-09-13 14:02 did the thing and more\n");
+
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha");
+    let cfg = Config { project_root: "/w/project".into(), clock: Arc::new(FixedClock(now)), journal: true, ..Config::default() };
+    assert_eq!(add_note("alpha", "first", &cfg, &ops).unwrap(), Path::new("/w/project/alpha/log.md"));
+    add_note("alpha", "second", &cfg, &ops).unwrap();
+    assert_eq!(String::from_utf8(ops.contents("/w/project/alpha/log.md").unwrap()).unwrap(), "- 2025-09-13 14:02 first\n- 2025-09-13 14:02 second\n");
+    assert!(add_note("alpha", "  ", &cfg, &ops).is_err());
+    assert!(matches!(add_note("beta", "x", &cfg, &ops), Err(SlugpmError::NotFound(_))));
+
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/project/alpha/log.md").unwrap(), b"- 2025-09-13 14:02 first\n");
+
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["note", "--project-root", "/w/project", "alpha"]), &mut "from stdin\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/project/alpha/log.md\n");
+    assert!(String::from_utf8(ops.contents("/w/project/alpha/log.md").unwrap()).unwrap().ends_with(" from stdin\n"));
+}
+
+#[test]
+fn test_today_creates_a_dated_note_once() {
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(9, 0, 0).unwrap();
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/templates");
+    let cfg = Config { project_root: "/w/project".into(), templates_dir: Some("/w/templates".into()), clock: Arc::new(FixedClock(now)), ..Config::default() };
+    let note = Path::new("/w/project/alpha/notes/2025-09-13.md");
+    assert_eq!(today_note("alpha", None, &cfg, &ops).unwrap(), (note.to_path_buf(), true));
+    assert_eq!(ops.contents(note).unwrap(), b"# 2025-09-13\n");
+    ops.write(note, b"edited").unwrap();
+    assert_eq!(today_note("alpha", None, &cfg, &ops).unwrap(), (note.to_path_buf(), false));
+    assert_eq!(ops.contents(note).unwrap(), b"edited");
+
+    // `templates/today.md` is the default template; others are picked by name.
+    ops.write(Path::new("/w/templates/today.md"), b"# {{title}}: {{date}}\n").unwrap();
+    ops.write(Path::new("/w/templates/standup.md"), b"## {{slug}} standup\n").unwrap();
+    let cfg = Config { clock: Arc::new(FixedClock(now + chrono::Duration::days(1))), ..cfg };
+    today_note("alpha", None, &cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/project/alpha/notes/2025-09-14.md").unwrap(), b"# Alpha: 2025-09-14\n");
+    let cfg = Config { clock: Arc::new(FixedClock(now + chrono::Duration::days(2))), ..cfg };
+    assert!(matches!(today_note("alpha", Some("missing"), &cfg, &ops), Err(SlugpmError::NotFound(_))));
+    today_note("alpha", Some("standup"), &cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/project/alpha/notes/2025-09-15.md").unwrap(), b"## alpha standup\n");
+}
+
+#[test]
+fn test_tags_are_kept_in_metadata_and_filter_list() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/project/beta").with_dir("/w/project/gamma");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+    assert_eq!(tags::add_tag("alpha", " Work ", &cfg, &ops).unwrap(), ["work"]);
+    assert_eq!(tags::add_tag("alpha", "rust", &cfg, &ops).unwrap(), ["work", "rust"]);
+    assert_eq!(tags::add_tag("alpha", "work", &cfg, &ops).unwrap(), ["work", "rust"]);
+    tags::add_tag("beta", "work", &cfg, &ops).unwrap();
+    assert!(String::from_utf8(ops.contents("/w/project/alpha/project.md").unwrap()).unwrap().contains("tags: work, rust\n"));
+    assert!(matches!(tags::add_tag("alpha", "a,b", &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+    assert!(matches!(tags::remove_tag("gamma", "work", &cfg, &ops), Err(SlugpmError::NotFound(_))));
+    assert_eq!(tags::tag_counts(&cfg, &ops).unwrap(), [("rust".to_string(), 1), ("work".to_string(), 2)]);
+
+    assert_eq!(tags::remove_tag("alpha", "WORK", &cfg, &ops).unwrap(), ["rust"]);
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(tags::project_tags("alpha", &cfg, &ops).unwrap(), ["work", "rust"]);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["list", "--project-root", "/w/project", "--tag", "Work"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "alpha\nbeta\n");
+    let mut out = Vec::new();
+    run_with(args(&["tag", "rm", "--project-root", "/w/project", "beta", "work"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let mut out = Vec::new();
+    run_with(args(&["tag", "list", "--project-root", "/w/project"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "rust\t1\nwork\t1\n");
+    assert!(!String::from_utf8(ops.contents("/w/project/beta/project.md").unwrap()).unwrap().contains("tags"));
+}
+
+#[test]
+fn test_search_finds_lines_in_project_notes() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/alpha/notes.md", "# Alpha\nfix the TODO list\n")
+        .with_file("/w/project/alpha/sub/log.txt", "nothing\ntodo: call back\n")
+        .with_file("/w/project/alpha/.hidden/x.md", "TODO")
+        .with_file("/w/project/alpha/data.bin", "TODO")
+        .with_file("/w/project/alpha/blob.md", "TODO\0")
+        .with_file("/w/project/beta/big.md", "TODO\n".repeat(10))
+        .with_file("/w/archive/old/notes.md", "old TODO\n");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    let run = |query: &str, opts: &search::SearchOptions| {
+        let mut found = Vec::new();
+        let stats = search::search_projects(query, opts, &cfg, &ops, &mut |m| { found.push(format!("{}:{}:{}", m.path.display(), m.line, m.text)); Ok(()) }).unwrap();
+        (found, stats.skipped)
+    };
+    let small = search::SearchOptions { max_file_size: 30, ..Default::default() };
+    let (found, skipped) = run("TODO", &small);
+    assert_eq!(found, ["/w/project/alpha/notes.md:2:fix the TODO list"]);
+    assert_eq!(skipped, [Path::new("/w/project/alpha/blob.md"), Path::new("/w/project/beta/big.md")]);
+    let (found, _) = run("todo", &search::SearchOptions { ignore_case: true, archived: true, ..small.clone() });
+    assert_eq!(found.len(), 3);
+    assert_eq!(found[2], "/w/archive/old/notes.md:1:old TODO");
+    let (found, _) = run(r"^\w+:", &search::SearchOptions { regex: true, ..small });
+    assert_eq!(found, ["/w/project/alpha/sub/log.txt:2:todo: call back"]);
+    assert!(search::query_pattern("(", &search::SearchOptions { regex: true, ..Default::default() }).is_err());
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["search", "--project-root", "/w/project", "call", "back"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/project/alpha/sub/log.txt:2:todo: call back\n");
+}
+
+#[test]
+fn test_recent_lists_used_projects_latest_first() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/project/beta").with_dir("/w/project/gamma");
+    let now = NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(9, 0, 0).unwrap();
+    let cfg = Config { project_root: "/w/project".into(), clock: Arc::new(FixedClock(now)), track_recent: true, ..Config::default() };
+    add_note("beta", "first", &cfg, &ops).unwrap();
+    add_note("alpha", "second", &cfg, &ops).unwrap();
+    add_note("beta", "third", &cfg, &ops).unwrap();
+    let slugs = |cfg: &Config| recent::recent_projects(cfg, &ops).unwrap().into_iter().map(|e| e.slug).collect::<Vec<_>>();
+    assert_eq!(slugs(&cfg), ["beta", "alpha"]);
+    let picker = pick::Picker::load(&cfg, &ops).unwrap();
+    assert_eq!(picker.visible().iter().map(|p| p.slug.as_str()).collect::<Vec<_>>()[..2], ["beta", "alpha"]);
+
+    archive_target(Path::new("/w/project/alpha"), &cfg, &ops).unwrap();
+    let entries = recent::recent_projects(&cfg, &ops).unwrap();
+    assert_eq!((entries[0].action.as_str(), entries[0].path.as_path()), ("archive", Path::new("/w/archive/alpha")));
+    // Untracked (as under --dry-run), nothing is noted.
+    add_note("gamma", "x", &Config { track_recent: false, ..cfg.clone() }, &ops).unwrap();
+    assert_eq!(slugs(&cfg), ["alpha", "beta"]);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["open", "--project-root", "/w/project", "gamma"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let mut out = Vec::new();
+    run_with(args(&["recent", "--project-root", "/w/project", "-n", "2"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().map(|l| l.rsplit('\t').next().unwrap()).collect::<Vec<_>>(), ["gamma", "alpha"]);
+    assert!(out.contains("\topen\tgamma\n"));
+}
+
+#[test]
+fn test_errors_map_to_stable_exit_codes() {
+    use std::io::ErrorKind;
+    assert_eq!(SlugpmError::not_found(Path::new("/x")).exit_code(), EXIT_NOT_FOUND);
+    assert_eq!(SlugpmError::Collision("/x".into()).exit_code(), EXIT_COLLISION);
+    assert_eq!(SlugpmError::AlreadyArchived("/x".into()).kind_name(), "already_archived");
+    assert_eq!(SlugpmError::InvalidName("..".into()).exit_code(), EXIT_INVALID_INPUT);
+    let denied = SlugpmError::Io { context: "writing /x".into(), error: ErrorKind::PermissionDenied.into() };
+    assert_eq!((denied.exit_code(), denied.kind_name()), (EXIT_PERMISSION_DENIED, "permission_denied"));
+    assert_eq!(io_exit(ErrorKind::Other), (EXIT_IO, "io"));
+    assert_eq!(SlugpmError::Other("x".into()).exit_code(), EXIT_FAILURE);
+
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/project/beta");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let mut err = Vec::new();
+        let code = run_with(args(list), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap();
+        (code, String::from_utf8(err).unwrap())
+    };
+    assert_eq!(run(&["note", "--project-root", "/w/project", "gamma", "x"]).0, EXIT_NOT_FOUND);
+    let (code, err) = run(&["rename", "--json", "--project-root", "/w/project", "alpha", "beta"]);
+    assert_eq!(code, EXIT_COLLISION);
+    let report: serde_json::Value = serde_json::from_str(&err).unwrap();
+    assert_eq!((report["error"].as_str(), report["code"].as_i64()), (Some("collision"), Some(EXIT_COLLISION.into())));
+    assert!(report["message"].as_str().unwrap().ends_with("/w/project/beta already exists"));
+}
+
+#[test]
+fn test_archive_refuses_archive_dirs_and_their_contents() {
+    assert_eq!(enclosing_archive_dir_pure(Path::new("/w/project/foo/archive"), "archive"), Some(Path::new("/w/project/foo/archive")));
+    assert_eq!(enclosing_archive_dir_pure(Path::new("/w/archive/site/notes.md"), "archive"), Some(Path::new("/w/archive")));
+    assert_eq!(enclosing_archive_dir_pure(Path::new("/p/old/archive/work/2025/2025-09-13/x.txt"), "old"), Some(Path::new("/p/old")));
+    assert_eq!(enclosing_archive_dir_pure(Path::new("/w/project/archived-notes"), "archive"), None);
+
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/foo/archive/a.txt", "a")
+        .with_file("/w/archive/site/notes.md", "n")
+        .with_file("/w/project/foo/b.txt", "b");
+    let cfg = Config::default();
+    let err = archive_target(Path::new("/w/project/foo/archive"), &cfg, &ops).unwrap_err();
+    assert!(matches!(&err, SlugpmError::InvalidName(m) if m.contains("--allow-nested")), "{err:?}");
+    assert!(matches!(archive_target(Path::new("/w/archive/site/notes.md"), &cfg, &ops), Err(SlugpmError::AlreadyArchived(_))));
+    assert!(matches!(archive_append_with(Path::new("/w/project/foo/archive/a.txt"), &mut "x".as_bytes(), &cfg, &ops), Err(SlugpmError::AlreadyArchived(_))));
+    assert!(ops.is_file(Path::new("/w/project/foo/archive/a.txt")));
+
+    let cfg = Config { allow_nested: true, ..cfg };
+    assert_eq!(archive_target(Path::new("/w/project/foo/archive"), &cfg, &ops).unwrap().dest, Path::new("/w/project/archive/archive"));
+    assert_eq!(archive_target(Path::new("/w/project/foo/b.txt"), &cfg, &ops).unwrap().dest, Path::new("/w/project/foo/archive/b.txt"));
+}
+
+#[test]
+fn test_workspace_selects_project_root() {
+    let config = "project_root = \"/w/project\"\n[workspaces.work]\nroot = \"/work/project\"\n[workspaces.home]\nroot = \"/home/project\"\n";
+    let file = config_file::parse_config_file(config).unwrap();
+    assert_eq!(file.workspace_root("work").unwrap(), Path::new("/work/project"));
+    let err = file.workspace_root("play").unwrap_err();
+    assert_eq!(err.to_string(), "no workspace \"play\" (known: home, work)");
+    assert!(config_file::parse_config_file("[workspaces.work]\npath = \"/x\"\n").is_err());
+
+    let ops = MemoryFileOps::new().with_file("/etc/slugpm.toml", config).with_dir("/work/project").with_dir("/w/project");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let mut out = Vec::new();
+        let code = run_with(args(list), &mut io::empty(), &mut out, &mut Vec::new(), &ctx).unwrap();
+        (code, String::from_utf8(out).unwrap())
+    };
+    assert_eq!(run(&["--config", "/etc/slugpm.toml", "-w", "work", "Plan"]).1, "/work/project/plan\n");
+    assert_eq!(run(&["--config", "/etc/slugpm.toml", "Other"]).1, "/w/project/other\n");
+    assert_eq!(run(&["list", "--config", "/etc/slugpm.toml", "--workspace", "work"]).1, "plan\n");
+    // An explicit --project-root still wins.
+    assert_eq!(run(&["list", "--config", "/etc/slugpm.toml", "-w", "work", "--project-root", "/w/project"]).1, "other\n");
+    assert_eq!(run(&["list", "--config", "/etc/slugpm.toml", "-w", "play"]).0, EXIT_NOT_FOUND);
+}
+
+#[test]
+fn test_env_overrides_sit_between_config_file_and_flags() {
+    let env = |vars: &'static [(&'static str, &'static str)]| move |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string());
+    let overrides = config_file::env_overrides(env(&[
+        ("SLUGPM_ARCHIVE_DIR", "attic"),
+        ("SLUGPM_SLUG_MAX_LEN", "12"),
+        ("SLUGPM_SLUG_STOPWORDS", "a, the"),
+        ("SLUGPM_SLUG_TRANSLITERATION", "German"),
+        ("SLUGPM_DATED", "yes"),
+        ("SLUGPM_GIT", ""),
+    ])).unwrap();
+    assert_eq!(overrides.archive_dir_name.as_deref(), Some("attic"));
+    assert_eq!(overrides.slug_max_len, Some(12));
+    assert_eq!(overrides.slug_stopwords, Some(vec!["a".to_string(), "the".to_string()]));
+    assert_eq!(overrides.slug_transliteration, Some(Transliteration::German));
+    assert_eq!((overrides.dated, overrides.git), (Some(true), None));
+    let err = config_file::env_overrides(env(&[("SLUGPM_DATED_ARCHIVE", "maybe")])).unwrap_err();
+    assert_eq!(err.to_string(), "SLUGPM_DATED_ARCHIVE=\"maybe\" must be true or false");
+    assert!(config_file::env_overrides(env(&[("SLUGPM_SLUG_MAX_LEN", "ten")])).is_err());
+
+    let file = config_file::parse_config_file("project_root = \"/file\"\ndate_format = \"%Y\"\narchive_dir_name = \"old\"\n[workspaces.work]\nroot = \"/work\"\n").unwrap();
+    let env = config_file::env_overrides(env(&[("SLUGPM_PROJECT_ROOT", "/env"), ("SLUGPM_DATE_FORMAT", "%Y%m")])).unwrap();
+    let flags = config_file::ConfigFile { date_format: Some("%d".into()), ..Default::default() };
+    let cfg = config_file::resolve_config(&file, &env, None, &config_file::ConfigFile::default()).unwrap();
+    assert_eq!((cfg.project_root.as_path(), cfg.date_format.as_str(), cfg.archive_dir_name.as_str()), (Path::new("/env"), "%Y%m", "old"));
+    let cfg = config_file::resolve_config(&file, &env, Some("work"), &flags).unwrap();
+    assert_eq!((cfg.project_root.as_path(), cfg.date_format.as_str()), (Path::new("/work"), "%d"));
+}
+
+#[test]
+fn test_create_no_create_prints_slug_and_path() {
+    let ops = MemoryFileOps::new().with_dir("/w/project");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let mut out = Vec::new();
+        run_with(args(list), &mut io::empty(), &mut out, &mut Vec::new(), &ctx).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(run(&["create", "--project-root", "/w/project", "--no-create", "My", "Project"]), "my-project\t/w/project/my-project\n");
+    assert_eq!(run(&["--project-root", "/w/project", "--no-create", "--json", "Plan"]),
+               "{\"action\":\"create\",\"created\":false,\"path\":\"/w/project/plan\",\"slug\":\"plan\",\"title\":\"Plan\"}\n");
+    assert_eq!(ops.tree(Path::new("/w/project")), Vec::<String>::new());
+
+    let record: serde_json::Value = serde_json::from_str(&run(&["create", "--project-root", "/w/project", "--json", "My Project"])).unwrap();
+    assert_eq!((record["slug"].as_str(), record["path"].as_str(), record["created"].as_bool()), (Some("my-project"), Some("/w/project/my-project"), Some(true)));
+}
+
+#[test]
+fn test_create_each_line_reports_skipped_duplicates() {
+    let ops = MemoryFileOps::new();
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--each-line"]), &mut "Alpha\n\nBeta\nalpha!\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "project/alpha\nproject/beta\n");
+    assert_eq!(String::from_utf8(err).unwrap(), "skipped duplicate: alpha!\ncreated 2 project(s), skipped 1 duplicate(s)\n");
+    assert!(ops.is_dir(Path::new("project/beta")));
+}
+
+#[test]
+fn test_create_refuses_an_existing_project_unless_reused_or_numbered() {
+    let ops = MemoryFileOps::new().with_file("/w/project/my-project/notes.md", "keep");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    assert!(matches!(create_project_dir_with("My Project", &cfg, &ops), Err(SlugpmError::Collision(p)) if p == Path::new("/w/project/my-project")));
+    assert_eq!(create_project_dir_with("My Project", &Config { reuse: true, ..cfg.clone() }, &ops).unwrap(), Path::new("/w/project/my-project"));
+    assert_eq!(ops.tree(Path::new("/w/project/my-project")), ["notes.md"]);
+    assert_eq!(create_project_dir_with("My Project", &Config { unique: true, ..cfg }, &ops).unwrap(), Path::new("/w/project/my-project-2"));
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    assert_eq!(run_with(args(&["--project-root", "/w/project", "My Project"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap(), EXIT_COLLISION);
+    run_with(args(&["--project-root", "/w/project", "--reuse", "--json", "My Project"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!((record["path"].as_str(), record["created"].as_bool()), (Some("/w/project/my-project"), Some(false)));
+}
+
+#[test]
+fn test_status_lifecycle_filters_lists_and_sweeps_archives() {
+    use status::ProjectStatus;
+    assert_eq!(ProjectStatus::parse(" Done "), Some(ProjectStatus::Done));
+    assert_eq!(ProjectStatus::parse("someday"), None);
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/project/beta").with_dir("/w/project/gamma");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+    assert_eq!(status::set_project_status("beta", ProjectStatus::Done, &cfg, &ops).unwrap(), ProjectStatus::Active);
+    assert_eq!(status::set_project_status("gamma", ProjectStatus::Paused, &cfg, &ops).unwrap(), ProjectStatus::Active);
+    assert_eq!(status::set_project_status("gamma", ProjectStatus::Done, &cfg, &ops).unwrap(), ProjectStatus::Paused);
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(matches!(status::set_project_status("alpha", ProjectStatus::Archived, &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+    let statuses = list_projects(false, ProjectSort::Status, &cfg, &ops).unwrap().into_iter().map(|p| (p.slug, p.status)).collect::<Vec<_>>();
+    assert_eq!(statuses, [("alpha".into(), ProjectStatus::Active), ("gamma".into(), ProjectStatus::Paused), ("beta".into(), ProjectStatus::Done)]);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let mut out = Vec::new();
+        run_with(args(list), &mut io::empty(), &mut out, &mut Vec::new(), &ctx).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(run(&["set-status", "--project-root", "/w/project", "alpha", "done"]), "alpha: active -> done\n");
+    assert_eq!(run(&["list", "--project-root", "/w/project", "--status", "done"]), "alpha\nbeta\n");
+    assert_eq!(run(&["archive", "--project-root", "/w/project", "--status", "done"]), "/w/archive/alpha\n/w/archive/beta\n");
+    assert_eq!(run(&["list", "--project-root", "/w/project", "--sort", "status", "--archived"]), "gamma\tpaused\nalpha\tarchived\nbeta\tarchived\n");
+}
+
+#[derive(Debug, Default)]
+struct RecordingHooks {
+    calls: std::sync::Mutex<Vec<String>>,
+}
+
+impl hooks::Hooks for RecordingHooks {
+    fn run(&self, call: &hooks::HookCall) -> Result<()> {
+        let source = call.source.map(|s| format!(" from {}", s.display())).unwrap_or_default();
+        self.calls.lock().unwrap().push(format!("{} {} {}{source}", call.event.name(), call.slug, call.path.display()));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_hooks_run_after_create_archive_and_restore() {
+    let ops = MemoryFileOps::new().with_dir("/w/project");
+    let recorder = Arc::new(RecordingHooks::default());
+    let cfg = Config { project_root: "/w/project".into(), hooks: recorder.clone(), ..Config::default() };
+    let dir = create_project_dir_with("My Project", &cfg, &ops).unwrap();
+    let archived = archive_target(&dir, &cfg, &ops).unwrap().dest;
+    restore_target(&archived, None, &cfg, &ops).unwrap();
+    assert_eq!(*recorder.calls.lock().unwrap(), [
+        "post-create my-project /w/project/my-project",
+        "post-archive my-project /w/archive/my-project from /w/project/my-project",
+        "post-restore my-project /w/project/my-project from /w/archive/my-project",
+    ]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_script_hooks_get_the_change_in_their_environment() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("slugpm-hooks-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("log");
+    let hook = dir.join("post-archive");
+    std::fs::write(&hook, format!("#!/bin/sh\necho \"$SLUGPM_HOOK $SLUGPM_SLUG $SLUGPM_PATH $SLUGPM_SOURCE\" > {}\n", log.display())).unwrap();
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(dir.join("post-restore"), "#!/bin/sh\nexit 3\n").unwrap();
+    std::fs::write(dir.join("post-create"), "#!/bin/sh\nexit 3\n").unwrap();
+    std::fs::set_permissions(dir.join("post-create"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let hooks = hooks::ScriptHooks::new(&dir);
+    let call = |event, source| hooks::HookCall { event, slug: "alpha", path: Path::new("/w/archive/alpha"), source };
+    use hooks::Hooks;
+    hooks.run(&call(hooks::HookEvent::PostArchive, Some(Path::new("/w/project/alpha")))).unwrap();
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "post-archive alpha /w/archive/alpha /w/project/alpha\n");
+    // Not executable, so skipped.
+    hooks.run(&call(hooks::HookEvent::PostRestore, None)).unwrap();
+    assert!(hooks.run(&call(hooks::HookEvent::PostCreate, None)).unwrap_err().to_string().contains("post-create failed"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_report_renders_markdown_and_csv_tables() {
+    let modified = std::time::SystemTime::now();
+    let entry = |slug: &str, title: Option<&str>, tags: &[&str]| ProjectEntry {
+        slug: slug.into(), path: Path::new("/w/project").join(slug), modified, archived: false,
+        title: title.map(String::from), created: Some("2025-09-13".into()),
+        tags: tags.iter().map(|t| t.to_string()).collect(), status: status::ProjectStatus::Paused,
+    };
+    let entries = [entry("a-b", Some("A | \"B\", C"), &["home", "q3"]), entry("plain", None, &[])];
+    let stamp = chrono::DateTime::<chrono::Local>::from(modified).format("%Y-%m-%d %H:%M").to_string();
+
+    let md = report::render_report(&entries, report::ReportFormat::Md);
+    assert_eq!(md, format!("| Title | Slug | Created | Status | Tags | Modified |\n|---|---|---|---|---|---|\n\
+        | A \\| \"B\", C | a-b | 2025-09-13 | paused | home, q3 | {stamp} |\n\
+        |  | plain | 2025-09-13 | paused |  | {stamp} |\n"));
+
+    let csv = report::render_report(&entries, report::ReportFormat::Csv);
+    assert_eq!(csv, format!("Title,Slug,Created,Status,Tags,Modified\r\n\
+        \"A | \"\"B\"\", C\",a-b,2025-09-13,paused,\"home, q3\",{stamp}\r\n\
+        ,plain,2025-09-13,paused,,{stamp}\r\n"));
+}
+
+#[test]
+fn test_safe_path_rejects_adversarial_names() {
+    for name in ["", ".", "..", "../x", "x/..", "/etc", "/", "a/b", "a\\b", "..\\x", "x\0y", "a/", "./a"] {
+        assert!(matches!(safe_path::check_segment(name, "project"), Err(SlugpmError::InvalidName(_))), "{name:?}");
+    }
+    for name in ["a", "...", "..a", "a..b", ".hidden", "2025-09-13-x"] {
+        safe_path::check_segment(name, "project").unwrap();
+    }
+
+    let root = Path::new("/w/project");
+    safe_path::ensure_under(Path::new("/w/project/a"), root).unwrap();
+    safe_path::ensure_under(Path::new("/w/project/./a/../b"), root).unwrap();
+    for path in ["/w/project", "/w/project/..", "/w/project/a/../../b", "/w/projects/a", "/etc/passwd", "/w/project/../project2"] {
+        assert!(safe_path::ensure_under(Path::new(path), root).is_err(), "{path}");
+    }
+    assert!(safe_path::file_segment(Path::new("/w/project/..")).is_err());
+    assert!(safe_path::file_segment(Path::new("/")).is_err());
+}
+
+#[test]
+fn test_project_names_cannot_escape_the_project_root() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_dir("/w/secret");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    for slug in ["../secret", "/w/secret", "alpha/../../secret", ".."] {
+        assert!(matches!(resolve_project(slug, &cfg, &ops), Err(SlugpmError::InvalidName(_))), "{slug}");
+        assert!(matches!(rename_project(slug, "Stolen", false, &cfg, &ops), Err(SlugpmError::InvalidName(_))), "{slug}");
+    }
+    // Titles are slugified, so separators never reach the path.
+    assert_eq!(create_project_dir_with("../../etc/passwd", &cfg, &ops).unwrap(), Path::new("/w/project/etc-passwd"));
+
+    // A date format with a separator would nest the project, so it's refused.
+    let dated = Config { dated: true, date_format: "%Y/%m".into(), ..cfg.clone() };
+    assert!(matches!(create_project_dir_with("Nested", &dated, &ops), Err(SlugpmError::InvalidName(_))));
+    assert_eq!(ops.tree("/w/secret"), Vec::<String>::new(), "{:?}", ops.dirs());
+}
+
+#[derive(Debug, Default)]
+struct RecordingProgress(std::sync::Mutex<Vec<String>>);
+
+impl copy::Progress for RecordingProgress {
+    fn update(&self, p: &copy::CopyProgress) {
+        self.0.lock().unwrap().push(format!("{}/{} files {}/{} bytes", p.files, p.total_files, p.bytes, p.total_bytes));
+    }
+
+    fn finish(&self) {
+        self.0.lock().unwrap().push("finish".into());
+    }
+}
+
+#[test]
+fn test_cross_device_archive_reports_copy_progress() {
+    let ops = MemoryFileOps::new()
+        .with_file("/home/p/proj/a.txt", "aaaa")
+        .with_file("/home/p/proj/sub/b.txt", "bb")
+        .with_mount("/home/archive");
+    let progress = Arc::new(RecordingProgress::default());
+    let cfg = Config { progress: progress.clone(), ..Config::default() };
+    archive_dir(Path::new("/home/p/proj"), &cfg, &ops).unwrap();
+    assert_eq!(*progress.0.lock().unwrap(), [
+        "0/2 files 0/6 bytes",
+        "0/2 files 4/6 bytes",
+        "1/2 files 4/6 bytes",
+        "1/2 files 6/6 bytes",
+        "2/2 files 6/6 bytes",
+        "finish",
+    ]);
+
+    // A large file is copied, and reported, a chunk at a time.
+    let ops = ops.with_file("/home/p/big/data.bin", vec![7; COPY_CHUNK + 10]);
+    let progress = RecordingProgress::default();
+    copy::copy_tree(Path::new("/home/p/big"), Path::new("/home/archive/big"), &ops, &|| false, &progress).unwrap();
+    let total = COPY_CHUNK + 10;
+    assert_eq!(*progress.0.lock().unwrap(), [
+        format!("0/1 files 0/{total} bytes"),
+        format!("0/1 files {COPY_CHUNK}/{total} bytes"),
+        format!("0/1 files {total}/{total} bytes"),
+        format!("1/1 files {total}/{total} bytes"),
+        "finish".into(),
+    ]);
+    assert_eq!(ops.contents("/home/archive/big/data.bin").unwrap(), vec![7; total]);
+
+    // A plain rename copies nothing.
+    let ops = MemoryFileOps::new().with_file("/home/p/proj/a.txt", "a");
+    let progress = Arc::new(RecordingProgress::default());
+    archive_dir(Path::new("/home/p/proj"), &Config { progress: progress.clone(), ..Config::default() }, &ops).unwrap();
+    assert!(progress.0.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_archive_many_in_parallel_keeps_order_and_serializes_shared_archives() {
+    let mut ops = MemoryFileOps::new();
+    let mut targets = Vec::new();
+    for i in 0..12 {
+        ops = ops.with_file(format!("/d{i}/notes.txt"), format!("{i}"));
+        targets.push(std::path::PathBuf::from(format!("/d{i}/notes.txt")));
+    }
+    // Both projects are bound for /w/archive/proj, so they go one after another, in order.
+    ops = ops.with_file("/w/a/proj/x.txt", "a").with_file("/w/b/proj/x.txt", "b");
+    targets.insert(3, "/w/a/proj".into());
+    targets.push("/w/b/proj".into());
+    targets.insert(5, "/d0/missing.txt".into());
+
+    let cfg = Config { jobs: 4, on_conflict: Some(ConflictPolicy::Number), ..Config::default() };
+    let results = archive_many(&targets, &cfg, &ops);
+    assert_eq!(results.iter().map(|r| r.source.clone()).collect::<Vec<_>>(), targets);
+    assert!(results[5].error.as_deref().unwrap().contains("does not exist"));
+    assert_eq!(results.iter().filter(|r| r.error.is_some()).count(), 1);
+    for i in 0..12 {
+        assert_eq!(ops.contents(format!("/d{i}/archive/notes.txt")).unwrap(), format!("{i}").as_bytes());
+    }
+    assert_eq!(results[3].dest.as_deref(), Some(Path::new("/w/archive/proj")));
+    assert_eq!(results.last().unwrap().dest.as_deref(), Some(Path::new("/w/archive/proj-2")));
+    assert_eq!(ops.contents("/w/archive/proj-2/x.txt").unwrap(), b"b");
+}
+
+#[test]
+fn test_archive_jobs_must_be_positive() {
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let ops = MemoryFileOps::new().with_file("/p/a.txt", "a").with_file("/p/b.txt", "b");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let code = run_with(args(&["archive", "--jobs", "0", "/p/a.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, EXIT_INVALID_INPUT);
+    let code = run_with(args(&["archive", "-j", "2", "/p/a.txt", "/p/b.txt"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0, "{}", String::from_utf8_lossy(&err));
+    assert_eq!(ops.tree("/p/archive"), [".manifest.jsonl", "a.txt", "b.txt"]);
+}
+
+#[test]
+fn test_move_into_project() {
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/alpha")
+        .with_file("/in/notes.md", "n")
+        .with_file("/in/other/notes.md", "o")
+        .with_file("/in/ref.pdf", "pdf");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+
+    assert_eq!(move_into_project(Path::new("/in/notes.md"), "alpha", false, &cfg, &ops).unwrap(), Path::new("/w/project/alpha/notes.md"));
+    assert!(!ops.exists(Path::new("/in/notes.md")));
+    // Collisions are handled like in the archive.
+    assert!(matches!(move_into_project(Path::new("/in/other/notes.md"), "alpha", false, &cfg, &ops), Err(SlugpmError::Collision(_))));
+    let numbered = Config { on_conflict: Some(ConflictPolicy::Number), ..cfg.clone() };
+    assert_eq!(move_into_project(Path::new("/in/other/notes.md"), "alpha", false, &numbered, &ops).unwrap(), Path::new("/w/project/alpha/notes-2.md"));
+
+    // Subdirectories are created; a copy leaves the source.
+    assert_eq!(move_into_project(Path::new("/in/ref.pdf"), "alpha/refs/papers", true, &cfg, &ops).unwrap(), Path::new("/w/project/alpha/refs/papers/ref.pdf"));
+    assert_eq!(ops.contents("/in/ref.pdf").unwrap(), b"pdf");
+    assert!(matches!(move_into_project(Path::new("/in/ref.pdf"), "alpha/../beta", false, &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+    assert!(matches!(move_into_project(Path::new("/in/ref.pdf"), "nope", false, &cfg, &ops), Err(SlugpmError::NotFound(_))));
+    assert!(matches!(move_into_project(Path::new("/w/project/alpha"), "alpha/sub", false, &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(!ops.exists(Path::new("/w/project/alpha/refs/papers/ref.pdf")));
+    assert_eq!(ops.contents("/in/ref.pdf").unwrap(), b"pdf");
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/in/other/notes.md").unwrap(), b"o");
+}
+
+#[test]
+fn test_run_mv_prints_destinations() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/alpha").with_file("/in/a.md", "a").with_file("/in/b.md", "b");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["mv", "--project-root", "/w/project", "/in/a.md", "/in/b.md", "alpha"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0, "{}", String::from_utf8_lossy(&err));
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/project/alpha/a.md\n/w/project/alpha/b.md\n");
+}
+
+#[test]
+fn test_markdown_sections_round_trip() {
+    let docs = [
+        "",
+        "no headings\n",
+        "# Title\n\nintro\n\n## Log\n- a\n\n## Next\n",
+        "---\ntitle: x\n# not a heading\n---\n# Real\n",
+        "# A\n```\n# in code\n```\n~~~md\n## also code\n~~~\n## B ##\nno newline at end",
+        "# CRLF\r\n\r\nbody\r\n## Two\r\n",
+        "#not-a-heading\n    # indented code\n####### seven\n",
+    ];
+    for doc in docs {
+        assert_eq!(render_sections(&parse_sections(doc)), doc);
+    }
+    let headings: Vec<_> = parse_sections(docs[4]).into_iter().filter_map(|s| s.heading).map(|h| (h.level, h.text)).collect();
+    assert_eq!(headings, [(1, "A".to_string()), (2, "B".to_string())]);
+    assert!(parse_sections(docs[3]).iter().filter_map(|s| s.heading.as_ref()).map(|h| h.text.as_str()).eq(["Real"]));
+    assert!(parse_sections(docs[6]).iter().all(|s| s.heading.is_none()));
+    assert_eq!(parse_heading("## C#").unwrap().text, "C#");
+}
+
+#[test]
+fn test_insert_under_heading() {
+    let insert = |doc: &str, heading: &str, content: &str| {
+        let (at, text) = insert_under_heading(doc, heading, content).unwrap();
+        format!("{}{text}{}", &doc[..at], &doc[at..])
+    };
+    let doc = "# Notes\n\n## Log\n- a\n\n### Detail\nd\n\n## Next\nn\n";
+    // The section runs through deeper headings and ends before the blank lines.
+    assert_eq!(insert(doc, "## Log", "- b"), "# Notes\n\n## Log\n- a\n\n### Detail\nd\n- b\n\n## Next\nn\n");
+    assert_eq!(insert(doc, "### Detail", "e\n"), "# Notes\n\n## Log\n- a\n\n### Detail\nd\ne\n\n## Next\nn\n");
+    assert_eq!(insert(doc, "## Next ##", "m"), "# Notes\n\n## Log\n- a\n\n### Detail\nd\n\n## Next\nn\nm\n");
+    assert_eq!(insert("## Log\n", "## Log", "x"), "## Log\nx\n");
+    assert_eq!(insert("## Log\n- a", "## Log", "x"), "## Log\n- a\nx\n");
+    assert_eq!(insert("## Log\r\n- a\r\n\r\n# B\r\n", "## Log", "x"), "## Log\r\n- a\r\nx\r\n\r\n# B\r\n");
+    // A heading in a code block doesn't count; a missing one is added at the end.
+    assert_eq!(insert("```\n## Log\n```\n", "## Log", "x"), "```\n## Log\n```\n\n## Log\n\nx\n");
+    assert_eq!(insert("", "## Log", "x"), "## Log\n\nx\n");
+    assert_eq!(insert("# Log\ntext", "## Log", "x"), "# Log\ntext\n\n## Log\n\nx\n");
+    assert!(matches!(insert_under_heading("", "Log", "x"), Err(SlugpmError::InvalidName(_))));
+}
+
+#[test]
+fn test_archive_append_under_heading_can_be_undone() {
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project")
+        .with_file("/w/log.md", "")
+        .with_file("/w/archive/log.md", "# Log\n\n## Done\n- old\n\n## Ideas\n- idea\n");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, append_under: Some("## Done".into()), ..Config::default() };
+    archive_append_with(Path::new("/w/log.md"), &mut "- new\n".as_bytes(), &cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/archive/log.md").unwrap(), b"# Log\n\n## Done\n- old\n- new\n\n## Ideas\n- idea\n");
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/archive/log.md").unwrap(), b"# Log\n\n## Done\n- old\n\n## Ideas\n- idea\n");
+
+    // Into a new file, which undo removes again.
+    let ops = ops.with_file("/w/fresh.md", "");
+    archive_append_with(Path::new("/w/fresh.md"), &mut "x".as_bytes(), &cfg, &ops).unwrap();
+    assert_eq!(ops.contents("/w/archive/fresh.md").unwrap(), b"## Done\n\nx\n");
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(!ops.exists(Path::new("/w/archive/fresh.md")));
+}
+
+#[test]
+fn test_archive_message_is_noted_in_archive_md() {
+    let ops = MemoryFileOps::new()
+        .with_file("/p/notes.md", "n")
+        .with_file("/p/archive/notes.md", "older")
+        .with_file("/p/draft.md", "d");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = run_with(args(&["archive", "-m", "superseded\nby v2", "--on-conflict", "number", "/p/notes.md", "/p/draft.md"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(code, 0, "{}", String::from_utf8_lossy(&err));
+
+    let text = String::from_utf8(ops.contents("/p/archive/ARCHIVE.md").unwrap()).unwrap();
+    assert!(text.starts_with("# Archive\n\n## notes.md\n\n- archived: "), "{text}");
+    let notes = manifest::read_notes(Path::new("/p/archive"), &ops).unwrap();
+    assert_eq!(notes.iter().map(|n| (n.name.as_str(), n.path.as_path(), n.reason.as_str())).collect::<Vec<_>>(), [
+        ("notes.md", Path::new("notes-2.md"), "superseded by v2"),
+        ("draft.md", Path::new("draft.md"), "superseded by v2"),
+    ]);
+    assert_eq!(notes[0].from, Path::new("/p/notes.md"));
+    assert_eq!(manifest::parse_notes(&manifest::render_note(&notes[1])), [notes[1].clone()]);
+
+    // The manifest isn't an archived item.
+    let entries = prune::archive_entries(Path::new("/p/archive"), &ops).unwrap();
+    assert!(entries.iter().all(|(path, _)| !path.ends_with("ARCHIVE.md")));
+}
+
+#[test]
+fn test_archive_manifest_and_list() {
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/alpha")
+        .with_file("/w/project/beta/x.md", "x")
+        .with_file("/w/notes/todo.md", "t");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run_with(args(list), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+        assert_eq!(code, 0, "{}", String::from_utf8_lossy(&err));
+        String::from_utf8(out).unwrap()
+    };
+    run(&["archive", "--project-root", "/w/project", "--project", "alpha", "-m", "done"]);
+    run(&["archive", "--project-root", "/w/project", "--compress", "/w/project/beta"]);
+    run(&["archive", "--project-root", "/w/project", "/w/notes/todo.md"]);
+
+    let entries = manifest::read_manifest(Path::new("/w/archive"), &ops).unwrap();
+    assert_eq!(entries.iter().map(|e| (e.from.as_path(), e.path.as_path(), e.reason.as_deref(), e.dir, e.compressed)).collect::<Vec<_>>(), [
+        (Path::new("/w/project/alpha"), Path::new("alpha"), Some("done"), true, false),
+        (Path::new("/w/project/beta"), Path::new("beta.tar.zst"), None, true, true),
+    ]);
+
+    let listed = run(&["archive", "--project-root", "/w/project", "--list"]);
+    let rows: Vec<Vec<&str>> = listed.lines().map(|l| l.split('\t').skip(1).collect()).collect();
+    assert_eq!(rows, [vec!["/w/archive/alpha", "/w/project/alpha", "done"], vec!["/w/archive/beta.tar.zst", "/w/project/beta"]]);
+    let listed = run(&["archive", "--project-root", "/w/project", "--list", "/w/notes"]);
+    assert!(listed.ends_with("\t/w/notes/archive/todo.md\t/w/notes/todo.md\n"), "{listed}");
+    assert_eq!(run(&["archive", "--project-root", "/w/project", "--list", "/w/notes/archive"]), listed);
+
+    // Once an item is gone from the archive, it's no longer listed.
+    run(&["undo", "--project-root", "/w/project"]);
+    run(&["undo", "--project-root", "/w/project"]);
+    assert_eq!(run(&["archive", "--project-root", "/w/project", "--list"]).lines().count(), 1);
+
+    // Dry runs don't touch the manifest.
+    let ops = MemoryFileOps::new().with_file("/p/a.txt", "a");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    run_with(args(&["--dry-run", "archive", "/p/a.txt"]), &mut io::empty(), &mut Vec::new(), &mut Vec::new(), &ctx).unwrap();
+    assert!(!ops.exists(Path::new("/p/archive/.manifest.jsonl")));
+}
+
+#[test]
+fn test_restore_uses_the_manifest() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/deep/nested/notes.md", "n")
+        .with_file("/w/project/beta/x.md", "x")
+        .with_dir("/w/archive")
+        .with_file("/w/other/beta/y.md", "y");
+    let clock = Arc::new(FixedClock(NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(12, 0, 0).unwrap()));
+    let cfg = Config { project_root: "/w/project".into(), manifest: true, dated_archive: true, clock, ..Config::default() };
+
+    // Dated subdirectories would send the file to the wrong place without the manifest.
+    let archived = archive_target(Path::new("/w/deep/nested/notes.md"), &cfg, &ops).unwrap().dest;
+    assert_eq!(archived, Path::new("/w/deep/nested/archive/2025/2025-09-13/notes.md"));
+    assert_eq!(restore_target(&archived, None, &cfg, &ops).unwrap(), Path::new("/w/deep/nested/notes.md"));
+
+    // A compressed project renamed for a conflict comes back under its original name.
+    let compress = Config { compress: true, dated_archive: false, on_conflict: Some(ConflictPolicy::Number), ..cfg.clone() };
+    ops.write(Path::new("/w/archive/beta.tar.zst"), b"taken").unwrap();
+    let archived = archive_target(Path::new("/w/project/beta"), &compress, &ops).unwrap().dest;
+    assert_eq!(archived, Path::new("/w/archive/beta-2.tar.zst"));
+
+    // By name: the archive next to the project root is searched through its manifest.
+    assert_eq!(restore_target(Path::new("beta"), None, &cfg, &ops).unwrap(), Path::new("/w/project/beta"));
+    assert_eq!(ops.contents("/w/project/beta/x.md").unwrap(), b"x");
+    assert!(matches!(restore_target(Path::new("gamma"), None, &cfg, &ops), Err(SlugpmError::NotFound(_))));
+
+    // Two items with the same original name are ambiguous.
+    archive_target(Path::new("/w/other/beta"), &Config { dated_archive: false, ..cfg.clone() }, &ops).unwrap();
+    archive_target(Path::new("/w/project/beta"), &compress, &ops).unwrap();
+    assert!(matches!(restore_target(Path::new("beta"), None, &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+    assert_eq!(restore_target(Path::new("beta-2.tar.zst"), None, &cfg, &ops).unwrap(), Path::new("/w/project/beta"));
+    assert_eq!(restore_target(Path::new("beta"), None, &cfg, &ops).unwrap(), Path::new("/w/other/beta"));
+}
+
+#[test]
+fn test_configured_date_prefixes() {
+    let prefixes = date_prefix::DatePrefixes::new(&["%Y-%m-%d", "%Y%m%d", "%Y.%m.%d"]).unwrap();
+    assert_eq!(prefixes.strip("20250913_alpha"), "alpha");
+    assert_eq!(prefixes.strip("2025.09.13-beta"), "beta");
+    assert_eq!(prefixes.strip("2025-09-13-gamma"), "gamma");
+    assert_eq!(prefixes.strip("2025-delta"), "2025-delta");
+    assert_eq!(date_prefix::DatePrefixes::new(&[r"Q\d-\d{4}-"]).unwrap().strip("Q3-2025-plan"), "plan");
+    assert!(date_prefix::DatePrefixes::new(&["%Y%q"]).is_err());
+    assert!(date_prefix::DatePrefixes::new(&["(unclosed"]).is_err());
+
+    let file = config_file::parse_config_file("date_prefixes = [\"%Y%m%d\", \"%Y.%m.%d\"]\n").unwrap();
+    let cfg = config_file::resolve_config(&file, &Default::default(), None, &Default::default()).unwrap();
+    assert_eq!(cfg.date_prefixes.strip("20250913_alpha"), "alpha");
+    assert_eq!(cfg.date_prefixes.strip("2025-09-13-alpha"), "2025-09-13-alpha");
+    assert!(config_file::parse_config_file("date_prefixes = [\"%Q\"]\n").and_then(|f| f.apply(&mut Config::default())).is_err());
+
+    let ctx = Context { ops: &MockFileOps, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let input = "20250913_alpha\n2025.09.13-beta\n2025-09-13-gamma\n";
+    run_with(args(&["name", "--date-prefix", "%Y%m%d", "--date-prefix", "%Y.%m.%d"]), &mut input.as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "alpha\nbeta\n2025-09-13-gamma\n");
+}
+
+#[test]
+fn test_rename_keeps_configured_date_prefix() {
+    let ops = MemoryFileOps::new().with_dir("/p/20250913_old-name");
+    let cfg = Config { project_root: "/p".into(), date_prefixes: date_prefix::DatePrefixes::new(&["%Y%m%d"]).unwrap(), ..Config::default() };
+    assert_eq!(rename_project("20250913_old-name", "New Name", false, &cfg, &ops).unwrap(), Path::new("/p/20250913_new-name"));
+    assert_eq!(project_title(Path::new("/p/20250913_new-name"), &[], &cfg, &ops).unwrap(), "New Name");
+}
+
+#[test]
+fn test_parse_project_name_and_sort_by_date() {
+    let cfg = Config { date_prefixes: date_prefix::DatePrefixes::new(&["%Y-%m-%d", "%Y%m%d"]).unwrap(), ..Config::default() };
+    let parsed = parse_project_name("2025-09-13-MyProject", &cfg);
+    assert_eq!(parsed, ParsedName { date: NaiveDate::from_ymd_opt(2025, 9, 13), name: "MyProject".into(), slug: "myproject".into() });
+    assert_eq!(parse_project_name("20240102_notes", &cfg).date, NaiveDate::from_ymd_opt(2024, 1, 2));
+    assert_eq!(parse_project_name("plain", &cfg), ParsedName { date: None, name: "plain".into(), slug: "plain".into() });
+    // A prefix that isn't a real day is still stripped, but gives no date.
+    assert_eq!(parse_project_name("2025-13-45-odd", &cfg).date, None);
+    assert_eq!(parse_project_name("2025-13-45-odd", &cfg).name, "odd");
+    let ordinal = date_prefix::DatePrefixes::new(&["%Y.%j"]).unwrap();
+    assert_eq!(ordinal.split("2025.256-x"), (NaiveDate::from_ymd_opt(2025, 9, 13), "x"));
+
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/undated")
+        .with_dir("/w/project/2025-09-13-later")
+        .with_dir("/w/project/20240102_earlier");
+    let cfg = Config { project_root: "/w/project".into(), ..cfg };
+    let slugs: Vec<String> = list_projects(false, ProjectSort::Date, &cfg, &ops).unwrap().into_iter().map(|e| e.slug).collect();
+    assert_eq!(slugs, ["20240102_earlier", "2025-09-13-later", "undated"]);
+}
+
+#[test]
+fn test_init_sets_up_a_root_once() {
+    let ops = MemoryFileOps::new().with_dir("/w/archive");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    assert_eq!(run_with(args(&["init", "/w", "--templates"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap(), 0);
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/project\n/w/templates\n/w/.slugpm.toml\n");
+    assert!(ops.is_dir(Path::new("/w/project")) && ops.is_dir(Path::new("/w/templates")));
+    let file = config_file::parse_config_file(&ops.read_to_string(Path::new("/w/.slugpm.toml")).unwrap()).unwrap();
+    assert_eq!((file.project_root, file.archive_dir_name, file.templates_dir), (Some("project".into()), Some("archive".into()), Some("templates".into())));
+
+    let mut err = Vec::new();
+    assert_eq!(run_with(args(&["init", "/w"]), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap(), EXIT_COLLISION);
+    assert!(String::from_utf8(err).unwrap().contains(".slugpm.toml"));
+    let outcome = init::init_root(Path::new("/w"), false, true, &Config::default(), &ops).unwrap();
+    assert!(outcome.created.is_empty());
+    assert!(ops.read_to_string(&outcome.config).unwrap().contains("# templates_dir"));
+}
+
+#[test]
+fn test_find_root_walks_up_to_the_marker() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/.slugpm.toml", "project_root = \"project\"\ntemplates_dir = \"~/templates\"\ndated = true\n")
+        .with_dir("/w/project/alpha/notes/deep")
+        .with_file("/w/project/beta/.slugpm.toml", "")
+        .with_dir("/elsewhere/sub");
+    assert_eq!(config_file::find_root(Path::new("/w/project/alpha/notes/deep"), &ops).as_deref(), Some(Path::new("/w")));
+    assert_eq!(config_file::find_root(Path::new("/w"), &ops).as_deref(), Some(Path::new("/w")));
+    // The nearest marker wins over one further up.
+    assert_eq!(config_file::find_root(Path::new("/w/project/beta"), &ops).as_deref(), Some(Path::new("/w/project/beta")));
+    assert_eq!(config_file::find_root(Path::new("/elsewhere/sub"), &ops), None);
+
+    let root = config_file::load_root_config(Path::new("/w"), &ops).unwrap();
+    assert_eq!((root.project_root.as_deref(), root.templates_dir.as_deref()), (Some(Path::new("/w/project")), Some(Path::new("~/templates"))));
+    let global = config_file::parse_config_file("project_root = \"/global\"\ndated = false\ngit = true\n").unwrap();
+    let cfg = config_file::resolve_config(&global.layered(&root), &Default::default(), None, &Default::default()).unwrap();
+    assert_eq!((cfg.project_root.as_path(), cfg.dated, cfg.git), (Path::new("/w/project"), true, true));
+}
+
+#[test]
+fn test_numbered_projects() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/2025-09-13-dated");
+    let cfg = Config { project_root: "/w/project".into(), ids: true, ..Config::default() };
+    assert_eq!(project_dir_for("My Project", &cfg, &ops).unwrap(), Path::new("/w/project/0001-my-project"));
+    assert_eq!(create_project_dir_with("My Project", &cfg, &ops).unwrap(), Path::new("/w/project/0001-my-project"));
+    assert_eq!(create_project_dir_with("Other", &cfg, &ops).unwrap(), Path::new("/w/project/0002-other"));
+    let meta = metadata::read_metadata(Path::new("/w/project/0002-other"), &ops).unwrap().unwrap();
+    assert_eq!(meta.get(ids::ID_KEY), Some("2"));
+    assert_eq!(ops.read(Path::new("/w/project/.slugpm-ids")).unwrap(), b"\n\n");
+
+    assert_eq!(resolve_project("2", &cfg, &ops).unwrap(), Path::new("/w/project/0002-other"));
+    assert_eq!(find_project("0001", &cfg, &ops).unwrap(), Path::new("/w/project/0001-my-project"));
+    // A number no project has is not mistaken for a dated name.
+    assert!(resolve_project("2025", &cfg, &ops).is_err());
+
+    // A tally that fell behind skips the IDs projects already have.
+    ops.write(Path::new("/w/project/.slugpm-ids"), b"\n").unwrap();
+    assert_eq!(ids::next_id(&cfg, &ops).unwrap(), 3);
+    assert_eq!(create_project_dir_with("Third", &cfg, &ops).unwrap(), Path::new("/w/project/0003-third"));
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--project-root", "/w/project", "open", "3"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "/w/project/0003-third\n");
+
+    // Creating a name that exists under any ID collides, or is reused, without taking an ID.
+    let tally = ops.read(Path::new("/w/project/.slugpm-ids")).unwrap();
+    assert!(matches!(create_project_dir_with("Other", &cfg, &ops), Err(SlugpmError::Collision(dir)) if dir == Path::new("/w/project/0002-other")));
+    let reuse = Config { reuse: true, ..cfg.clone() };
+    assert_eq!(create_project_dir_with("Other", &reuse, &ops).unwrap(), Path::new("/w/project/0002-other"));
+    assert_eq!(project_dir_for("Other", &cfg, &ops).unwrap(), Path::new("/w/project/0002-other"));
+    assert_eq!(ops.read(Path::new("/w/project/.slugpm-ids")).unwrap(), tally);
+
+    // Renaming keeps the ID, and reports the directory the ID named.
+    assert_eq!(rename_project("0001-my-project", "Bar", false, &cfg, &ops).unwrap(), Path::new("/w/project/0001-bar"));
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--project-root", "/w/project", "--json", "rename", "2", "Renamed"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!((&record["source"], &record["dest"]), (&serde_json::json!("/w/project/0002-other"), &serde_json::json!("/w/project/0002-renamed")));
+}
+
+#[test]
+fn test_concurrent_creates_get_distinct_ids() {
+    let ops = MemoryFileOps::new().with_dir("/w/project");
+    let cfg = Config { project_root: "/w/project".into(), ..Config::default() };
+    let mut ids: Vec<u64> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8).map(|_| scope.spawn(|| ids::allocate_id(&cfg, &ops).unwrap())).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    ids.sort();
+    assert_eq!(ids, (1..=8).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_link_projects_both_ways() {
+    let ops = MemoryFileOps::new().with_dir("/w/project/compiler").with_dir("/w/project/parser").with_dir("/w/project/harness");
+    let cfg = Config { project_root: "/w/project".into(), journal: true, ..Config::default() };
+    let link = links::link_projects("parser", "compiler", links::LinkKind::SpunOffFrom, &cfg, &ops).unwrap();
+    assert_eq!(link.to_string(), "spun-off-from:compiler");
+    links::link_projects("parser", "harness", links::LinkKind::BlockedBy, &cfg, &ops).unwrap();
+    // Linking again changes nothing.
+    links::link_projects("compiler", "parser", links::LinkKind::SpunOffInto, &cfg, &ops).unwrap();
+
+    let meta = metadata::read_metadata(Path::new("/w/project/parser"), &ops).unwrap().unwrap();
+    assert_eq!(meta.get("links"), Some("spun-off-from:compiler, blocked-by:harness"));
+    let kinds = |slug| links::project_links(slug, &cfg, &ops).unwrap().into_iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    assert_eq!(kinds("compiler"), ["spun-off-into:parser"]);
+    assert_eq!(kinds("harness"), ["blocks:parser"]);
+    assert_eq!(links::parse_links("other, related:x, odd:y"), [
+        links::Link { kind: links::LinkKind::Related, slug: "other".into() },
+        links::Link { kind: links::LinkKind::Related, slug: "x".into() },
+        links::Link { kind: links::LinkKind::Related, slug: "odd:y".into() },
+    ]);
+
+    assert!(matches!(links::link_projects("parser", "missing", Default::default(), &cfg, &ops), Err(SlugpmError::NotFound(_))));
+    assert!(matches!(links::link_projects("parser", "parser", Default::default(), &cfg, &ops), Err(SlugpmError::InvalidName(_))));
+
+    // One undo takes the link back out of both projects.
+    journal::undo_last(&cfg, &ops).unwrap();
+    assert!(kinds("harness").is_empty());
+    assert_eq!(kinds("parser"), ["spun-off-from:compiler"]);
+
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["--project-root", "/w/project", "links", "compiler"]), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "spun-off-into\tparser\n");
+}
+
+#[test]
+fn test_watch_rules_file_the_inbox() {
+    use chrono::TimeZone;
+    let rules: Vec<watch::WatchRule> = ["ext:PDF,epub -> file:reading", "name:Screenshot* -> file:reading/shots", "age:2w -> archive"]
+        .iter().map(|spec| watch::parse_rule(spec).unwrap()).collect();
+    let hour = std::time::Duration::from_secs(3600);
+    let name_of = |name: &str, age| watch::rule_for(&rules, name, false, age).map(|r| r.spec.as_str());
+    assert_eq!(name_of("paper.pdf", hour), Some("ext:PDF,epub -> file:reading"));
+    assert_eq!(name_of("Screenshot 1.png", hour), Some("name:Screenshot* -> file:reading/shots"));
+    assert_eq!(name_of("notes.txt", hour), None);
+    assert_eq!(name_of("notes.txt", hour * 24 * 15), Some("age:2w -> archive"));
+    assert!(!rules[0].matches("book.epub", true, hour));
+    for bad in ["ext:pdf", "what:x -> archive", "ext: -> archive", "* -> file:", "* -> delete", "age:soon -> archive"] {
+        assert!(watch::parse_rule(bad).is_err(), "{bad}");
+    }
+
+    let now = chrono::NaiveDate::from_ymd_opt(2025, 9, 13).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let at = |secs_ago: u64| std::time::SystemTime::from(chrono::Local.from_local_datetime(&now).unwrap()) - std::time::Duration::from_secs(secs_ago);
+    let ops = MemoryFileOps::new()
+        .with_dir("/w/project/reading")
+        .with_file("/w/inbox/paper.pdf", "pdf").with_modified("/w/inbox/paper.pdf", at(60))
+        .with_file("/w/inbox/fresh.pdf", "pdf").with_modified("/w/inbox/fresh.pdf", at(0))
+        .with_file("/w/inbox/old.txt", "old").with_modified("/w/inbox/old.txt", at(3600 * 24 * 30))
+        .with_file("/w/inbox/.hidden.pdf", "").with_modified("/w/inbox/.hidden.pdf", at(60))
+        .with_file("/w/inbox/keep.txt", "").with_modified("/w/inbox/keep.txt", at(60));
+    let cfg = Config { project_root: "/w/project".into(), clock: Arc::new(FixedClock(now)), ..Config::default() };
+    let filed = watch::file_inbox(Path::new("/w/inbox"), &rules, std::time::Duration::from_secs(2), &cfg, &ops).unwrap();
+    let moved: Vec<(&Path, &Path)> = filed.iter().map(|f| (f.source.as_path(), f.result.as_ref().unwrap().as_path())).collect();
+    assert_eq!(moved, [
+        (Path::new("/w/inbox/old.txt"), Path::new("/w/inbox/archive/old.txt")),
+        (Path::new("/w/inbox/paper.pdf"), Path::new("/w/project/reading/paper.pdf")),
+    ]);
+    // The archive directory, dotfiles, unmatched and still-settling items stay.
+    assert!(ops.exists(Path::new("/w/inbox/fresh.pdf")) && ops.exists(Path::new("/w/inbox/keep.txt")) && ops.exists(Path::new("/w/inbox/.hidden.pdf")));
+    assert!(watch::file_inbox(Path::new("/w/inbox"), &rules, std::time::Duration::from_secs(2), &cfg, &ops).unwrap().is_empty());
+}
+
+#[test]
+fn test_filing_rules_in_order() {
+    let file = config_file::parse_config_file("[rules]\n\"report-*.pdf\" = \"work/reports\"\n\"*.pdf\" = \"reading\"\n\"notes-??.txt\" = \"notes\"\n").unwrap();
+    let table = file.rules.clone().unwrap();
+    assert_eq!(table.0.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(), ["report-*.pdf", "*.pdf", "notes-??.txt"]);
+    let cfg = config_file::resolve_config(&file, &Default::default(), None, &Default::default()).unwrap();
+    let dest = |name: &str| rules::rule_for(&cfg.rules, Path::new(name)).map(|r| r.dest.as_str());
+    assert_eq!(dest("/in/report-q3.pdf"), Some("work/reports"));
+    assert_eq!(dest("/in/paper.pdf"), Some("reading"));
+    assert_eq!(dest("/in/notes-01.txt"), Some("notes"));
+    assert_eq!(dest("/in/notes-1.txt"), None);
+    assert_eq!(dest("/in/paper.PDF"), None);
+
+    assert!(rules::shadows("*", "*.pdf"));
+    assert!(rules::shadows("*.pdf", "report-*.pdf"));
+    assert!(!rules::shadows("report-*", "*.pdf"));
+    assert!(!rules::shadows("a?c", "a*c"));
+    let conflict = |pairs: &[(&str, &str)]| rules::parse_rules(&rules::RuleTable(pairs.iter().map(|(p, d)| (p.to_string(), d.to_string())).collect()));
+    let err = conflict(&[("*.pdf", "reading"), ("report-*.pdf", "work")]).unwrap_err();
+    assert!(err.to_string().contains("\"*.pdf\" comes first"), "{err}");
+    assert!(conflict(&[("docs/*.pdf", "reading")]).is_err());
+    assert!(conflict(&[("*.pdf", "")]).is_err());
+    assert!(config_file::parse_config_file("[rules]\n\"*\" = \"inbox\"\n\"*.pdf\" = \"reading\"\n").and_then(|f| f.apply(&mut Config::default())).is_err());
+
+    let ops = MemoryFileOps::new().with_dir("/w/project/reading").with_dir("/w/project/work")
+        .with_file("/in/report-q3.pdf", "r").with_file("/in/paper.pdf", "p").with_file("/in/misc.txt", "m");
+    let cfg = Config { project_root: "/w/project".into(), ..cfg };
+    let (rule, to) = rules::file_by_rules(Path::new("/in/report-q3.pdf"), false, &cfg, &ops).unwrap();
+    assert_eq!((rule.pattern.as_str(), to.as_path()), ("report-*.pdf", Path::new("/w/project/work/reports/report-q3.pdf")));
+    assert_eq!(rules::file_by_rules(Path::new("/in/paper.pdf"), true, &cfg, &ops).unwrap().1, Path::new("/w/project/reading/paper.pdf"));
+    assert!(ops.exists(Path::new("/in/paper.pdf")));
+    assert!(matches!(rules::file_by_rules(Path::new("/in/misc.txt"), false, &cfg, &ops), Err(SlugpmError::NotFound(_))));
+}
+
+#[test]
+fn test_skip_duplicate_appends() {
+    let ops = MemoryFileOps::new().with_dir("/p");
+    let cfg = Config { skip_duplicate: true, ..Config::default() };
+    let append = |text: &str| archive_append_outcome(Path::new("/p/log.txt"), &mut text.as_bytes(), &cfg, &ops).unwrap();
+    assert!(!append("first\n").skipped);
+    // The same payload piped again is the last thing in the file.
+    assert_eq!(append("first\n"), AppendOutcome { dest: "/p/archive/log.txt".into(), skipped: true });
+    assert!(!append("second\n").skipped);
+    // An earlier block is found through the hash index.
+    assert!(append("first\n").skipped);
+    assert_eq!(ops.read_to_string(Path::new("/p/archive/log.txt")).unwrap(), "first\nsecond\n");
+    assert_eq!(ops.read_to_string(&integrity::block_index_path(Path::new("/p/archive/log.txt"))).unwrap().lines().count(), 2);
+    assert_eq!(integrity::block_index_path(Path::new("/p/archive/log.txt")), Path::new("/p/archive/.log.txt.blocks"));
+    // Without the flag, duplicates are appended as before.
+    archive_append_with(Path::new("/p/log.txt"), &mut "second\n".as_bytes(), &Config::default(), &ops).unwrap();
+    assert_eq!(ops.read_to_string(Path::new("/p/archive/log.txt")).unwrap(), "first\nsecond\nsecond\n");
+
+    let ctx = Context { ops: &ops, stdin_is_tty: false };
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    run_with(args(&["archive", "--append", "--skip-duplicate", "/p/log.txt"]), &mut "second\n".as_bytes(), &mut out, &mut err, &ctx).unwrap();
+    assert_eq!(String::from_utf8(err).unwrap(), "skipped duplicate content for /p/archive/log.txt\n");
+    assert_eq!(ops.read_to_string(Path::new("/p/archive/log.txt")).unwrap(), "first\nsecond\nsecond\n");
+    let mut err = Vec::new();
+    assert_eq!(run_with(args(&["archive", "--skip-duplicate", "/p/log.txt"]), &mut io::empty(), &mut Vec::new(), &mut err, &ctx).unwrap(), EXIT_FAILURE);
+}
+
+#[test]
+fn test_verify_archived_hashes() {
+    let ops = MemoryFileOps::new()
+        .with_file("/w/project/alpha/notes.md", "n")
+        .with_file("/w/project/alpha/src/main.rs", "fn main() {}")
+        .with_file("/w/project/todo.md", "t");
+    let ctx = Context { ops: &ops, stdin_is_tty: true };
+    let run = |list: &[&str]| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run_with(args(list), &mut io::empty(), &mut out, &mut err, &ctx).unwrap();
+        (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+    };
+    run(&["archive", "--project-root", "/w/project", "--project", "alpha"]);
+    run(&["archive", "--project-root", "/w/project", "/w/project/todo.md"]);
+
+    let entries = manifest::read_manifest(Path::new("/w/archive"), &ops).unwrap();
+    assert_eq!(entries[0].files.keys().collect::<Vec<_>>(), ["notes.md", "src/main.rs"]);
+    assert_eq!(entries[0].files["notes.md"], integrity::sha256_hex(b"n"));
+    assert_eq!(entries[0].sha256, None);
+    let entries = manifest::read_manifest(Path::new("/w/project/archive"), &ops).unwrap();
+    assert_eq!(entries[0].sha256.as_deref(), Some(integrity::sha256_hex(b"t").as_str()));
+    assert_eq!(integrity::sha256_reader(&b"t"[..]).unwrap(), integrity::sha256_hex(b"t"));
+
+    assert_eq!(run(&["verify", "--project-root", "/w/project"]), (0, String::new(), "1 item(s) checked, 0 with problems, 0 without recorded hashes\n".into()));
+    ops.write(Path::new("/w/archive/alpha/src/main.rs"), b"fn main() { evil() }").unwrap();
+    ops.remove_file(Path::new("/w/archive/alpha/notes.md")).unwrap();
+    ops.write(Path::new("/w/archive/alpha/extra.md"), b"e").unwrap();
+    let (code, out, _) = run(&["verify", "--project-root", "/w/project", "/w"]);
+    assert_eq!(code, EXIT_FAILURE);
+    assert_eq!(out, "added\t/w/archive/alpha/extra.md\nmissing\t/w/archive/alpha/notes.md\nchanged\t/w/archive/alpha/src/main.rs\n");
+    ops.write(Path::new("/w/project/archive/todo.md"), b"T").unwrap();
+    let (code, out, _) = run(&["--json", "verify", "/w/project/archive"]);
+    assert_eq!(code, EXIT_FAILURE);
+    let record: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+    assert_eq!(record["problem"], "changed");
+    assert_eq!(record["path"], "/w/project/archive/todo.md");
+
+    // An item that's gone is missing, unless it was restored; undoing the restore brings it back.
+    ops.remove_dir_all(Path::new("/w/archive/alpha")).unwrap();
+    assert_eq!(run(&["verify", "--project-root", "/w/project", "/w"]).1, "missing\t/w/archive/alpha\n");
+    assert_eq!(run(&["restore", "--project-root", "/w/project", "/w/project/archive/todo.md"]).0, 0);
+    assert_eq!(run(&["verify", "/w/project/archive"]), (0, String::new(), "0 item(s) checked, 0 with problems, 0 without recorded hashes\n".into()));
+    assert_eq!(run(&["undo", "--project-root", "/w/project"]).0, 0);
+    assert_eq!(run(&["verify", "/w/project/archive"]).1, "changed\t/w/project/archive/todo.md\n");
+
+    // Entries written before hashes were recorded can't be checked, and don't fail.
+    let ops = MemoryFileOps::new()
+        .with_file("/p/archive/old.md", "o")
+        .with_file("/p/archive/.manifest.jsonl", "{\"from\":\"/p/old.md\",\"path\":\"old.md\",\"archived_at\":\"2025-01-01T00:00:00\"}\n");
+    let verified = integrity::verify_archive(Path::new("/p/archive"), &ops).unwrap();
+    assert_eq!(verified, [integrity::Verified { path: "/p/archive/old.md".into(), recorded: false, problems: Vec::new() }]);
+}